@@ -0,0 +1,33 @@
+/* C interface of ormseparatev3, build with:
+ *   cargo rustc --release --lib --features ffi --crate-type cdylib
+ * Functions return 0 or a pointer on success, -1 or NULL on failure with the message
+ * available from ormsep_last_error(). All strings are UTF-8. */
+#ifndef ORMSEP_H
+#define ORMSEP_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct OrmsepConfig OrmsepConfig;
+
+/* Receives each log line of a processed file, user_data is passed through unchanged */
+typedef void (*ormsep_progress_fn)(const char *message, void *user_data);
+
+/* Load a config.toml, relative LUT paths resolve against its folder */
+OrmsepConfig *ormsep_load_config(const char *path);
+void ormsep_free_config(OrmsepConfig *config);
+
+/* Split one image next to itself. profile may be NULL for the config's default profile
+ * and progress may be NULL */
+int ormsep_process_file(const OrmsepConfig *config, const char *profile, const char *path,
+                        ormsep_progress_fn progress, void *user_data);
+
+/* Message of the last failed call on this thread, valid until the next failure */
+const char *ormsep_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
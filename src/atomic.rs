@@ -0,0 +1,37 @@
+// Outputs are written to a temporary file next to their destination and renamed into place, so
+// a crash, Ctrl-C or full disk never leaves a truncated file where an importer would find it.
+// Temporary names start with a dot, so directory walks skip them like other hidden files
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Result;
+
+// Makes temporary names unique between threads of this process
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{}.{}-{}.tmp", name, std::process::id(), id))
+}
+
+pub fn write(path: &Path, data: impl AsRef<[u8]>) -> io::Result<()> {
+    let temp = temp_path(path);
+    let result = fs::write(&temp, data).and_then(|_| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+// For writers that need a path of their own to write to, like the layered formats
+pub fn write_with(path: &Path, write: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    let temp = temp_path(path);
+    let result = write(&temp).and_then(|_| Ok(fs::rename(&temp, path)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
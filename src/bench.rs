@@ -0,0 +1,129 @@
+// Timing of the decode, extract and encode stages of a profile, to compare thread counts and options
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, anyhow, Context};
+use rayon::prelude::*;
+
+use crate::generate::write_test_images;
+use crate::report::StageTimes;
+use crate::{
+    BitDepth, FileMatcher, Profile, encode_image, extract_page, image_dimensions, matching_files, open_pages,
+    output_extension, write_layered,
+};
+
+// Corpus used when no inputs are given: every test pattern at this size in 8 and 16 bit
+const GENERATED_SIZE: u32 = 1024;
+
+// Process the inputs (or a generated corpus) iterations times per thread count. Outputs are
+// encoded in memory and never written, so disk speed only shows up in decoding
+pub fn bench(inputs: &[PathBuf], iterations: usize, threads: &[usize], profile: &Profile) -> Result<()> {
+    if iterations == 0 {
+        return Err(anyhow!("Run at least one iteration"));
+    }
+    let threads = if threads.is_empty() {
+        let available = std::thread::available_parallelism().map_or(1, |count| count.get());
+        if available > 1 { vec![1, available] } else { vec![1] }
+    } else {
+        threads.to_vec()
+    };
+    if threads.contains(&0) {
+        return Err(anyhow!("Thread counts must be at least 1"));
+    }
+    
+    let corpus_dir = std::env::temp_dir().join(format!("ormseparatev3-bench-{}", std::process::id()));
+    let files = if inputs.is_empty() {
+        println!("No inputs given, generating test images at {}x{}", GENERATED_SIZE, GENERATED_SIZE);
+        write_test_images(&corpus_dir, &[(GENERATED_SIZE, GENERATED_SIZE)], &[BitDepth::Eight, BitDepth::Sixteen], false, 1, profile)?
+    } else {
+        let matcher = FileMatcher::new(profile)?;
+        let mut files = Vec::new();
+        for input in inputs {
+            if input.is_dir() {
+                files.extend(matching_files(input, profile, &matcher));
+            } else {
+                files.push(input.clone());
+            }
+        }
+        files
+    };
+    
+    let result = run(&files, iterations, &threads, profile);
+    if inputs.is_empty() {
+        let _ = fs::remove_dir_all(&corpus_dir);
+    }
+    result
+}
+
+fn run(files: &[PathBuf], iterations: usize, threads: &[usize], profile: &Profile) -> Result<()> {
+    if files.is_empty() {
+        return Err(anyhow!("No files to benchmark"));
+    }
+    let mut megapixels = 0.0;
+    for file in files {
+        let (width, height) = image_dimensions(file)
+            .with_context(|| format!("Failed to read image dimensions: {}", file.display()))?;
+        megapixels += width as f64 * height as f64 / 1_000_000.0;
+    }
+    
+    println!("Benchmarking {} files ({:.1} MPix) with profile '{}', {} iterations", files.len(), megapixels, profile.name, iterations);
+    println!("Stage times are summed over all threads and averaged per iteration");
+    println!();
+    println!("Threads  Best wall  Mean wall   Files/s    MPix/s     Decode    Extract     Encode");
+    
+    for &count in threads {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(count).build()?;
+        let (mut best, mut total_wall, mut stages) = (Duration::MAX, Duration::ZERO, StageTimes::default());
+        for _ in 0..iterations {
+            let started = Instant::now();
+            let times = pool.install(|| files.par_iter().map(|file| bench_file(file, profile)).collect::<Result<Vec<_>>>())?;
+            let wall = started.elapsed();
+            best = best.min(wall);
+            total_wall += wall;
+            times.into_iter().for_each(|times| stages += times);
+        }
+        
+        let per_iteration = |duration: Duration| duration.as_secs_f64() / iterations as f64;
+        let best_secs = best.as_secs_f64().max(f64::EPSILON);
+        println!("{:>7} {:>9.3}s {:>9.3}s {:>9.1} {:>9.1} {:>9.3}s {:>9.3}s {:>9.3}s",
+            count, best.as_secs_f64(), per_iteration(total_wall), files.len() as f64 / best_secs, megapixels / best_secs,
+            per_iteration(stages.decode), per_iteration(stages.extract), per_iteration(stages.encode));
+    }
+    
+    Ok(())
+}
+
+// Split one file the way process_file does, timing each stage
+fn bench_file(file: &Path, profile: &Profile) -> Result<StageTimes> {
+    let mut times = StageTimes::default();
+    
+    let started = Instant::now();
+    let max_alloc = profile.max_alloc();
+    let pages = open_pages(file, max_alloc, profile.psd_layer.as_ref())
+        .with_context(|| format!("Failed to open image: {}", file.display()))?;
+    times.decode = started.elapsed();
+    
+    for img in &pages {
+        let started = Instant::now();
+        let (outputs, layers) = extract_page(img, profile);
+        times.extract += started.elapsed();
+        
+        let started = Instant::now();
+        for (channel, format, channel_img) in &outputs {
+            encode_image(channel_img, format, profile, channel.color_space, None)
+                .with_context(|| format!("Failed to encode channel {} of {}", channel.name, file.display()))?;
+        }
+        // Layered writers need a file, one per worker thread so they don't collide
+        if !layers.is_empty() {
+            let path = std::env::temp_dir().join(format!("ormseparatev3-bench-{}-{}.{}",
+                std::process::id(), rayon::current_thread_index().unwrap_or(0), output_extension(&profile.output_format)));
+            let result = write_layered(&path, &layers, &profile.output_format);
+            let _ = fs::remove_file(&path);
+            result.with_context(|| format!("Failed to write layers of {}", file.display()))?;
+        }
+        times.encode += started.elapsed();
+    }
+    
+    Ok(times)
+}
@@ -0,0 +1,39 @@
+// Cap on the memory of files processed at the same time (max_inflight_memory_mb). Workers wait
+// for room before decoding, so big textures queue up while small ones keep the other cores busy
+use std::sync::{Condvar, Mutex};
+
+pub struct MemoryBudget {
+    limit: u64,
+    in_use: Mutex<u64>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: u64) -> Self {
+        MemoryBudget { limit, in_use: Mutex::new(0), released: Condvar::new() }
+    }
+    
+    // Wait until bytes fit next to what's in flight. A file larger than the whole budget runs once
+    // nothing else does, instead of never
+    pub fn reserve(&self, bytes: u64) -> Reservation<'_> {
+        let mut in_use = self.in_use.lock().unwrap();
+        while *in_use > 0 && *in_use + bytes > self.limit {
+            in_use = self.released.wait(in_use).unwrap();
+        }
+        *in_use += bytes;
+        Reservation { budget: self, bytes }
+    }
+}
+
+// Returned to the budget when dropped
+pub struct Reservation<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        *self.budget.in_use.lock().unwrap() -= self.bytes;
+        self.budget.released.notify_all();
+    }
+}
@@ -0,0 +1,85 @@
+// Stopping a run early. Cancelled runs start no new files; files in flight stop before their next
+// channel, so every output that was written is complete
+use std::sync::Arc;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Flag set by the Ctrl-C handler. Signal handlers can't capture, so the token lives here
+static CTRL_C: OnceLock<CancelToken> = OnceLock::new();
+
+// Cancel token on the first Ctrl-C. The handler is removed at the same time, so a second Ctrl-C
+// ends the process immediately
+pub fn cancel_on_ctrl_c(token: &CancelToken) {
+    if CTRL_C.set(token.clone()).is_ok() {
+        install_handler();
+    }
+}
+
+fn cancel_from_handler() {
+    if let Some(token) = CTRL_C.get() {
+        token.cancel();
+    }
+}
+
+#[cfg(unix)]
+fn install_handler() {
+    use std::ffi::c_int;
+    
+    const SIGINT: c_int = 2;
+    const SIG_DFL: usize = 0;
+    
+    unsafe extern "C" {
+        fn signal(signum: c_int, handler: usize) -> usize;
+    }
+    
+    // Only an atomic store and signal(), both safe in a signal handler
+    extern "C" fn handler(_: c_int) {
+        cancel_from_handler();
+        unsafe { signal(SIGINT, SIG_DFL) };
+    }
+    
+    unsafe { signal(SIGINT, handler as extern "C" fn(c_int) as usize) };
+}
+
+#[cfg(windows)]
+fn install_handler() {
+    const CTRL_C_EVENT: u32 = 0;
+    const CTRL_BREAK_EVENT: u32 = 1;
+    
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn SetConsoleCtrlHandler(handler: Option<unsafe extern "system" fn(u32) -> i32>, add: i32) -> i32;
+    }
+    
+    // Runs on its own thread. Returning 0 hands the event on to the default handler, which exits
+    static HANDLED: AtomicBool = AtomicBool::new(false);
+    unsafe extern "system" fn handler(event: u32) -> i32 {
+        if !matches!(event, CTRL_C_EVENT | CTRL_BREAK_EVENT) || HANDLED.swap(true, Ordering::Relaxed) {
+            return 0;
+        }
+        cancel_from_handler();
+        1
+    }
+    
+    unsafe { SetConsoleCtrlHandler(Some(handler), 1) };
+}
+
+#[cfg(not(any(unix, windows)))]
+fn install_handler() {}
@@ -0,0 +1,215 @@
+// Per-channel image comparison, to check that a round trip or encoder change kept the data intact
+use std::path::Path;
+
+use anyhow::{Result, anyhow, Context};
+use image::{DynamicImage, Rgba, Rgba32FImage};
+use walkdir::WalkDir;
+
+use crate::{Profile, encode_image, is_hidden, open_pages, relative_path};
+
+// SSIM is averaged over windows of this size moved by half a window, with the usual constants for 0-1 data
+const SSIM_WINDOW: u32 = 8;
+const SSIM_C1: f64 = 0.01 * 0.01;
+const SSIM_C2: f64 = 0.03 * 0.03;
+
+pub struct ChannelMetrics {
+    // Infinite for identical channels
+    pub psnr: f64,
+    pub ssim: f64,
+    // Largest absolute difference, in 0-255 units
+    pub max_diff: f32,
+}
+
+// Compare the given channels of two images of the same size, values are taken as 0-1
+pub fn compare(a: &Rgba32FImage, b: &Rgba32FImage, channels: &[usize]) -> Result<Vec<ChannelMetrics>> {
+    if a.dimensions() != b.dimensions() {
+        return Err(anyhow!("Sizes differ: {}x{} and {}x{}", a.width(), a.height(), b.width(), b.height()));
+    }
+    
+    Ok(channels.iter().map(|&channel| {
+        let (mut squared, mut max_diff) = (0.0f64, 0.0f32);
+        for (pixel_a, pixel_b) in a.pixels().zip(b.pixels()) {
+            let diff = pixel_a[channel] - pixel_b[channel];
+            squared += diff as f64 * diff as f64;
+            max_diff = max_diff.max(diff.abs());
+        }
+        let mse = squared / (a.width() as f64 * a.height() as f64).max(1.0);
+        let psnr = if mse == 0.0 { f64::INFINITY } else { 10.0 * (1.0 / mse).log10() };
+        ChannelMetrics { psnr, ssim: ssim(a, b, channel), max_diff: max_diff * 255.0 }
+    }).collect())
+}
+
+fn ssim(a: &Rgba32FImage, b: &Rgba32FImage, channel: usize) -> f64 {
+    // Images smaller than a window are compared as a single window
+    let window_width = SSIM_WINDOW.min(a.width());
+    let window_height = SSIM_WINDOW.min(a.height());
+    let step_x = (window_width / 2).max(1);
+    let step_y = (window_height / 2).max(1);
+    
+    let (mut total, mut windows) = (0.0, 0);
+    for top in (0..=a.height() - window_height).step_by(step_y as usize) {
+        for left in (0..=a.width() - window_width).step_by(step_x as usize) {
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0f64, 0.0f64, 0.0f64, 0.0f64, 0.0f64);
+            for y in top..top + window_height {
+                for x in left..left + window_width {
+                    let value_a = a.get_pixel(x, y)[channel] as f64;
+                    let value_b = b.get_pixel(x, y)[channel] as f64;
+                    sum_a += value_a;
+                    sum_b += value_b;
+                    sum_aa += value_a * value_a;
+                    sum_bb += value_b * value_b;
+                    sum_ab += value_a * value_b;
+                }
+            }
+            let count = (window_width * window_height) as f64;
+            let (mean_a, mean_b) = (sum_a / count, sum_b / count);
+            let variance_a = sum_aa / count - mean_a * mean_a;
+            let variance_b = sum_bb / count - mean_b * mean_b;
+            let covariance = sum_ab / count - mean_a * mean_b;
+            total += ((2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+                / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1) * (variance_a + variance_b + SSIM_C2));
+            windows += 1;
+        }
+    }
+    
+    if windows == 0 { 1.0 } else { total / windows as f64 }
+}
+
+// Channels worth comparing, by name and index into the RGBA conversion: gray (and alpha),
+// RGB or RGBA depending on what either image has
+pub fn channels(a: &DynamicImage, b: &DynamicImage) -> &'static [(&'static str, usize)] {
+    let has_alpha = a.color().has_alpha() || b.color().has_alpha();
+    let is_gray = a.color().channel_count() < 3 && b.color().channel_count() < 3;
+    match (is_gray, has_alpha) {
+        (true, false) => &[("Gray", 0)],
+        (true, true) => &[("Gray", 0), ("Alpha", 3)],
+        (false, false) => &[("R", 0), ("G", 1), ("B", 2)],
+        (false, true) => &[("R", 0), ("G", 1), ("B", 2), ("A", 3)],
+    }
+}
+
+pub fn metrics(a: &DynamicImage, b: &DynamicImage) -> Result<Vec<(&'static str, ChannelMetrics)>> {
+    let channels = channels(a, b);
+    let indices: Vec<usize> = channels.iter().map(|&(_, index)| index).collect();
+    let metrics = compare(&a.to_rgba32f(), &b.to_rgba32f(), &indices)?;
+    Ok(channels.iter().map(|&(name, _)| name).zip(metrics).collect())
+}
+
+// Absolute difference of the color channels times gain, black where the images agree
+pub fn difference_image(a: &DynamicImage, b: &DynamicImage, gain: f32) -> DynamicImage {
+    let (a, b) = (a.to_rgba32f(), b.to_rgba32f());
+    DynamicImage::ImageRgba32F(Rgba32FImage::from_fn(a.width(), a.height(), |x, y| {
+        let (pixel_a, pixel_b) = (a.get_pixel(x, y), b.get_pixel(x, y));
+        let diff = |channel: usize| ((pixel_a[channel] - pixel_b[channel]).abs() * gain).min(1.0);
+        Rgba([diff(0), diff(1), diff(2), 1.0])
+    }))
+}
+
+pub fn format_psnr(psnr: f64) -> String {
+    if psnr.is_infinite() { "inf".to_string() } else { format!("{:.2}", psnr) }
+}
+
+// Compare two files or two directory trees (files under the first are looked up by relative path
+// in the second) and print per-channel metrics. Difference images are written to diff_image,
+// a file when comparing files and a directory of PNGs mirroring the tree otherwise
+pub fn diff(first: &Path, second: &Path, diff_image: Option<&Path>, gain: f32, profile: &Profile) -> Result<()> {
+    if first.is_dir() != second.is_dir() {
+        return Err(anyhow!("Compare two files or two directories: {} and {}", first.display(), second.display()));
+    }
+    
+    if !first.is_dir() {
+        return diff_files(first, second, diff_image, gain, profile);
+    }
+    
+    let first_files = image_files(first, profile);
+    let second_files = image_files(second, profile);
+    println!("Comparing {} with {}", first.display(), second.display());
+    
+    let mut failed = 0;
+    for relative in &first_files {
+        if !second_files.contains(relative) {
+            println!("Only in {}: {}", first.display(), relative);
+            continue;
+        }
+        let output = diff_image.map(|dir| dir.join(Path::new(relative).with_extension("png")));
+        if let Err(err) = diff_files(&first.join(relative), &second.join(relative), output.as_deref(), gain, profile) {
+            println!("  Error: {:#}", err);
+            failed += 1;
+        }
+    }
+    for relative in second_files.iter().filter(|relative| !first_files.contains(relative)) {
+        println!("Only in {}: {}", second.display(), relative);
+    }
+    
+    if failed > 0 {
+        return Err(anyhow!("{} files couldn't be compared", failed));
+    }
+    Ok(())
+}
+
+fn diff_files(first: &Path, second: &Path, diff_image: Option<&Path>, gain: f32, profile: &Profile) -> Result<()> {
+    let max_alloc = profile.max_alloc();
+    let first_pages = open_pages(first, max_alloc, profile.psd_layer.as_ref())
+        .with_context(|| format!("Failed to open image: {}", first.display()))?;
+    let second_pages = open_pages(second, max_alloc, profile.psd_layer.as_ref())
+        .with_context(|| format!("Failed to open image: {}", second.display()))?;
+    if first_pages.len() != second_pages.len() {
+        return Err(anyhow!("{} has {} pages, {} has {}", first.display(), first_pages.len(), second.display(), second_pages.len()));
+    }
+    
+    println!("{} vs {}", first.display(), second.display());
+    for (page_idx, (a, b)) in first_pages.iter().zip(&second_pages).enumerate() {
+        if first_pages.len() > 1 {
+            println!("  Page {} of {}", page_idx + 1, first_pages.len());
+        }
+        for (name, metrics) in metrics(a, b)? {
+            println!("  {:<5} PSNR {:>7} dB  SSIM {:.4}  max diff {:.1}", name, format_psnr(metrics.psnr), metrics.ssim, metrics.max_diff);
+        }
+        
+        if let Some(path) = diff_image {
+            let path = if first_pages.len() > 1 {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                path.with_file_name(format!("{}_p{}.png", stem, page_idx + 1))
+            } else {
+                path.to_path_buf()
+            };
+            write_difference(&path, a, b, gain, profile)?;
+            println!("  Saved difference image to: {}", path.display());
+        }
+    }
+    
+    Ok(())
+}
+
+fn write_difference(path: &Path, a: &DynamicImage, b: &DynamicImage, gain: f32, profile: &Profile) -> Result<()> {
+    let img = DynamicImage::ImageRgba8(difference_image(a, b, gain).to_rgba8());
+    let format = path.extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_else(|| "png".to_string());
+    let data = encode_image(&img, &format, profile, None, None)?;
+    
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create output directory: {}", dir.display()))?;
+    }
+    std::fs::write(path, data)
+        .with_context(|| format!("Failed to save difference image: {}", path.display()))
+}
+
+// Relative paths of the images under dir, with the profile's extensions and hidden file rules
+fn image_files(dir: &Path, profile: &Profile) -> Vec<String> {
+    let extensions = profile.allowed_extensions();
+    let mut files: Vec<String> = WalkDir::new(dir)
+        .follow_links(profile.follow_symlinks)
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || profile.include_hidden || !is_hidden(entry))
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_type().is_file() &&
+            entry.path().extension().is_some_and(|ext| extensions.contains(&ext.to_string_lossy().to_ascii_lowercase()))
+        })
+        .map(|entry| relative_path(entry.path(), dir))
+        .collect();
+    files.sort();
+    files
+}
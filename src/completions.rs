@@ -0,0 +1,289 @@
+// Shell completion scripts generated from the clap definitions. Profile names are completed at
+// runtime with `list-profiles --names`, so they follow the config next to the executable
+use clap::{Arg, Command, CommandFactory};
+use clap::builder::ValueParser;
+
+use crate::Cli;
+
+const BIN: &str = "ormseparatev3";
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+enum Values {
+    // A flag without a value
+    None,
+    Profiles,
+    Choices(Vec<String>),
+    Path,
+    // Numbers, regexes and other free text, nothing to offer
+    Any,
+}
+
+struct OptionSpec {
+    long: Option<String>,
+    short: Option<char>,
+    help: String,
+    values: Values,
+}
+
+impl OptionSpec {
+    // Every spelling of the option, like -p and --profile
+    fn names(&self) -> Vec<String> {
+        self.short.map(|short| format!("-{}", short)).into_iter()
+            .chain(self.long.as_ref().map(|long| format!("--{}", long)))
+            .collect()
+    }
+}
+
+struct CommandSpec {
+    name: String,
+    options: Vec<OptionSpec>,
+    // Name and description, only the top level command has any
+    subcommands: Vec<(String, String)>,
+}
+
+pub fn completions(shell: Shell) -> String {
+    let mut cli = Cli::command();
+    cli.build();
+    let root = spec(&cli);
+    let subcommands: Vec<CommandSpec> = cli.get_subcommands().map(spec).collect();
+    
+    match shell {
+        Shell::Bash => bash(&root, &subcommands),
+        Shell::Zsh => zsh(&root, &subcommands),
+        Shell::Fish => fish(&root, &subcommands),
+        Shell::Powershell => powershell(&root, &subcommands),
+    }
+}
+
+fn spec(command: &Command) -> CommandSpec {
+    let options = command.get_arguments()
+        .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+        .map(|arg| OptionSpec {
+            long: arg.get_long().map(String::from),
+            short: arg.get_short(),
+            help: arg.get_help().map(|help| help.to_string()).unwrap_or_default(),
+            values: values(arg),
+        })
+        .collect();
+    let subcommands = command.get_subcommands()
+        .filter(|subcommand| !subcommand.is_hide_set())
+        .map(|subcommand| (subcommand.get_name().to_string(), subcommand.get_about().map(|about| about.to_string()).unwrap_or_default()))
+        .collect();
+    CommandSpec { name: command.get_name().to_string(), options, subcommands }
+}
+
+fn values(arg: &Arg) -> Values {
+    if !arg.get_action().takes_values() {
+        return Values::None;
+    }
+    if arg.get_id() == "profile" {
+        return Values::Profiles;
+    }
+    let choices: Vec<String> = arg.get_possible_values().iter().map(|value| value.get_name().to_string()).collect();
+    if !choices.is_empty() {
+        Values::Choices(choices)
+    } else if arg.get_value_parser().type_id() == ValueParser::path_buf().type_id() {
+        Values::Path
+    } else {
+        Values::Any
+    }
+}
+
+fn bash(root: &CommandSpec, subcommands: &[CommandSpec]) -> String {
+    let names: Vec<&str> = root.subcommands.iter().map(|(name, _)| name.as_str()).collect();
+    let mut script = format!("_{bin}() {{
+    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\" sub=\"\" opts=\"\" word
+    for word in \"${{COMP_WORDS[@]:1:COMP_CWORD-1}}\"; do
+        case \"$word\" in
+            {subs}) sub=\"$word\"; break;;
+        esac
+    done
+    
+    case \"$sub\" in
+", bin = BIN, subs = names.join("|"));
+    
+    for command in std::iter::once(root).chain(subcommands) {
+        let label = if std::ptr::eq(command, root) { String::new() } else { command.name.clone() };
+        script.push_str(&format!("        \"{}\")\n            case \"$prev\" in\n", label));
+        for option in &command.options {
+            let reply = match &option.values {
+                Values::None => continue,
+                Values::Profiles => format!("$(compgen -W \"$({} list-profiles --names 2>/dev/null)\" -- \"$cur\")", BIN),
+                Values::Choices(choices) => format!("$(compgen -W \"{}\" -- \"$cur\")", choices.join(" ")),
+                Values::Path => "$(compgen -f -- \"$cur\")".to_string(),
+                Values::Any => String::new(),
+            };
+            script.push_str(&format!("                {}) COMPREPLY=({}); return;;\n", option.names().join("|"), reply));
+        }
+        let mut words: Vec<String> = command.options.iter().flat_map(OptionSpec::names).collect();
+        words.extend(command.subcommands.iter().map(|(name, _)| name.clone()));
+        script.push_str(&format!("            esac\n            opts=\"{}\";;\n", words.join(" ")));
+    }
+    
+    script.push_str(&format!("    esac
+    
+    if [[ \"$cur\" == -* ]]; then
+        COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\"))
+    else
+        COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\") $(compgen -f -- \"$cur\"))
+    fi
+}}
+complete -o filenames -F _{bin} {bin}
+", bin = BIN));
+    script
+}
+
+fn zsh(root: &CommandSpec, subcommands: &[CommandSpec]) -> String {
+    let escape = |text: &str| text.replace('\'', "'\\''").replace(['[', ']'], "").replace(':', "\\:");
+    let specs = |command: &CommandSpec, indent: &str| -> String {
+        command.options.iter().map(|option| {
+            let action = match &option.values {
+                Values::None => String::new(),
+                Values::Profiles => format!(":profile:_{}_profiles", BIN),
+                Values::Choices(choices) => format!(":value:({})", choices.join(" ")),
+                Values::Path => ":path:_files".to_string(),
+                Values::Any => ":value: ".to_string(),
+            };
+            let names = option.names();
+            let spelled = if names.len() > 1 { format!("'({})'{{{}}}'", names.join(" "), names.join(",")) } else { format!("'{}", names[0]) };
+            format!("{}{}[{}]{}' \\\n", indent, spelled, escape(&option.help), action)
+        }).collect()
+    };
+    
+    let commands: Vec<String> = root.subcommands.iter()
+        .map(|(name, about)| format!("{}\\:\"{}\"", name, escape(about).replace(['"', '(', ')'], "")))
+        .collect();
+    let mut script = format!("#compdef {bin}
+
+_{bin}_profiles() {{
+    local -a profiles
+    profiles=(${{(f)\"$({bin} list-profiles --names 2>/dev/null)\"}})
+    _describe 'profile' profiles
+}}
+
+_{bin}() {{
+    local state line
+    _arguments -C \\
+{root_specs}        '1: :->command' \\
+        '*:: :->args'
+    
+    case $state in
+        command)
+            _alternative 'commands:command:(({commands}))' 'files:input:_files';;
+        args)
+            case $line[1] in
+", bin = BIN, root_specs = specs(root, "        "), commands = commands.join(" "));
+    for command in subcommands {
+        script.push_str(&format!("                {})\n                    _arguments \\\n{}                        '*:input:_files';;\n",
+            command.name, specs(command, "                        ")));
+    }
+    script.push_str(&format!("                *)
+                    _files;;
+            esac;;
+    esac
+}}
+
+_{bin} \"$@\"
+", bin = BIN));
+    script
+}
+
+fn fish(root: &CommandSpec, subcommands: &[CommandSpec]) -> String {
+    let escape = |text: &str| text.replace('\\', "\\\\").replace('\'', "\\'");
+    let line = |condition: &str, option: &OptionSpec| -> String {
+        let mut line = format!("complete -c {} -n '{}'", BIN, condition);
+        if let Some(short) = option.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        if let Some(long) = &option.long {
+            line.push_str(&format!(" -l {}", long));
+        }
+        match &option.values {
+            Values::None => {}
+            Values::Profiles => line.push_str(&format!(" -x -a '({} list-profiles --names 2>/dev/null)'", BIN)),
+            Values::Choices(choices) => line.push_str(&format!(" -x -a '{}'", choices.join(" "))),
+            Values::Path => line.push_str(" -r -F"),
+            Values::Any => line.push_str(" -x"),
+        }
+        line.push_str(&format!(" -d '{}'\n", escape(&option.help)));
+        line
+    };
+    
+    let mut script = String::new();
+    for (name, about) in &root.subcommands {
+        script.push_str(&format!("complete -c {} -n '__fish_use_subcommand' -a {} -d '{}'\n", BIN, name, escape(about)));
+    }
+    for option in &root.options {
+        script.push_str(&line("__fish_use_subcommand", option));
+    }
+    for command in subcommands {
+        let condition = format!("__fish_seen_subcommand_from {}", command.name);
+        for option in &command.options {
+            script.push_str(&line(&condition, option));
+        }
+    }
+    script
+}
+
+fn powershell(root: &CommandSpec, subcommands: &[CommandSpec]) -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+    let mut script = format!("Register-ArgumentCompleter -Native -CommandName {bin} -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+    $elements = @($commandAst.CommandElements | Select-Object -Skip 1 | ForEach-Object {{ $_.ToString() }})
+    if ($wordToComplete -ne '' -and $elements.Count -gt 0) {{ $elements = @($elements | Select-Object -SkipLast 1) }}
+    $previous = if ($elements.Count -gt 0) {{ $elements[-1] }} else {{ '' }}
+    $subcommands = @({subs})
+    $sub = @($elements | Where-Object {{ $subcommands -contains $_ }} | Select-Object -First 1)
+    $sub = if ($sub.Count -gt 0) {{ $sub[0] }} else {{ '' }}
+    
+    $values = $null
+    $candidates = @()
+    switch ($sub) {{
+", bin = BIN, subs = root.subcommands.iter().map(|(name, _)| quote(name)).collect::<Vec<_>>().join(", "));
+    
+    for command in std::iter::once(root).chain(subcommands) {
+        let label = if std::ptr::eq(command, root) { String::new() } else { command.name.clone() };
+        script.push_str(&format!("        {} {{\n", quote(&label)));
+        for option in &command.options {
+            let names: Vec<String> = option.names().iter().map(|name| quote(name)).collect();
+            // Returning nothing lets PowerShell fall back to completing paths
+            let values = match &option.values {
+                Values::None => continue,
+                Values::Path | Values::Any => {
+                    script.push_str(&format!("            if (@({}) -contains $previous) {{ return }}\n", names.join(", ")));
+                    continue;
+                }
+                Values::Profiles => format!("@(& {} list-profiles --names 2>$null)", BIN),
+                Values::Choices(choices) => format!("@({})", choices.iter().map(|choice| quote(choice)).collect::<Vec<_>>().join(", ")),
+            };
+            script.push_str(&format!("            if (@({}) -contains $previous) {{ $values = {} }}\n", names.join(", "), values));
+        }
+        let candidates: Vec<String> = command.options.iter()
+            .flat_map(|option| option.names().into_iter().map(|name| format!("@({}, {})", quote(&name), quote(&option.help))))
+            .chain(command.subcommands.iter().map(|(name, about)| format!("@({}, {})", quote(name), quote(about))))
+            .collect();
+        script.push_str(&format!("            $candidates = @({})\n        }}\n", candidates.join(", ")));
+    }
+    
+    script.push_str("    }
+    
+    if ($values) {
+        $values | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {
+            [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+        }
+        return
+    }
+    $candidates | Where-Object { $_[0] -like \"$wordToComplete*\" } | ForEach-Object {
+        [System.Management.Automation.CompletionResult]::new($_[0], $_[0], 'ParameterName', $_[1])
+    }
+}
+");
+    script
+}
@@ -0,0 +1,172 @@
+// Cubemaps packed into one image, as reflection probes and skyboxes are often delivered, and their
+// conversion from and to equirectangular panoramas. Faces are named px, nx, py, ny, pz and nz for
+// +X, -X, +Y, -Y, +Z and -Z
+use std::f32::consts::{PI, TAU};
+
+use image::{DynamicImage, Rgba, Rgba32FImage};
+
+use crate::CubemapLayout;
+
+pub const FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+// The layout an image's aspect ratio implies, None when it fits none
+pub fn detect(width: u32, height: u32) -> Option<CubemapLayout> {
+    let (width, height) = (width as u64, height as u64);
+    if width * 3 == height * 4 {
+        Some(CubemapLayout::Hcross)
+    } else if width * 4 == height * 3 {
+        Some(CubemapLayout::Vcross)
+    } else if width == height * 6 {
+        Some(CubemapLayout::Hstrip)
+    } else if width * 6 == height {
+        Some(CubemapLayout::Vstrip)
+    } else {
+        None
+    }
+}
+
+// Grid size in faces, and the cell of each face in FACES order. -Z of vertical crosses hangs
+// below -Y upside down, so it is turned when cut out
+fn cells(layout: CubemapLayout) -> (u32, u32, [(u32, u32); 6]) {
+    match layout {
+        CubemapLayout::Hcross | CubemapLayout::Auto => (4, 3, [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)]),
+        CubemapLayout::Vcross => (3, 4, [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)]),
+        CubemapLayout::Hstrip => (6, 1, [(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0)]),
+        CubemapLayout::Vstrip => (1, 6, [(0, 0), (0, 1), (0, 2), (0, 3), (0, 4), (0, 5)]),
+    }
+}
+
+// The six faces in FACES order, None when the image doesn't have the layout's proportions
+pub fn faces(img: &DynamicImage, layout: CubemapLayout) -> Option<Vec<DynamicImage>> {
+    let layout = match layout {
+        CubemapLayout::Auto => detect(img.width(), img.height())?,
+        layout => layout,
+    };
+    let (columns, rows, cells) = cells(layout);
+    let size = img.width() / columns;
+    if size == 0 || img.width() != size * columns || img.height() != size * rows {
+        return None;
+    }
+    Some(cells.iter().enumerate().map(|(index, &(column, row))| {
+        let face = img.crop_imm(column * size, row * size, size, size);
+        if layout == CubemapLayout::Vcross && FACES[index] == "nz" { face.rotate180() } else { face }
+    }).collect())
+}
+
+// Pack six faces in FACES order into one image with the layout, cells without a face stay
+// transparent
+pub fn assemble(faces: &[DynamicImage], layout: CubemapLayout) -> DynamicImage {
+    let (columns, rows, cells) = cells(layout);
+    let size = faces[0].width();
+    let mut packed = Rgba32FImage::new(size * columns, size * rows);
+    for (index, (face, &(column, row))) in faces.iter().zip(&cells).enumerate() {
+        let face = if layout == CubemapLayout::Vcross && FACES[index] == "nz" { face.rotate180() } else { face.clone() };
+        image::imageops::replace(&mut packed, &face.to_rgba32f(), (column * size) as i64, (row * size) as i64);
+    }
+    DynamicImage::ImageRgba32F(packed)
+}
+
+// Direction through (u, v) of a face, both -1 to 1 with v pointing down. Faces follow the OpenGL
+// cubemap convention, so crosses are seamless
+fn direction(face: usize, u: f32, v: f32) -> [f32; 3] {
+    match face {
+        0 => [1.0, -v, -u],
+        1 => [-1.0, -v, u],
+        2 => [u, 1.0, v],
+        3 => [u, -1.0, -v],
+        4 => [u, -v, 1.0],
+        _ => [-u, -v, -1.0],
+    }
+}
+
+// The face a direction points into and where it crosses it, the inverse of direction
+fn face_at([x, y, z]: [f32; 3]) -> (usize, f32, f32) {
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    if ax >= ay && ax >= az {
+        if x > 0.0 { (0, -z / ax, -y / ax) } else { (1, z / ax, -y / ax) }
+    } else if ay >= az {
+        if y > 0.0 { (2, x / ay, z / ay) } else { (3, x / ay, -z / ay) }
+    } else if z > 0.0 {
+        (4, x / az, -y / az)
+    } else {
+        (5, -x / az, -y / az)
+    }
+}
+
+// Equirectangular panoramas put +Z in the middle, +X a quarter to the right of it and +Y on top
+fn equirect_direction(u: f32, v: f32) -> [f32; 3] {
+    let longitude = (u - 0.5) * TAU;
+    let latitude = (0.5 - v) * PI;
+    [latitude.cos() * longitude.sin(), latitude.sin(), latitude.cos() * longitude.cos()]
+}
+
+fn equirect_position([x, y, z]: [f32; 3]) -> (f32, f32) {
+    let length = (x * x + y * y + z * z).sqrt().max(f32::EPSILON);
+    (0.5 + x.atan2(z) / TAU, 0.5 - (y / length).clamp(-1.0, 1.0).asin() / PI)
+}
+
+// Bilinear sample at (x, y) in pixel units, wrapping horizontally (panoramas) or clamping
+fn sample(img: &Rgba32FImage, x: f32, y: f32, wrap: bool) -> [f32; 4] {
+    let (width, height) = (img.width() as i64, img.height() as i64);
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let texel = |tx: i64, ty: i64| {
+        let tx = if wrap { tx.rem_euclid(width) } else { tx.clamp(0, width - 1) };
+        img.get_pixel(tx as u32, ty.clamp(0, height - 1) as u32).0
+    };
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let (a, b, c, d) = (texel(x0, y0), texel(x0 + 1, y0), texel(x0, y0 + 1), texel(x0 + 1, y0 + 1));
+    std::array::from_fn(|i| {
+        let top = a[i] + (b[i] - a[i]) * fx;
+        let bottom = c[i] + (d[i] - c[i]) * fx;
+        top + (bottom - top) * fy
+    })
+}
+
+// Samples per axis of each output pixel, enough to cover the source texels it spans
+fn samples_per_axis(source_texels: f32, target_pixels: f32) -> u32 {
+    (source_texels / target_pixels).ceil().clamp(1.0, 8.0) as u32
+}
+
+// Average of n×n bilinear samples spread over an output pixel, positions in 0-1 of the output
+fn supersample(n: u32, x: u32, y: u32, width: u32, height: u32, mut at: impl FnMut(f32, f32) -> [f32; 4]) -> Rgba<f32> {
+    let mut sum = [0.0; 4];
+    for sy in 0..n {
+        for sx in 0..n {
+            let u = (x as f32 + (sx as f32 + 0.5) / n as f32) / width as f32;
+            let v = (y as f32 + (sy as f32 + 0.5) / n as f32) / height as f32;
+            for (total, value) in sum.iter_mut().zip(at(u, v)) {
+                *total += value;
+            }
+        }
+    }
+    Rgba(sum.map(|total| total / (n * n) as f32))
+}
+
+// The six size×size faces of an equirectangular panorama, in FACES order
+pub fn from_equirect(panorama: &Rgba32FImage, size: u32) -> Vec<Rgba32FImage> {
+    let n = samples_per_axis(panorama.width() as f32 / 4.0, size as f32);
+    let (width, height) = (panorama.width() as f32, panorama.height() as f32);
+    (0..6).map(|face| {
+        Rgba32FImage::from_fn(size, size, |x, y| {
+            supersample(n, x, y, size, size, |u, v| {
+                let (pu, pv) = equirect_position(direction(face, u * 2.0 - 1.0, v * 2.0 - 1.0));
+                sample(panorama, pu * width, pv * height, true)
+            })
+        })
+    }).collect()
+}
+
+// A width×width/2 equirectangular panorama of six faces in FACES order
+pub fn to_equirect(faces: &[Rgba32FImage], width: u32) -> Rgba32FImage {
+    let size = faces[0].width();
+    let height = (width / 2).max(1);
+    let n = samples_per_axis(size as f32, width as f32 / 4.0);
+    Rgba32FImage::from_fn(width, height, |x, y| {
+        supersample(n, x, y, width, height, |u, v| {
+            let (face, fu, fv) = face_at(equirect_direction(u, v));
+            sample(&faces[face], (fu + 1.0) / 2.0 * size as f32, (fv + 1.0) / 2.0 * size as f32, false)
+        })
+    })
+}
@@ -0,0 +1,483 @@
+// Long-running ingest: watches the hot folders from the config, takes jobs over a local socket and
+// splits them with a fixed number of workers. The queue is saved on every change, so jobs that were
+// waiting or running when the daemon stopped are picked up again on the next start
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::{Result, anyhow, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::{CancelToken, Config, DaemonConfig, FileMatcher, NoProgress, Profile, matching_files, print_log, summarized_process_file, usable_profiles};
+
+// Failures listed by the status command
+const RECENT_ERRORS: usize = 10;
+
+// A command line is the token, a profile name and a path, longer ones are refused unread
+const MAX_COMMAND_BYTES: u64 = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum DaemonAction {
+    /// Start the daemon in the foreground
+    Run,
+    /// Show the queue, running jobs and recent errors of the running daemon
+    Status,
+    /// Queue files on the running daemon
+    Submit,
+    /// Stop the running daemon once its running jobs finish
+    Stop,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct QueuedJob {
+    path: PathBuf,
+    profile: String,
+}
+
+// What's saved to the state file
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct State {
+    #[serde(default)]
+    queue: Vec<QueuedJob>,
+    // Modification time in milliseconds of every file already split, so unchanged files
+    // aren't split again after a restart
+    #[serde(default)]
+    processed: BTreeMap<String, u64>,
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: VecDeque<QueuedJob>,
+    running: Vec<QueuedJob>,
+    processed: BTreeMap<String, u64>,
+    done: usize,
+    skipped: usize,
+    failed: usize,
+    errors: VecDeque<String>,
+}
+
+impl Shared {
+    fn is_pending(&self, job: &QueuedJob) -> bool {
+        self.queue.contains(job) || self.running.contains(job)
+    }
+}
+
+struct Daemon {
+    config: DaemonConfig,
+    default_profile: String,
+    profiles: HashMap<String, Profile>,
+    state_path: PathBuf,
+    token: String,
+    workers: usize,
+    shared: Mutex<Shared>,
+    wake: Condvar,
+    stopping: AtomicBool,
+}
+
+impl Daemon {
+    // Running jobs are saved at the front of the queue, they start over after a restart
+    fn save(&self, shared: &Shared) {
+        let state = State {
+            queue: shared.running.iter().chain(&shared.queue).cloned().collect(),
+            processed: shared.processed.clone(),
+        };
+        let saved = toml::to_string_pretty(&state).map_err(anyhow::Error::from).and_then(|content| {
+            let temp = self.state_path.with_extension("tmp");
+            fs::write(&temp, content)?;
+            fs::rename(&temp, &self.state_path)?;
+            Ok(())
+        });
+        if let Err(err) = saved {
+            println!("Warning: failed to save daemon state to {}: {:#}", self.state_path.display(), err);
+        }
+    }
+    
+    fn enqueue(&self, job: QueuedJob) -> bool {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.is_pending(&job) {
+            return false;
+        }
+        println!("Queued {} ({})", job.path.display(), job.profile);
+        shared.queue.push_back(job);
+        self.save(&shared);
+        self.wake.notify_one();
+        true
+    }
+}
+
+pub fn daemon(action: DaemonAction, inputs: &[PathBuf], profile: Option<&str>, config: &Config, exe_dir: &Path) -> Result<()> {
+    let request = |command: &str| request(&config.daemon.address, &token_path(config, exe_dir), command);
+    match action {
+        DaemonAction::Run => run(config, exe_dir),
+        DaemonAction::Status => {
+            print!("{}", request("status")?);
+            Ok(())
+        }
+        DaemonAction::Stop => {
+            print!("{}", request("stop")?);
+            Ok(())
+        }
+        DaemonAction::Submit => {
+            if inputs.is_empty() {
+                return Err(anyhow!("Give the files to submit"));
+            }
+            // The daemon runs in its own working directory
+            for input in inputs {
+                let path = fs::canonicalize(input)
+                    .with_context(|| format!("Failed to resolve path: {}", input.display()))?;
+                let reply = request(&format!("submit\t{}\t{}", profile.unwrap_or(""), path.display()))?;
+                print!("{}", reply);
+                if reply.starts_with("error") {
+                    return Err(anyhow!("The daemon rejected {}", input.display()));
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+// The running daemon's token sits next to its state file, readable by its user only
+fn token_path(config: &Config, exe_dir: &Path) -> PathBuf {
+    exe_dir.join(&config.daemon.state_file).with_file_name("daemon.token")
+}
+
+// 256 bits from the OS-seeded keys std gives every hasher, no random number crate needed
+fn new_token() -> String {
+    let seed: Vec<u8> = (0..4u64).flat_map(|index| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(index);
+        hasher.finish().to_le_bytes()
+    }).collect();
+    crate::hash::hex(&crate::hash::sha256(&seed))
+}
+
+// The file is created anew so it never keeps looser permissions from an earlier one
+fn write_token(path: &Path, token: &str) -> Result<()> {
+    let _ = fs::remove_file(path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+        .and_then(|mut file| file.write_all(token.as_bytes()))
+        .with_context(|| format!("Failed to write daemon token: {}", path.display()))
+}
+
+// Compares every byte, so the time taken doesn't tell how much of a guess was right
+fn is_token(given: &str, token: &str) -> bool {
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// Send one command line and read the reply until the daemon closes the connection
+fn request(address: &str, token_path: &Path, command: &str) -> Result<String> {
+    let token = fs::read_to_string(token_path)
+        .with_context(|| format!("No daemon token at {}, start the daemon with `ormseparatev3 daemon run`", token_path.display()))?;
+    let mut stream = TcpStream::connect(address)
+        .with_context(|| format!("No daemon is listening on {}, start one with `ormseparatev3 daemon run`", address))?;
+    stream.write_all(format!("{}\t{}\n", token.trim(), command).as_bytes())?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).context("Failed to read the daemon's reply")?;
+    Ok(reply)
+}
+
+fn run(config: &Config, exe_dir: &Path) -> Result<()> {
+    let settings = config.daemon.clone();
+    let profiles = usable_profiles(config, exe_dir);
+    for folder in &settings.hot_folders {
+        let profile = folder.profile.as_deref().unwrap_or(&config.default_profile);
+        if !profiles.contains_key(profile) {
+            return Err(anyhow!("Hot folder {} uses profile '{}', which is missing or invalid", folder.path.display(), profile));
+        }
+        if !folder.path.is_dir() {
+            return Err(anyhow!("Hot folder {} is not a directory", folder.path.display()));
+        }
+    }
+    
+    let state_path = exe_dir.join(&settings.state_file);
+    let mut state: State = if state_path.exists() {
+        let content = fs::read_to_string(&state_path)
+            .with_context(|| format!("Failed to read daemon state: {}", state_path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse daemon state: {}", state_path.display()))?
+    } else {
+        State::default()
+    };
+    
+    state.queue.retain(|job| {
+        let usable = profiles.contains_key(&job.profile);
+        if !usable {
+            println!("Warning: dropping queued {}, profile '{}' is missing or invalid", job.path.display(), job.profile);
+        }
+        usable
+    });
+    
+    let listener = TcpListener::bind(&settings.address)
+        .with_context(|| format!("Failed to listen on {}", settings.address))?;
+    // Only after binding, a second daemon that can't start mustn't replace the first one's token
+    let token = new_token();
+    let token_path = token_path(config, exe_dir);
+    write_token(&token_path, &token)?;
+    let workers = settings.workers
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |count| count.get()))
+        .max(1);
+    println!("Daemon listening on {} with {} workers, state in {}", settings.address, workers, state_path.display());
+    if !state.queue.is_empty() {
+        println!("Resuming {} queued jobs", state.queue.len());
+    }
+    for folder in &settings.hot_folders {
+        println!("Watching {} ({})", folder.path.display(), folder.profile.as_deref().unwrap_or(&config.default_profile));
+    }
+    
+    let daemon = Daemon {
+        config: settings,
+        default_profile: config.default_profile.clone(),
+        profiles,
+        state_path,
+        token,
+        workers,
+        shared: Mutex::new(Shared { queue: state.queue.into(), processed: state.processed, ..Default::default() }),
+        wake: Condvar::new(),
+        stopping: AtomicBool::new(false),
+    };
+    
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| work(&daemon));
+        }
+        scope.spawn(|| watch(&daemon));
+        
+        // Commands are answered one at a time, none of them block
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            if let Err(err) = handle(stream, &daemon) {
+                println!("Warning: {:#}", err);
+            }
+            if daemon.stopping.load(Ordering::SeqCst) {
+                break;
+            }
+        }
+        // Under the lock, so a worker can't miss the wakeup between checking stopping and waiting
+        let _shared = daemon.shared.lock().unwrap();
+        daemon.wake.notify_all();
+    });
+    
+    let _ = fs::remove_file(&token_path);
+    println!("Daemon stopped");
+    Ok(())
+}
+
+fn work(daemon: &Daemon) {
+    loop {
+        let job = {
+            let mut shared = daemon.shared.lock().unwrap();
+            loop {
+                if daemon.stopping.load(Ordering::SeqCst) {
+                    return;
+                }
+                if let Some(job) = shared.queue.pop_front() {
+                    shared.running.push(job.clone());
+                    daemon.save(&shared);
+                    break job;
+                }
+                shared = daemon.wake.wait(shared).unwrap();
+            }
+        };
+        
+        let modified = modified_millis(&job.path);
+        let profile = &daemon.profiles[&job.profile];
+        let root = job.path.parent().unwrap_or(Path::new("."));
+        let mut log = Vec::new();
+        let (summary, result) = match FileMatcher::new(profile) {
+            Ok(matcher) => {
+                let (summary, result) = summarized_process_file(&job.path, root, profile, &matcher, &mut log, &NoProgress, (0, 1), &CancelToken::new());
+                (Some(summary), result)
+            }
+            Err(err) => (None, Err(err)),
+        };
+        
+        let mut shared = daemon.shared.lock().unwrap();
+        print_log(&log);
+        shared.running.retain(|running| running != &job);
+        match result {
+            Ok(()) if summary.is_some_and(|summary| summary.skipped.is_some()) => shared.skipped += 1,
+            Ok(()) => shared.done += 1,
+            Err(err) => {
+                println!("  Error: {:#}", err);
+                shared.failed += 1;
+                shared.errors.push_back(format!("{}: {:#}", job.path.display(), err));
+                if shared.errors.len() > RECENT_ERRORS {
+                    shared.errors.pop_front();
+                }
+            }
+        }
+        if let Some(modified) = modified {
+            shared.processed.insert(job.path.to_string_lossy().into_owned(), modified);
+        }
+        daemon.save(&shared);
+    }
+}
+
+// Poll the hot folders. A file is queued once its size and modification time stayed the same for
+// one poll, so files that are still being copied in aren't split half-written
+fn watch(daemon: &Daemon) {
+    let mut last_seen: HashMap<PathBuf, (u64, u64)> = HashMap::new();
+    let matchers: Vec<Option<FileMatcher>> = daemon.config.hot_folders.iter()
+        .map(|folder| FileMatcher::new(&daemon.profiles[folder_profile(daemon, folder)]).ok())
+        .collect();
+    
+    while !daemon.stopping.load(Ordering::SeqCst) {
+        let mut seen = HashMap::new();
+        for (folder, matcher) in daemon.config.hot_folders.iter().zip(&matchers) {
+            let Some(matcher) = matcher else { continue };
+            let profile_name = folder_profile(daemon, folder);
+            for file in matching_files(&folder.path, &daemon.profiles[profile_name], matcher) {
+                let Ok(metadata) = fs::metadata(&file) else { continue };
+                let Some(modified) = modified_millis(&file) else { continue };
+                let stamp = (modified, metadata.len());
+                let job = QueuedJob { path: file.clone(), profile: profile_name.to_string() };
+                let wanted = {
+                    let shared = daemon.shared.lock().unwrap();
+                    shared.processed.get(file.to_string_lossy().as_ref()) != Some(&modified) && !shared.is_pending(&job)
+                };
+                if wanted && last_seen.get(&file) == Some(&stamp) {
+                    daemon.enqueue(job);
+                }
+                seen.insert(file, stamp);
+            }
+        }
+        last_seen = seen;
+        
+        // Forget files that were deleted, the state file would only grow otherwise
+        {
+            let mut shared = daemon.shared.lock().unwrap();
+            let before = shared.processed.len();
+            shared.processed.retain(|path, _| Path::new(path).exists());
+            if shared.processed.len() != before {
+                daemon.save(&shared);
+            }
+        }
+        
+        // Sleep in short steps so stop doesn't wait for a whole poll interval
+        for _ in 0..daemon.config.poll_seconds.max(1) * 4 {
+            if daemon.stopping.load(Ordering::SeqCst) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(250));
+        }
+    }
+}
+
+fn folder_profile<'a>(daemon: &'a Daemon, folder: &'a crate::HotFolder) -> &'a str {
+    folder.profile.as_deref().unwrap_or(&daemon.default_profile)
+}
+
+fn modified_millis(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?;
+    modified.duration_since(UNIX_EPOCH).ok().map(|since| since.as_millis() as u64)
+}
+
+// One tab-separated command per connection after the daemon's token: status, stop, or submit with a
+// profile (empty for the default) and an absolute path
+fn handle(mut stream: TcpStream, daemon: &Daemon) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut line = String::new();
+    BufReader::new((&stream).take(MAX_COMMAND_BYTES)).read_line(&mut line)?;
+    if !line.ends_with('\n') && line.len() as u64 == MAX_COMMAND_BYTES {
+        stream.write_all(b"error: command too long\n")?;
+        return Err(anyhow!("Refused a control command over {} bytes", MAX_COMMAND_BYTES));
+    }
+    let mut fields: Vec<&str> = line.trim_end_matches(['\r', '\n']).split('\t').collect();
+    if !is_token(fields.first().copied().unwrap_or(""), &daemon.token) {
+        stream.write_all(b"error: wrong or missing daemon token\n")?;
+        return Err(anyhow!("Refused a control command without the daemon token"));
+    }
+    fields.remove(0);
+    let line = fields.join(" ");
+    
+    let reply = match fields.as_slice() {
+        ["status"] => status(daemon),
+        ["stop"] => {
+            daemon.stopping.store(true, Ordering::SeqCst);
+            let running = daemon.shared.lock().unwrap().running.len();
+            format!("Stopping after {} running jobs, queued jobs are kept for the next start\n", running)
+        }
+        ["submit", profile, path] => {
+            let profile = if profile.is_empty() { daemon.default_profile.as_str() } else { profile };
+            let path = PathBuf::from(path);
+            if !daemon.profiles.contains_key(profile) {
+                format!("error: profile '{}' is missing or invalid\n", profile)
+            } else if !path.is_absolute() || !path.is_file() {
+                format!("error: {} is not an absolute path to a file\n", path.display())
+            } else if daemon.enqueue(QueuedJob { path: path.clone(), profile: profile.to_string() }) {
+                format!("Queued {}\n", path.display())
+            } else {
+                format!("Already queued {}\n", path.display())
+            }
+        }
+        _ => format!("error: unknown command '{}'\n", line.trim()),
+    };
+    stream.write_all(reply.as_bytes())?;
+    Ok(())
+}
+
+fn status(daemon: &Daemon) -> String {
+    let shared = daemon.shared.lock().unwrap();
+    let mut status = format!("Workers: {} ({} busy)\n", daemon.workers, shared.running.len());
+    status.push_str(&format!("Queued: {}\n", shared.queue.len()));
+    for job in &shared.running {
+        status.push_str(&format!("  Running: {} ({})\n", job.path.display(), job.profile));
+    }
+    status.push_str(&format!("Since start: {} split, {} skipped, {} failed\n", shared.done, shared.skipped, shared.failed));
+    for folder in &daemon.config.hot_folders {
+        status.push_str(&format!("Watching: {} ({})\n", folder.path.display(), folder_profile(daemon, folder)));
+    }
+    if !shared.errors.is_empty() {
+        status.push_str("Recent errors:\n");
+        for error in &shared.errors {
+            status.push_str(&format!("  {}\n", error));
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn tokens_are_fresh_and_compared_whole() {
+        let token = new_token();
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, new_token());
+        assert!(is_token(&token, &token));
+        assert!(!is_token(&token[..63], &token));
+        assert!(!is_token("", &token));
+        let mut wrong = token.clone().into_bytes();
+        wrong[63] = if wrong[63] == b'0' { b'1' } else { b'0' };
+        assert!(!is_token(std::str::from_utf8(&wrong).unwrap(), &token));
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn token_file_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("ormsep-token-{}", std::process::id()));
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_token(&path, "secret").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "secret");
+        fs::remove_file(&path).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,204 @@
+// CLI reference rendered from the clap definitions, as a man page or Markdown, plus the examples
+// shared with the help shown when the executable is started without arguments
+use clap::{Arg, ArgAction, Command, CommandFactory};
+
+use crate::Cli;
+
+pub const EXAMPLES: &[(&str, &str)] = &[
+    ("ormseparatev3 image.png", "Process a single image"),
+    ("ormseparatev3 --profile custom folder/", "Process a folder with custom profile"),
+    ("ormseparatev3 --threads 4 folder/", "Process a folder on at most 4 cores"),
+    ("ormseparatev3 rock.obj", "Split the packed textures the model's materials use"),
+    ("ormseparatev3 scan model.gltf", "List the textures a model references and which look packed"),
+    ("ormseparatev3 --atlas 4x2 trims_ORM.png", "Split a trim sheet into 8 tiles, then each tile into channels"),
+    ("ormseparatev3 sprites_ORM.png --sprite-frames", "Split every frame of sprites_ORM.json into channels"),
+    ("ormseparatev3 --atlas-only probe.png --cubemap", "Cut a cross or strip cubemap into its six faces"),
+    ("ormseparatev3 --output-dir split/ folder/", "Write outputs under split/, in the same subfolders as their sources"),
+    ("ormseparatev3 list-profiles", "List available profiles"),
+    ("ormseparatev3 verify rock_ORM.png", "Check that the profile's outputs re-pack losslessly"),
+    ("ormseparatev3 diff before/ after/", "Compare two trees per channel (PSNR/SSIM)"),
+    ("ormseparatev3 inspect rock_ORM.png", "Show channel statistics and the likely packing"),
+    ("ormseparatev3 list-profiles --format json", "List profiles and their channel layouts for scripts"),
+    ("ormseparatev3 --detect downloads/", "Guess the packing of every image and the profile splitting it"),
+    ("ormseparatev3 inspect-sets delivery/", "Check texture sets before processing"),
+    ("ormseparatev3 normal-flip rock_normal.png", "Write rock_normal_flipped.png with green inverted"),
+    ("ormseparatev3 height-to-normal rock_height.png", "Write rock_height_normal.png"),
+    ("ormseparatev3 equirect-to-cubemap sky.exr --size 512", "Write sky_cubemap.exr, a cross of 512px faces"),
+    ("ormseparatev3 pack textures/", "Pack each set's AO, roughness and metallic maps into {stem}_ORM.png"),
+    ("ormseparatev3 pack --gltf textures/", "Pack roughness into G and metallic into B of {stem}_metallicRoughness.png"),
+    ("ormseparatev3 generate-test samples/", "Write gradient, noise and pattern test textures"),
+    ("ormseparatev3 bench --threads 1,4,8", "Time each stage on generated textures"),
+    ("ormseparatev3 doctor", "Check the config, profiles and environment"),
+    ("ormseparatev3 self-update --check", "Check for a newer release"),
+    ("ormseparatev3 serve --port 8080", "Serve the HTTP processing API"),
+    ("ormseparatev3 daemon status", "Show the queue of the running daemon"),
+    ("ormseparatev3 profile new --interactive", "Create a profile step by step, trying its file pattern on samples"),
+    ("ormseparatev3 profile import studio.ormprofile", "Add the profiles and LUTs of a shared bundle to config.toml"),
+    ("ormseparatev3 substance-preset", "Write a Painter export preset matching the profile"),
+    ("ormseparatev3 shell-integration install", "Add the Explorer or Finder right-click menu"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum DocsFormat {
+    Man,
+    Markdown,
+}
+
+pub fn render(format: DocsFormat) -> String {
+    let mut cli = Cli::command();
+    cli.build();
+    match format {
+        DocsFormat::Man => man(&mut cli),
+        DocsFormat::Markdown => markdown(&mut cli),
+    }
+}
+
+// Visible subcommands, without clap's own help subcommand
+fn subcommands(cli: &Command) -> Vec<Command> {
+    cli.get_subcommands()
+        .filter(|command| !command.is_hide_set() && command.get_name() != "help")
+        .cloned()
+        .collect()
+}
+
+fn usage(command: &mut Command) -> String {
+    let usage = command.render_usage().to_string();
+    usage.trim_start_matches("Usage:").trim().to_string()
+}
+
+// Options and positionals that aren't hidden, with the automatic help and version flags last
+fn arguments(command: &Command) -> Vec<&Arg> {
+    let (mut visible, automatic): (Vec<&Arg>, Vec<&Arg>) = command.get_arguments()
+        .filter(|arg| !arg.is_hide_set())
+        .partition(|arg| !matches!(arg.get_action(), ArgAction::Help | ArgAction::Version));
+    visible.extend(automatic);
+    visible
+}
+
+// How the argument is written on the command line, like `-p, --profile <PROFILE>`
+fn spelling(arg: &Arg) -> String {
+    let value = arg.get_value_names()
+        .map(|names| names.iter().map(|name| name.to_string()).collect::<Vec<_>>().join(" "))
+        .unwrap_or_else(|| arg.get_id().as_str().to_uppercase());
+    if arg.is_positional() {
+        let repeated = if matches!(arg.get_action(), ArgAction::Append) { "..." } else { "" };
+        return if arg.is_required_set() { format!("<{}>{}", value, repeated) } else { format!("[{}]{}", value, repeated) };
+    }
+    
+    let mut names: Vec<String> = arg.get_short().map(|short| format!("-{}", short)).into_iter().collect();
+    names.extend(arg.get_long().map(|long| format!("--{}", long)));
+    let mut spelling = names.join(", ");
+    if arg.get_action().takes_values() {
+        let optional = arg.get_num_args().is_some_and(|range| range.min_values() == 0);
+        spelling.push_str(&if optional { format!(" [{}]", value) } else { format!(" <{}>", value) });
+    }
+    spelling
+}
+
+// Help text followed by the accepted and default values
+fn description(arg: &Arg) -> String {
+    let mut description = arg.get_help().map(|help| help.to_string()).unwrap_or_default();
+    let choices: Vec<String> = arg.get_possible_values().iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    if arg.get_action().takes_values() && !choices.is_empty() {
+        description.push_str(&format!(" [possible values: {}]", choices.join(", ")));
+    }
+    let defaults: Vec<String> = arg.get_default_values().iter().map(|value| value.to_string_lossy().into_owned()).collect();
+    if arg.get_action().takes_values() && !defaults.is_empty() {
+        description.push_str(&format!(" [default: {}]", defaults.join(",")));
+    }
+    description
+}
+
+fn man(cli: &mut Command) -> String {
+    let name = cli.get_name().to_string();
+    let about = cli.get_about().map(|about| about.to_string()).unwrap_or_default();
+    let mut page = format!(".TH {} 1 \"\" \"{} {}\" \"User Commands\"\n", name.to_uppercase(), name, cli.get_version().unwrap_or_default());
+    page.push_str(&format!(".SH NAME\n{} \\- {}\n", name, roff(&about)));
+    
+    page.push_str(".SH SYNOPSIS\n");
+    page.push_str(&format!("\\fB{}\\fR\n", roff(&usage(cli))));
+    for mut command in subcommands(cli) {
+        page.push_str(&format!(".br\n\\fB{}\\fR\n", roff(&usage(&mut command))));
+    }
+    
+    page.push_str(".SH DESCRIPTION\n");
+    page.push_str(&format!("{}\n.PP\n", roff(&about)));
+    page.push_str("Profiles are read from config.toml next to the executable, which is created with the defaults on first run.\n");
+    page.push_str("Files and folders can also be dragged onto the executable.\n");
+    
+    page.push_str(".SH OPTIONS\n");
+    page.push_str(&man_arguments(cli));
+    
+    page.push_str(".SH COMMANDS\n");
+    for command in subcommands(cli) {
+        page.push_str(&format!(".SS {}\n", command.get_name()));
+        page.push_str(&format!("{}\n", roff(&command.get_about().map(|about| about.to_string()).unwrap_or_default())));
+        page.push_str(&man_arguments(&command));
+    }
+    
+    page.push_str(".SH EXAMPLES\n");
+    for (command, description) in EXAMPLES {
+        page.push_str(&format!(".TP\n\\fB{}\\fR\n{}\n", roff(command), roff(description)));
+    }
+    page
+}
+
+fn man_arguments(command: &Command) -> String {
+    arguments(command).into_iter()
+        .map(|arg| format!(".TP\n\\fB{}\\fR\n{}\n", roff(&spelling(arg)), roff(&description(arg))))
+        .collect()
+}
+
+// Escape backslashes and dashes, and keep lines from being read as requests
+fn roff(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let line = line.replace('\\', "\\e").replace('-', "\\-");
+            if line.starts_with(['.', '\'']) { format!("\\&{}", line) } else { line }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn markdown(cli: &mut Command) -> String {
+    let name = cli.get_name().to_string();
+    let mut doc = format!("# {}\n\n", name);
+    doc.push_str(&format!("{}\n\n", cli.get_about().map(|about| about.to_string()).unwrap_or_default()));
+    doc.push_str("<!-- Generated by `ormseparatev3 docs markdown`, edit the clap definitions instead -->\n\n");
+    
+    doc.push_str(&format!("## Usage\n\n```\n{}\n", usage(cli)));
+    for mut command in subcommands(cli) {
+        doc.push_str(&format!("{}\n", usage(&mut command)));
+    }
+    doc.push_str("```\n\n");
+    
+    doc.push_str("## Options\n\n");
+    doc.push_str(&markdown_arguments(cli));
+    
+    doc.push_str("## Commands\n\n");
+    for command in subcommands(cli) {
+        doc.push_str(&format!("### {}\n\n", command.get_name()));
+        doc.push_str(&format!("{}\n\n", command.get_about().map(|about| about.to_string()).unwrap_or_default()));
+        doc.push_str(&markdown_arguments(&command));
+    }
+    
+    doc.push_str("## Examples\n\n```\n");
+    let width = EXAMPLES.iter().map(|(command, _)| command.len()).max().unwrap_or(0);
+    for (command, description) in EXAMPLES {
+        doc.push_str(&format!("{:<width$}  # {}\n", command, description));
+    }
+    doc.push_str("```\n");
+    doc
+}
+
+fn markdown_arguments(command: &Command) -> String {
+    let mut table = String::from("| Argument | Description |\n| --- | --- |\n");
+    for arg in arguments(command) {
+        table.push_str(&format!("| `{}` | {} |\n", spelling(arg), description(arg).replace('|', "\\|")));
+    }
+    table.push('\n');
+    table
+}
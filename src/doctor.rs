@@ -0,0 +1,129 @@
+// Environment report for bug reports: config, profiles, formats and CPU, with anything broken flagged
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use image::ImageFormat;
+
+use crate::{Config, FileMatcher, default_extensions, load_channel_resources, validate_profile};
+
+// Doesn't create the config like a normal run would, so it can be pointed at a broken setup
+pub fn doctor(exe_dir: &Path, config_path: &Path) -> Result<()> {
+    let mut problems = 0;
+    let mut problem = |message: String| {
+        println!("  Error: {}", message);
+        problems += 1;
+    };
+    
+    println!("ormseparatev3 {} on {} {}", env!("CARGO_PKG_VERSION"), std::env::consts::OS, std::env::consts::ARCH);
+    println!();
+    println!("Config");
+    println!("  Path: {}", config_path.display());
+    
+    let probe = exe_dir.join(format!(".ormseparatev3-doctor-{}", std::process::id()));
+    match fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            println!("  OK: {} is writable", exe_dir.display());
+        }
+        Err(err) => problem(format!("{} is not writable, the default config can't be created: {}", exe_dir.display(), err)),
+    }
+    
+    let config = if config_path.exists() {
+        match fs::read_to_string(config_path).map_err(anyhow::Error::from).and_then(|content| Ok(toml::from_str::<Config>(&content)?)) {
+            Ok(config) => {
+                println!("  OK: parsed, {} profiles, default '{}'", config.profiles.len(), config.default_profile);
+                Some(config)
+            }
+            Err(err) => {
+                problem(format!("can't be read: {:#}", err));
+                None
+            }
+        }
+    } else {
+        println!("  Not found, the default config will be created on the next run");
+        Some(Config::default())
+    };
+    
+    if let Some(config) = &config {
+        if !config.profiles.contains_key(&config.default_profile) {
+            problem(format!("default profile '{}' is not defined", config.default_profile));
+        }
+        
+        println!();
+        println!("Profiles");
+        let mut names: Vec<&String> = config.profiles.keys().collect();
+        names.sort();
+        for name in names {
+            let mut profile = config.profiles[name].clone();
+            let checked = load_channel_resources(&mut profile, exe_dir)
+                .and_then(|_| validate_profile(&profile))
+                .and_then(|_| FileMatcher::new(&profile).map(|_| ()));
+            match checked {
+                Ok(()) => println!("  OK: {} ({} channels, {})", name, profile.channels.len(), profile.output_format),
+                Err(err) => problem(format!("{}: {:#}", name, err)),
+            }
+        }
+    }
+    
+    println!();
+    println!("Formats");
+    println!("  Extension  Read  Write");
+    for extension in default_extensions() {
+        // PSD is read by our own decoder and only written as psd-layered
+        let (read, write) = match extension.as_str() {
+            "psd" | "psb" => (true, extension == "psd"),
+            _ => ImageFormat::from_extension(&extension)
+                .map_or((false, false), |format| (format.reading_enabled(), format.writing_enabled())),
+        };
+        let mark = |enabled: bool| if enabled { "yes" } else { "no" };
+        println!("  {:<9}  {:<4}  {}", extension, mark(read), mark(write));
+    }
+    println!("  Layered outputs: tiff-layered, psd-layered, exr-multichannel, ktx2-array");
+    
+    println!();
+    println!("CPU");
+    let cores = std::thread::available_parallelism().map_or(1, |count| count.get());
+    println!("  Logical cores: {}", cores);
+    let features = simd_features();
+    println!("  SIMD: {}", if features.is_empty() { "none detected".to_string() } else { features.join(", ") });
+    match std::env::var("RAYON_NUM_THREADS") {
+        Ok(threads) => println!("  Thread pool: {} threads (RAYON_NUM_THREADS={})", rayon::current_num_threads(), threads),
+        Err(_) => println!("  Thread pool: {} threads", rayon::current_num_threads()),
+    }
+    
+    println!();
+    if problems > 0 {
+        return Err(anyhow!("{} problems found", problems));
+    }
+    println!("No problems found");
+    Ok(())
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn simd_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    macro_rules! detect {
+        ($($feature:tt),*) => {
+            $(if std::arch::is_x86_feature_detected!($feature) {
+                features.push($feature);
+            })*
+        };
+    }
+    detect!("sse2", "sse4.1", "sse4.2", "avx", "avx2", "fma", "avx512f");
+    features
+}
+
+#[cfg(target_arch = "aarch64")]
+fn simd_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        features.push("neon");
+    }
+    features
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+fn simd_features() -> Vec<&'static str> {
+    Vec::new()
+}
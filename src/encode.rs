@@ -0,0 +1,223 @@
+// Output encoding with explicit, fixed settings so repeated runs produce identical bytes
+use std::io::{Cursor, Write};
+
+use anyhow::{Result, anyhow};
+use image::{ColorType, DynamicImage, ImageEncoder, ImageFormat, RgbaImage};
+use image::codecs::png::{CompressionType, FilterType as PngFilterType, PngEncoder};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::tga::TgaEncoder;
+use image::codecs::webp::WebPEncoder;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+
+use crate::metadata::{JPEG_EXIF_HEADER, JPEG_ICC_HEADER, JPEG_XMP_HEADER, Metadata, PNG_XMP_KEYWORD};
+use crate::{ColorSpace, JpegSubsampling, PngCompression, PngFilter, Profile, TgaOrigin};
+
+// Byte 17 of the fixed 18-byte TGA header, bit 5 marks top-left origin
+const TGA_DESCRIPTOR_OFFSET: usize = 17;
+const TGA_TOP_ORIGIN_BIT: u8 = 0x20;
+
+// Encode in memory, so a failing encoder never leaves a partial file behind and
+// callers can compare outputs before writing them
+pub fn encode_image(img: &DynamicImage, format: &str, profile: &Profile, color_space: Option<ColorSpace>, metadata: Option<&Metadata>) -> Result<Vec<u8>> {
+    let format = format.to_ascii_lowercase();
+    
+    let mut writer = Cursor::new(Vec::new());
+    
+    match format.as_str() {
+        "png" => {
+            let compression = match profile.png_compression {
+                PngCompression::Fast => CompressionType::Fast,
+                PngCompression::Default => CompressionType::Default,
+                PngCompression::Best => CompressionType::Best,
+            };
+            let filter = match profile.png_filter {
+                PngFilter::None => PngFilterType::NoFilter,
+                PngFilter::Sub => PngFilterType::Sub,
+                PngFilter::Up => PngFilterType::Up,
+                PngFilter::Avg => PngFilterType::Avg,
+                PngFilter::Paeth => PngFilterType::Paeth,
+                PngFilter::Adaptive => PngFilterType::Adaptive,
+            };
+            PngEncoder::new_with_quality(&mut writer, compression, filter)
+                .write_image(img.as_bytes(), img.width(), img.height(), img.color())?;
+            let mut chunks = color_space.map(color_space_chunks).unwrap_or_default();
+            if let Some(metadata) = metadata {
+                // An embedded profile would contradict the color space tag
+                chunks.extend(png_metadata_chunks(metadata, color_space.is_none()));
+            }
+            insert_png_chunks(writer.get_mut(), &chunks);
+        }
+        "jpg" | "jpeg" => {
+            let img = rgba8(img, &format)?;
+            let mut encoder = JpegEncoder::new_with_quality(&mut writer, profile.jpeg_quality.clamp(1, 100));
+            match profile.jpeg_subsampling {
+                JpegSubsampling::None => {
+                    if !is_grayscale(img) {
+                        return Err(anyhow!("jpeg_subsampling = \"none\" needs grayscale output, use \"4:2:2\" for color images"));
+                    }
+                    let luma: Vec<u8> = img.pixels().map(|pixel| pixel[0]).collect();
+                    encoder.encode(&luma, img.width(), img.height(), ColorType::L8)?;
+                }
+                // JPEG has no alpha, so only the color channels are encoded
+                JpegSubsampling::Yuv422 => {
+                    let rgb: Vec<u8> = img.pixels().flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+                    encoder.encode(&rgb, img.width(), img.height(), ColorType::Rgb8)?;
+                }
+            }
+            if let Some(metadata) = metadata {
+                insert_jpeg_segments(writer.get_mut(), metadata);
+            }
+        }
+        "tga" => {
+            let img = rgba8(img, &format)?;
+            let mut encoder = TgaEncoder::new(&mut writer);
+            if !profile.tga_rle {
+                encoder = encoder.disable_rle();
+            }
+            match profile.tga_origin {
+                TgaOrigin::Top => encoder.encode(img.as_raw(), img.width(), img.height(), ColorType::Rgba8)?,
+                TgaOrigin::Bottom => {
+                    let flipped = image::imageops::flip_vertical(img);
+                    encoder.encode(flipped.as_raw(), img.width(), img.height(), ColorType::Rgba8)?;
+                    // The encoder always flags a top-left origin, clear that bit in the image descriptor
+                    writer.get_mut()[TGA_DESCRIPTOR_OFFSET] &= !TGA_TOP_ORIGIN_BIT;
+                }
+            }
+        }
+        "webp" => {
+            if !profile.lossless {
+                return Err(anyhow!("Lossy WebP encoding is not available in this build, set lossless = true"));
+            }
+            let img = rgba8(img, &format)?;
+            let quality = profile.quality.unwrap_or(100).min(100);
+            let data = near_lossless(img.as_raw(), quality);
+            WebPEncoder::new_lossless(&mut writer)
+                .encode(&data, img.width(), img.height(), ColorType::Rgba8)?;
+        }
+        _ => {
+            let image_format = ImageFormat::from_extension(&format)
+                .ok_or_else(|| anyhow!("Unsupported output format: {}", format))?;
+            img.write_to(&mut writer, image_format)?;
+        }
+    }
+    
+    Ok(writer.into_inner())
+}
+
+// cICP is the modern color space tag, gAMA/sRGB are there for older viewers
+fn color_space_chunks(color_space: ColorSpace) -> Vec<([u8; 4], Vec<u8>)> {
+    match color_space {
+        // Rendering intent 0 is perceptual, gAMA holds 1/2.2 scaled by 100000
+        ColorSpace::Srgb => vec![(*b"sRGB", vec![0]), (*b"gAMA", 45455u32.to_be_bytes().to_vec()), (*b"cICP", vec![1, 13, 0, 1])],
+        ColorSpace::Linear => vec![(*b"gAMA", 100000u32.to_be_bytes().to_vec()), (*b"cICP", vec![1, 8, 0, 1])],
+    }
+}
+
+fn png_metadata_chunks(metadata: &Metadata, include_icc: bool) -> Vec<([u8; 4], Vec<u8>)> {
+    let mut chunks = Vec::new();
+    
+    // Profile name, null, compression method 0 (zlib), compressed profile
+    if include_icc && let Some(icc) = &metadata.icc {
+        let mut data = b"ICC Profile\0\0".to_vec();
+        let mut encoder = ZlibEncoder::new(&mut data, Compression::default());
+        if encoder.write_all(icc).and_then(|_| encoder.finish()).is_ok() {
+            chunks.push((*b"iCCP", data));
+        }
+    }
+    if let Some(exif) = &metadata.exif {
+        chunks.push((*b"eXIf", exif.clone()));
+    }
+    // Uncompressed iTXt with empty language tag and translated keyword
+    if let Some(xmp) = &metadata.xmp {
+        let mut data = PNG_XMP_KEYWORD.to_vec();
+        data.extend_from_slice(&[0, 0, 0, 0, 0]);
+        data.extend_from_slice(xmp);
+        chunks.push((*b"iTXt", data));
+    }
+    
+    chunks
+}
+
+// Insert chunks right after IHDR, where decoders expect them before image data
+pub fn insert_png_chunks(png: &mut Vec<u8>, chunks: &[([u8; 4], Vec<u8>)]) {
+    let mut encoded = Vec::new();
+    for (kind, data) in chunks {
+        encoded.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        encoded.extend_from_slice(kind);
+        encoded.extend_from_slice(data);
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(kind);
+        crc.update(data);
+        encoded.extend_from_slice(&crc.finalize().to_be_bytes());
+    }
+    
+    // 8-byte signature, then IHDR with its length, type, 13 data bytes and CRC
+    let ihdr_end = 8 + 4 + 4 + 13 + 4;
+    png.splice(ihdr_end..ihdr_end, encoded);
+}
+
+// Insert APP1 (EXIF, XMP) and APP2 (ICC) segments after SOI and the encoder's JFIF header
+fn insert_jpeg_segments(jpeg: &mut Vec<u8>, metadata: &Metadata) {
+    let mut segments: Vec<(u8, Vec<u8>)> = Vec::new();
+    if let Some(exif) = &metadata.exif {
+        segments.push((0xE1, [JPEG_EXIF_HEADER, exif].concat()));
+    }
+    if let Some(xmp) = &metadata.xmp {
+        segments.push((0xE1, [JPEG_XMP_HEADER, xmp].concat()));
+    }
+    // Profiles are split into numbered parts that fit a segment
+    if let Some(icc) = &metadata.icc {
+        let parts: Vec<&[u8]> = icc.chunks(JPEG_MAX_SEGMENT - JPEG_ICC_HEADER.len() - 2).collect();
+        for (index, part) in parts.iter().enumerate() {
+            segments.push((0xE2, [JPEG_ICC_HEADER, &[index as u8 + 1, parts.len() as u8], part].concat()));
+        }
+    }
+    
+    let mut encoded = Vec::new();
+    for (marker, data) in segments {
+        // Blocks too large for a single segment can't be stored
+        if data.len() > JPEG_MAX_SEGMENT {
+            continue;
+        }
+        encoded.extend_from_slice(&[0xFF, marker]);
+        encoded.extend_from_slice(&(data.len() as u16 + 2).to_be_bytes());
+        encoded.extend_from_slice(&data);
+    }
+    
+    let mut pos = 2;
+    if jpeg.get(2..4) == Some(&[0xFF, 0xE0]) {
+        pos += 2 + u16::from_be_bytes([jpeg[4], jpeg[5]]) as usize;
+    }
+    jpeg.splice(pos..pos, encoded);
+}
+
+// Largest payload of a marker segment, its 16-bit length includes the length field
+const JPEG_MAX_SEGMENT: usize = 65533;
+
+// Formats encoded by hand here only take 8-bit data
+fn rgba8<'a>(img: &'a DynamicImage, format: &str) -> Result<&'a RgbaImage> {
+    img.as_rgba8()
+        .ok_or_else(|| anyhow!("{} output only supports bit_depth = \"8\"", format))
+}
+
+fn is_grayscale(img: &RgbaImage) -> bool {
+    img.pixels().all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2])
+}
+
+// Round color values to coarser steps as quality drops (alpha is kept exact),
+// which lossless WebP compresses much better. Quality 100 leaves data untouched
+fn near_lossless(data: &[u8], quality: u8) -> Vec<u8> {
+    let dropped_bits = (100 - quality as u32).div_ceil(20);
+    if dropped_bits == 0 {
+        return data.to_vec();
+    }
+    
+    let step = 1u32 << dropped_bits;
+    data.chunks(4)
+        .flat_map(|pixel| {
+            let round = |value: u8| ((value as u32 + step / 2) / step * step).min(255) as u8;
+            [round(pixel[0]), round(pixel[1]), round(pixel[2]), pixel[3]]
+        })
+        .collect()
+}
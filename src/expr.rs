@@ -0,0 +1,431 @@
+// Per-pixel channel math: a small expression language over the normalized source channels r, g, b and a.
+// Expressions are parsed once and evaluated column-wise over blocks of pixels
+use anyhow::{Result, anyhow};
+
+#[derive(Debug, Clone)]
+pub struct Expression {
+    root: Node,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Number(f32),
+    // Index into the r, g, b, a inputs
+    Input(usize),
+    Negate(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Function {
+    Min,
+    Max,
+    Clamp,
+    Abs,
+    Sqrt,
+    Pow,
+    Exp,
+    Log,
+    Floor,
+    Ceil,
+    Round,
+    Sin,
+    Cos,
+    Mix,
+    Step,
+    Smoothstep,
+    If,
+}
+
+impl Function {
+    // Name, minimum and maximum argument count
+    const ALL: [(&'static str, Function, usize, usize); 18] = [
+        ("min", Function::Min, 2, usize::MAX),
+        ("max", Function::Max, 2, usize::MAX),
+        ("clamp", Function::Clamp, 3, 3),
+        ("abs", Function::Abs, 1, 1),
+        ("sqrt", Function::Sqrt, 1, 1),
+        ("pow", Function::Pow, 2, 2),
+        ("exp", Function::Exp, 1, 1),
+        ("log", Function::Log, 1, 1),
+        ("floor", Function::Floor, 1, 1),
+        ("ceil", Function::Ceil, 1, 1),
+        ("round", Function::Round, 1, 1),
+        ("sin", Function::Sin, 1, 1),
+        ("cos", Function::Cos, 1, 1),
+        ("mix", Function::Mix, 3, 3),
+        ("lerp", Function::Mix, 3, 3),
+        ("step", Function::Step, 2, 2),
+        ("smoothstep", Function::Smoothstep, 3, 3),
+        ("if", Function::If, 3, 3),
+    ];
+}
+
+// Pixels evaluated per block, bounds the memory of intermediate columns
+const BLOCK_SIZE: usize = 4096;
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens: &tokens, pos: 0 };
+        let root = parser.comparison()?;
+        if let Some(token) = parser.peek() {
+            return Err(anyhow!("Unexpected {} in expression: {}", token, source));
+        }
+        Ok(Expression { root })
+    }
+    
+    // Evaluate for interleaved RGBA samples in 0-1, one value per pixel. NaN results become 0
+    pub fn eval(&self, rgba: &[f32]) -> Vec<f32> {
+        let mut output = Vec::with_capacity(rgba.len() / 4);
+        
+        for block in rgba.chunks(BLOCK_SIZE * 4) {
+            let inputs: [Vec<f32>; 4] = std::array::from_fn(|channel| {
+                block.chunks_exact(4).map(|pixel| pixel[channel]).collect()
+            });
+            let values = eval_node(&self.root, &inputs, block.len() / 4);
+            output.extend(values.into_iter().map(|value| if value.is_nan() { 0.0 } else { value }));
+        }
+        
+        output
+    }
+}
+
+fn eval_node(node: &Node, inputs: &[Vec<f32>; 4], len: usize) -> Vec<f32> {
+    match node {
+        Node::Number(value) => vec![*value; len],
+        Node::Input(index) => inputs[*index].clone(),
+        Node::Negate(operand) => {
+            let mut values = eval_node(operand, inputs, len);
+            values.iter_mut().for_each(|value| *value = -*value);
+            values
+        }
+        Node::Binary(op, left, right) => {
+            let mut values = eval_node(left, inputs, len);
+            let right = eval_node(right, inputs, len);
+            let apply = |a: f32, b: f32| -> f32 {
+                let truth = |condition: bool| if condition { 1.0 } else { 0.0 };
+                match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Sub => a - b,
+                    BinaryOp::Mul => a * b,
+                    BinaryOp::Div => a / b,
+                    BinaryOp::Rem => a % b,
+                    BinaryOp::Pow => a.powf(b),
+                    BinaryOp::Less => truth(a < b),
+                    BinaryOp::LessEqual => truth(a <= b),
+                    BinaryOp::Greater => truth(a > b),
+                    BinaryOp::GreaterEqual => truth(a >= b),
+                    BinaryOp::Equal => truth(a == b),
+                    BinaryOp::NotEqual => truth(a != b),
+                }
+            };
+            values.iter_mut().zip(&right).for_each(|(a, &b)| *a = apply(*a, b));
+            values
+        }
+        Node::Call(function, args) => {
+            let args: Vec<Vec<f32>> = args.iter().map(|arg| eval_node(arg, inputs, len)).collect();
+            (0..len).map(|i| call(*function, |n| args[n][i], args.len())).collect()
+        }
+    }
+}
+
+fn call(function: Function, arg: impl Fn(usize) -> f32, count: usize) -> f32 {
+    match function {
+        Function::Min => (1..count).fold(arg(0), |acc, n| acc.min(arg(n))),
+        Function::Max => (1..count).fold(arg(0), |acc, n| acc.max(arg(n))),
+        Function::Clamp => arg(0).max(arg(1)).min(arg(2)),
+        Function::Abs => arg(0).abs(),
+        Function::Sqrt => arg(0).sqrt(),
+        Function::Pow => arg(0).powf(arg(1)),
+        Function::Exp => arg(0).exp(),
+        Function::Log => arg(0).ln(),
+        Function::Floor => arg(0).floor(),
+        Function::Ceil => arg(0).ceil(),
+        Function::Round => arg(0).round(),
+        Function::Sin => arg(0).sin(),
+        Function::Cos => arg(0).cos(),
+        Function::Mix => arg(0) + (arg(1) - arg(0)) * arg(2),
+        Function::Step => if arg(1) >= arg(0) { 1.0 } else { 0.0 },
+        Function::Smoothstep => {
+            let t = ((arg(2) - arg(0)) / (arg(1) - arg(0))).clamp(0.0, 1.0);
+            t * t * (3.0 - 2.0 * t)
+        }
+        Function::If => if arg(0) != 0.0 { arg(1) } else { arg(2) },
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "number {}", value),
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Symbol(symbol) => write!(f, "'{}'", symbol),
+        }
+    }
+}
+
+// Longer symbols first so "<=" isn't read as "<" and "="
+const SYMBOLS: [&str; 15] = ["<=", ">=", "==", "!=", "**", "+", "-", "*", "/", "%", "^", "<", ">", "(", ")"];
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() || c == '.' {
+            let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '.')).unwrap_or(rest.len());
+            // Exponents like 1e-3 continue past the sign
+            let end = if rest[..end].ends_with(['e', 'E']) && rest[end..].starts_with(['+', '-']) {
+                end + 1 + rest[end + 1..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - end - 1)
+            } else {
+                end
+            };
+            let value = rest[..end].parse()
+                .map_err(|_| anyhow!("Invalid number '{}' in expression: {}", &rest[..end], source))?;
+            tokens.push(Token::Number(value));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_ascii_lowercase()));
+            rest = &rest[end..];
+        } else if c == ',' {
+            tokens.push(Token::Symbol(","));
+            rest = &rest[1..];
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push(Token::Symbol(if *symbol == "**" { "^" } else { symbol }));
+            rest = &rest[symbol.len()..];
+        } else {
+            return Err(anyhow!("Unexpected '{}' in expression: {}", c, source));
+        }
+        rest = rest.trim_start();
+    }
+    
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+    
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+    
+    fn expect(&mut self, symbol: &str) -> Result<()> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(token) => Err(anyhow!("Expected '{}' but found {}", symbol, token)),
+            None => Err(anyhow!("Expected '{}' at the end of the expression", symbol)),
+        }
+    }
+    
+    // Comparisons bind loosest and yield 1 or 0
+    fn comparison(&mut self) -> Result<Node> {
+        let mut node = self.additive()?;
+        loop {
+            let op = if self.eat("<=") {
+                BinaryOp::LessEqual
+            } else if self.eat(">=") {
+                BinaryOp::GreaterEqual
+            } else if self.eat("==") {
+                BinaryOp::Equal
+            } else if self.eat("!=") {
+                BinaryOp::NotEqual
+            } else if self.eat("<") {
+                BinaryOp::Less
+            } else if self.eat(">") {
+                BinaryOp::Greater
+            } else {
+                return Ok(node);
+            };
+            node = Node::Binary(op, Box::new(node), Box::new(self.additive()?));
+        }
+    }
+    
+    fn additive(&mut self) -> Result<Node> {
+        let mut node = self.multiplicative()?;
+        loop {
+            let op = if self.eat("+") {
+                BinaryOp::Add
+            } else if self.eat("-") {
+                BinaryOp::Sub
+            } else {
+                return Ok(node);
+            };
+            node = Node::Binary(op, Box::new(node), Box::new(self.multiplicative()?));
+        }
+    }
+    
+    fn multiplicative(&mut self) -> Result<Node> {
+        let mut node = self.unary()?;
+        loop {
+            let op = if self.eat("*") {
+                BinaryOp::Mul
+            } else if self.eat("/") {
+                BinaryOp::Div
+            } else if self.eat("%") {
+                BinaryOp::Rem
+            } else {
+                return Ok(node);
+            };
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+    }
+    
+    // -x^2 is -(x^2), as in maths
+    fn unary(&mut self) -> Result<Node> {
+        if self.eat("-") {
+            return Ok(Node::Negate(Box::new(self.unary()?)));
+        }
+        if self.eat("+") {
+            return self.unary();
+        }
+        self.power()
+    }
+    
+    // Right-associative, 2^3^2 is 2^9
+    fn power(&mut self) -> Result<Node> {
+        let base = self.primary()?;
+        if self.eat("^") {
+            return Ok(Node::Binary(BinaryOp::Pow, Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+    
+    fn primary(&mut self) -> Result<Node> {
+        let token = self.peek().cloned()
+            .ok_or_else(|| anyhow!("Unexpected end of expression"))?;
+        self.pos += 1;
+        
+        match token {
+            Token::Number(value) => Ok(Node::Number(value)),
+            Token::Symbol("(") => {
+                let node = self.comparison()?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Token::Ident(name) if self.eat("(") => {
+                let (_, function, min_args, max_args) = Function::ALL.iter()
+                    .find(|(function_name, ..)| *function_name == name)
+                    .ok_or_else(|| anyhow!("Unknown function '{}'", name))?;
+                
+                let mut args = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        args.push(self.comparison()?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                if args.len() < *min_args || args.len() > *max_args {
+                    return Err(anyhow!("Wrong number of arguments for {}(): {}", name, args.len()));
+                }
+                Ok(Node::Call(*function, args))
+            }
+            Token::Ident(name) => match name.as_str() {
+                "r" => Ok(Node::Input(0)),
+                "g" => Ok(Node::Input(1)),
+                "b" => Ok(Node::Input(2)),
+                "a" => Ok(Node::Input(3)),
+                "pi" => Ok(Node::Number(std::f32::consts::PI)),
+                _ => Err(anyhow!("Unknown variable '{}', use r, g, b or a", name)),
+            },
+            token => Err(anyhow!("Unexpected {}", token)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Value of an expression for one pixel
+    fn eval(source: &str, rgba: [f32; 4]) -> f32 {
+        Expression::parse(source).unwrap().eval(&rgba)[0]
+    }
+    
+    #[test]
+    fn precedence_and_associativity() {
+        let pixel = [0.0; 4];
+        assert_eq!(eval("1 + 2 * 3", pixel), 7.0);
+        assert_eq!(eval("(1 + 2) * 3", pixel), 9.0);
+        assert_eq!(eval("10 - 4 - 3", pixel), 3.0);
+        assert_eq!(eval("2 ^ 3 ^ 2", pixel), 512.0);
+        assert_eq!(eval("2 ** 3", pixel), 8.0);
+        assert_eq!(eval("-2 ^ 2", pixel), -4.0);
+        assert_eq!(eval("7 % 4", pixel), 3.0);
+        assert_eq!(eval("1 + 1 == 2", pixel), 1.0);
+        assert_eq!(eval("1 < 2 < 3", pixel), 1.0);
+    }
+    
+    #[test]
+    fn numbers_inputs_and_functions() {
+        let pixel = [0.25, 0.5, 0.75, 1.0];
+        assert_eq!(eval("r + g + b + a", pixel), 2.5);
+        assert_eq!(eval("1 - R", pixel), 0.75);
+        assert_eq!(eval("1e-1 * 10 + .5 + 2E+1", pixel), 21.5);
+        assert_eq!(eval("max(r, g, b)", pixel), 0.75);
+        assert_eq!(eval("clamp(b * 2, 0, 1)", pixel), 1.0);
+        assert_eq!(eval("lerp(0, 10, g)", pixel), 5.0);
+        assert_eq!(eval("if(r > g, 1, 2)", pixel), 2.0);
+        assert_eq!(eval("step(0.5, g)", pixel), 1.0);
+        assert_eq!(eval("round(cos(pi))", pixel), -1.0);
+    }
+    
+    #[test]
+    fn evaluates_every_pixel_and_zeroes_nan() {
+        let expression = Expression::parse("r / g").unwrap();
+        assert_eq!(expression.eval(&[0.5, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]), [0.5, 0.0]);
+        
+        let pixels: Vec<f32> = (0..BLOCK_SIZE * 3).flat_map(|i| [i as f32, 0.0, 0.0, 0.0]).collect();
+        let values = Expression::parse("r").unwrap().eval(&pixels);
+        assert_eq!(values.len(), BLOCK_SIZE * 3);
+        assert_eq!(values[BLOCK_SIZE * 2 + 1], (BLOCK_SIZE * 2 + 1) as f32);
+    }
+    
+    #[test]
+    fn parse_errors() {
+        for source in ["", "1 +", "(r", "r g", "x", "foo(r)", "min(r)", "clamp(r, 0)", "1..2", "r $ g", "max(r,)"] {
+            assert!(Expression::parse(source).is_err(), "{}", source);
+        }
+    }
+}
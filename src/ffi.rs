@@ -0,0 +1,114 @@
+// C ABI for embedding the splitter in native plugins, see include/ormsep.h. Functions return 0 or a
+// pointer on success; on failure -1 or NULL, with the message available from ormsep_last_error
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow, Context};
+
+use crate::{CancelToken, Config, FileMatcher, NoProgress, load_channel_resources, summarized_process_file, validate_profile};
+
+pub type ProgressCallback = Option<unsafe extern "C" fn(message: *const c_char, user_data: *mut c_void)>;
+
+// A parsed config, relative LUT paths resolve against the folder it was loaded from
+pub struct OrmsepConfig {
+    config: Config,
+    dir: PathBuf,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(err: &anyhow::Error) {
+    let message = CString::new(format!("{:#}", err).replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+unsafe fn string<'a>(text: *const c_char, what: &str) -> Result<&'a str> {
+    if text.is_null() {
+        return Err(anyhow!("{} is NULL", what));
+    }
+    unsafe { CStr::from_ptr(text) }.to_str().with_context(|| format!("{} is not valid UTF-8", what))
+}
+
+/// Load a config.toml. Unlike the executable this never creates a default config.
+///
+/// # Safety
+/// `path` must be a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ormsep_load_config(path: *const c_char) -> *mut OrmsepConfig {
+    let loaded = (|| {
+        let path = Path::new(unsafe { string(path, "path") }?);
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        let config: Config = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        Ok(OrmsepConfig { config, dir })
+    })();
+    match loaded {
+        Ok(config) => Box::into_raw(Box::new(config)),
+        Err(err) => {
+            set_last_error(&err);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `config` must come from `ormsep_load_config` and not be used afterwards. NULL is ignored.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ormsep_free_config(config: *mut OrmsepConfig) {
+    if !config.is_null() {
+        drop(unsafe { Box::from_raw(config) });
+    }
+}
+
+/// Split one image next to itself with a profile of the config, NULL for the default profile.
+/// `progress` (may be NULL) receives each log line the executable would print.
+///
+/// # Safety
+/// `config` must be a live config, `path` and `profile` NUL-terminated strings. `user_data` is only
+/// passed through to `progress`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ormsep_process_file(config: *const OrmsepConfig, profile: *const c_char, path: *const c_char,
+    progress: ProgressCallback, user_data: *mut c_void) -> c_int
+{
+    let processed = (|| {
+        let config = unsafe { config.as_ref() }.ok_or_else(|| anyhow!("config is NULL"))?;
+        let profile_name = if profile.is_null() { None } else { Some(unsafe { string(profile, "profile") }?) };
+        let file = Path::new(unsafe { string(path, "path") }?);
+        
+        let mut profile = config.config.get_profile(profile_name)?;
+        load_channel_resources(&mut profile, &config.dir)?;
+        validate_profile(&profile)?;
+        let matcher = FileMatcher::new(&profile)?;
+        
+        let root = file.parent().unwrap_or(Path::new("."));
+        let mut log = Vec::new();
+        let (_, result) = summarized_process_file(file, root, &profile, &matcher, &mut log, &NoProgress, (0, 1), &CancelToken::new());
+        if let Some(progress) = progress {
+            for line in log {
+                let line = CString::new(line.replace('\0', " ")).unwrap_or_default();
+                unsafe { progress(line.as_ptr(), user_data) };
+            }
+        }
+        result
+    })();
+    match processed {
+        Ok(()) => 0,
+        Err(err) => {
+            set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// The message of the last failed call on this thread, NULL if there was none. Valid until the
+/// next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn ormsep_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(std::ptr::null(), |message| message.as_ptr()))
+}
@@ -0,0 +1,98 @@
+// Synthetic packed textures for trying out profiles and benchmarking, without hunting for sample assets
+use std::f32::consts::TAU;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow, Context};
+use image::{DynamicImage, Rgba, Rgba32FImage};
+
+use crate::{BitDepth, Profile, encode_image};
+
+// Each channel gets a different shape so swapped or mixed up channels are easy to spot
+const PATTERNS: &[&str] = &["gradient", "noise", "channels"];
+
+// Parse "1024" as a square size or "1000x600" as width x height
+pub fn parse_size(text: &str) -> Result<(u32, u32), String> {
+    let (width, height) = text.split_once(['x', 'X']).unwrap_or((text, text));
+    let parse = |value: &str| value.trim().parse::<u32>().ok().filter(|&value| value > 0);
+    match (parse(width), parse(height)) {
+        (Some(width), Some(height)) => Ok((width, height)),
+        _ => Err(format!("expected a size like 1024 or 1000x600, got '{}'", text)),
+    }
+}
+
+// Write every pattern at every size and depth into dir, named {pattern}_{width}x{height}_{depth}_ORM
+// so the default profile picks them up. 8 and 16 bit files are PNG, 32f files EXR
+pub fn generate_test(dir: &Path, sizes: &[(u32, u32)], depths: &[BitDepth], alpha: bool, seed: u32, profile: &Profile) -> Result<()> {
+    let paths = write_test_images(dir, sizes, depths, alpha, seed, profile)?;
+    for path in &paths {
+        println!("Generated: {}", path.display());
+    }
+    println!("Generated {} test images in {}", paths.len(), dir.display());
+    Ok(())
+}
+
+pub fn write_test_images(dir: &Path, sizes: &[(u32, u32)], depths: &[BitDepth], alpha: bool, seed: u32, profile: &Profile) -> Result<Vec<PathBuf>> {
+    if sizes.is_empty() || depths.is_empty() {
+        return Err(anyhow!("Nothing to generate, give at least one size and one bit depth"));
+    }
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create output directory: {}", dir.display()))?;
+    
+    let mut paths = Vec::new();
+    for &(width, height) in sizes {
+        for pattern in PATTERNS {
+            let img = DynamicImage::ImageRgba32F(render(pattern, width, height, seed));
+            for &depth in depths {
+                let (img, label, format) = match (depth, alpha) {
+                    (BitDepth::Eight, false) => (DynamicImage::ImageRgb8(img.to_rgb8()), "8bit", "png"),
+                    (BitDepth::Eight, true) => (DynamicImage::ImageRgba8(img.to_rgba8()), "8bit", "png"),
+                    (BitDepth::Sixteen, false) => (DynamicImage::ImageRgb16(img.to_rgb16()), "16bit", "png"),
+                    (BitDepth::Sixteen, true) => (DynamicImage::ImageRgba16(img.to_rgba16()), "16bit", "png"),
+                    (BitDepth::Float, false) => (DynamicImage::ImageRgb32F(img.to_rgb32f()), "32f", "exr"),
+                    (BitDepth::Float, true) => (img.clone(), "32f", "exr"),
+                };
+                
+                let path = dir.join(format!("{}_{}x{}_{}_ORM.{}", pattern, width, height, label, format));
+                let data = encode_image(&img, format, profile, None, None)
+                    .with_context(|| format!("Failed to encode test image: {}", path.display()))?;
+                fs::write(&path, data)
+                    .with_context(|| format!("Failed to save test image: {}", path.display()))?;
+                paths.push(path);
+            }
+        }
+    }
+    
+    Ok(paths)
+}
+
+fn render(pattern: &str, width: u32, height: u32, seed: u32) -> Rgba32FImage {
+    let span = |size: u32| (size.max(2) - 1) as f32;
+    Rgba32FImage::from_fn(width, height, |x, y| {
+        let (u, v) = (x as f32 / span(width), y as f32 / span(height));
+        match pattern {
+            // Horizontal, vertical and diagonal ramps, alpha runs the other way
+            "gradient" => Rgba([u, v, (u + v) / 2.0, 1.0 - u]),
+            "noise" => Rgba([0, 1, 2, 3].map(|channel| noise(x, y, channel, seed))),
+            // Checkerboard, sine stripes, radial falloff and a vertical ramp
+            _ => {
+                let cell = (width.max(height) / 8).max(1);
+                let checker = ((x / cell + y / cell) % 2) as f32;
+                let stripes = 0.5 + 0.5 * (u * 4.0 * TAU).sin();
+                let distance = ((u - 0.5).powi(2) + (v - 0.5).powi(2)).sqrt() * 2.0;
+                Rgba([checker, stripes, (1.0 - distance).max(0.0), v])
+            }
+        }
+    })
+}
+
+// Integer hash of the coordinates, so the same seed always gives the same noise
+fn noise(x: u32, y: u32, channel: u32, seed: u32) -> f32 {
+    let mut hash = x.wrapping_mul(0x9E37_79B1) ^ y.wrapping_mul(0x85EB_CA77) ^ channel.wrapping_mul(0xC2B2_AE3D) ^ seed.wrapping_mul(0x27D4_EB2F);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2C1B_3C6D);
+    hash ^= hash >> 12;
+    hash = hash.wrapping_mul(0x297A_2D39);
+    hash ^= hash >> 15;
+    (hash >> 8) as f32 / (1u32 << 24) as f32
+}
@@ -0,0 +1,43 @@
+// Value histograms of channel outputs, to spot clipped or quantized data
+use image::{DynamicImage, Rgba, RgbaImage};
+
+pub const BINS: usize = 256;
+const IMAGE_HEIGHT: u32 = 64;
+
+// Pixel counts of the first sample of a grayscale channel image, in 256 bins whatever its depth
+pub fn count(img: &DynamicImage) -> [u64; BINS] {
+    let mut bins = [0u64; BINS];
+    match img {
+        DynamicImage::ImageLuma8(buffer) => buffer.pixels().for_each(|pixel| bins[pixel[0] as usize] += 1),
+        DynamicImage::ImageRgba8(buffer) => buffer.pixels().for_each(|pixel| bins[pixel[0] as usize] += 1),
+        _ => img.to_rgba32f().pixels().for_each(|pixel| {
+            bins[(pixel[0].clamp(0.0, 1.0) * 255.0).round() as usize] += 1;
+        }),
+    }
+    bins
+}
+
+// One column per bin scaled to the fullest bin. Bins at pure black and white are drawn
+// red when used, since a tall one there usually means clipped data
+pub fn render(bins: &[u64; BINS]) -> RgbaImage {
+    let peak = bins.iter().copied().max().unwrap_or(0).max(1);
+    RgbaImage::from_fn(BINS as u32, IMAGE_HEIGHT, |x, y| {
+        let count = bins[x as usize];
+        let bar = (count as f64 / peak as f64 * IMAGE_HEIGHT as f64).ceil() as u32;
+        if IMAGE_HEIGHT - y > bar {
+            Rgba([0, 0, 0, 255])
+        } else if x == 0 || x as usize == BINS - 1 {
+            Rgba([255, 64, 64, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    })
+}
+
+pub fn to_csv(bins: &[u64; BINS]) -> String {
+    let mut csv = String::from("value,count\n");
+    for (value, count) in bins.iter().enumerate() {
+        csv.push_str(&format!("{},{}\n", value, count));
+    }
+    csv
+}
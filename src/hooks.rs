@@ -0,0 +1,133 @@
+// pre_command and post_command: external commands run through the shell for every output file,
+// before it's written (e.g. `p4 edit {output}`) and after (e.g. `oxipng {output}`)
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Result, anyhow, Context};
+
+// Tokens are replaced with quoted values, so paths with spaces stay one argument. Quotes written
+// around a token are replaced along with it, quotes around more than the token break the command
+pub fn run(command: &str, input: &Path, output: &Path, channel: &str, log: &mut Vec<String>) -> Result<()> {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let dir = output.parent().unwrap_or(Path::new(".")).to_string_lossy();
+    let values = [
+        ("{input}", input.to_string_lossy()),
+        ("{output}", output.to_string_lossy()),
+        ("{dir}", dir),
+        ("{stem}", stem),
+        ("{channel}", channel.into()),
+    ];
+    let command = substitute(command, &values);
+    
+    // cmd does its own parsing of the command line, quoting it once more would break the tokens
+    #[cfg(windows)]
+    let result = {
+        use std::os::windows::process::CommandExt;
+        Command::new("cmd").arg("/C").raw_arg(&command).output()
+    };
+    #[cfg(not(windows))]
+    let result = Command::new("sh").arg("-c").arg(&command).output();
+    let result = result.with_context(|| format!("Failed to run: {}", command))?;
+    
+    log.push(format!("  Ran: {}", command));
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(anyhow!("Command failed ({}): {}{}", result.status, command,
+            if stderr.trim().is_empty() { String::new() } else { format!("\n{}", stderr.trim()) }));
+    }
+    Ok(())
+}
+
+// One left-to-right scan of the template, inserted values are never scanned again, so a file
+// name containing {stem} or quotes can't turn into shell syntax
+fn substitute(template: &str, values: &[(&str, std::borrow::Cow<str>)]) -> String {
+    let mut command = String::new();
+    let mut rest = template;
+    'scan: while let Some(c) = rest.chars().next() {
+        for (token, value) in values {
+            for written in [format!("\"{}\"", token), format!("'{}'", token), token.to_string()] {
+                if let Some(after) = rest.strip_prefix(written.as_str()) {
+                    command.push_str(&quote(value));
+                    rest = after;
+                    continue 'scan;
+                }
+            }
+        }
+        command.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    command
+}
+
+fn quote(value: &str) -> String {
+    if cfg!(windows) { quote_cmd(value) } else { quote_sh(value) }
+}
+
+fn quote_sh(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+// The value is quoted for the program's own argument parsing first (backslashes only double in
+// front of a quote), then every character cmd treats specially is escaped with ^, the quotes too,
+// so cmd never sees a quoted section and % or & inside a path stay literal
+fn quote_cmd(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in value.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    
+    let mut escaped = String::new();
+    for c in quoted.chars() {
+        if matches!(c, '^' | '"' | '%' | '!' | '&' | '|' | '<' | '>' | '(' | ')') {
+            escaped.push('^');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn sh_quoting_round_trips() {
+        for value in ["plain", "with space", "it's", "$HOME `ls` \"x\"", ""] {
+            let output = Command::new("sh").arg("-c").arg(format!("printf %s {}", quote_sh(value))).output().unwrap();
+            assert_eq!(String::from_utf8(output.stdout).unwrap(), value);
+        }
+    }
+    
+    #[test]
+    fn inserted_values_are_not_substituted_again() {
+        let input = "/d/a{stem};id;#'x.png";
+        let values = [("{input}", input.into()), ("{stem}", "a{stem};id;#'x".into())];
+        let command = substitute("printf '%s|' {input} \"{stem}\"", &values);
+        let output = Command::new("sh").arg("-c").arg(&command).output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{}|a{{stem}};id;#'x|", input));
+    }
+    
+    #[test]
+    fn cmd_quoting_escapes_specials() {
+        assert_eq!(quote_cmd(r"C:\Textures\Rock 1.png"), r#"^"C:\Textures\Rock 1.png^""#);
+        assert_eq!(quote_cmd(r"C:\100% & more\"), r#"^"C:\100^% ^& more\\^""#);
+        assert_eq!(quote_cmd(r#"say "hi""#), r#"^"say \^"hi\^"^""#);
+        assert_eq!(quote_cmd("(a|b)!"), r#"^"^(a^|b^)^!^""#);
+    }
+}
@@ -0,0 +1,89 @@
+// Self-contained HTML summary of a run, thumbnails are embedded so the file can be attached to a review
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use anyhow::{Result, Context};
+use image::{DynamicImage, ImageOutputFormat, RgbaImage};
+
+use crate::report::FileSummary;
+
+const STYLE: &str = "body{font-family:sans-serif;background:#202020;color:#e6e6e6;margin:2em}\
+h2{font-size:1.1em;margin:1.5em 0 .3em}.meta{color:#999}.warning{color:#e8c547}.error{color:#ff6b6b}\
+.thumbs{display:flex;flex-wrap:wrap;gap:8px}figure{margin:0;text-align:center}\
+figure img{width:128px;height:128px;object-fit:contain;background:#000;image-rendering:pixelated}\
+figcaption{font-size:.8em;margin-top:2px}";
+
+pub fn write_report(path: &Path, summaries: &[FileSummary], profile_name: &str) -> Result<()> {
+    let failed = summaries.iter().filter(|summary| summary.error.is_some()).count();
+    let skipped = summaries.iter().filter(|summary| summary.skipped.is_some()).count();
+    let warnings: usize = summaries.iter().map(|summary| summary.warnings.len()).sum();
+    
+    let mut html = String::new();
+    let _ = write!(html, "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>ormseparatev3 report</title><style>{}</style></head><body>\n", STYLE);
+    let _ = writeln!(html, "<h1>ormseparatev3 report</h1>");
+    let _ = writeln!(html, "<p>Profile {}: {} files, {} processed, {} skipped, {} failed, {} warnings</p>",
+        escape(profile_name), summaries.len(), summaries.len() - failed - skipped, skipped, failed, warnings);
+    
+    for summary in summaries {
+        let _ = writeln!(html, "<h2>{}</h2>", escape(&summary.file.display().to_string()));
+        if let Some((width, height)) = summary.dimensions {
+            let _ = writeln!(html, "<p class=\"meta\">{}x{}, {} pages, {:.2} s</p>", width, height, summary.pages, summary.duration.as_secs_f64());
+        }
+        if let Some(error) = &summary.error {
+            let _ = writeln!(html, "<p class=\"error\">Failed: {}</p>", escape(error));
+        }
+        if let Some(reason) = &summary.skipped {
+            let _ = writeln!(html, "<p class=\"warning\">Skipped: {}</p>", escape(reason));
+        }
+        for warning in &summary.warnings {
+            let _ = writeln!(html, "<p class=\"warning\">Warning: {}</p>", escape(warning));
+        }
+        
+        if !summary.thumbnails.is_empty() {
+            html.push_str("<div class=\"thumbs\">\n");
+            for (label, thumb) in &summary.thumbnails {
+                let _ = writeln!(html, "<figure><img src=\"data:image/png;base64,{}\" alt=\"{}\"><figcaption>{}</figcaption></figure>",
+                    base64(&encode_png(thumb)?), escape(label), escape(label));
+            }
+            html.push_str("</div>\n");
+        }
+    }
+    html.push_str("</body></html>\n");
+    
+    if let Some(dir) = path.parent() && !dir.as_os_str().is_empty() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create output directory: {}", dir.display()))?;
+    }
+    fs::write(path, html)
+        .with_context(|| format!("Failed to save report: {}", path.display()))
+}
+
+fn encode_png(img: &RgbaImage) -> Result<Vec<u8>> {
+    let mut data = Cursor::new(Vec::new());
+    DynamicImage::ImageRgba8(img.clone()).write_to(&mut data, ImageOutputFormat::Png)?;
+    Ok(data.into_inner())
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// Standard alphabet with padding, as data URIs expect
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], chunk.get(1).copied().unwrap_or(0), chunk.get(2).copied().unwrap_or(0)];
+        let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * index) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,629 @@
+// Read-only reports about inputs, to check a delivery before splitting it
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow, Context};
+use image::{DynamicImage, Rgba32FImage};
+use walkdir::WalkDir;
+
+use crate::json;
+use crate::scene;
+use crate::{FileMatcher, InfoFormat, Profile, image_dimensions, is_hidden, open_pages, relative_path};
+
+// Map types recognized from the last word(s) of a file stem, compared lowercase without separators
+const MAP_TYPES: &[(&str, &[&str])] = &[
+    ("BaseColor", &["basecolor", "albedo", "diffuse", "color", "col"]),
+    ("Normal", &["normal", "normalgl", "normaldx", "nrm", "nor"]),
+    ("ORM", &["orm", "occlusionroughnessmetallic"]),
+    ("Occlusion", &["occlusion", "ambientocclusion", "ao"]),
+    ("Roughness", &["roughness", "rough"]),
+    ("Metallic", &["metallic", "metalness", "metal"]),
+    ("Height", &["height", "displacement", "disp", "bump"]),
+    ("Emissive", &["emissive", "emission"]),
+    ("Opacity", &["opacity", "alpha", "mask"]),
+];
+
+const STEM_SEPARATORS: &[char] = &['_', '-', '.', ' '];
+
+struct SetMember {
+    map_type: &'static str,
+    path: PathBuf,
+    dimensions: Option<(u32, u32)>,
+}
+
+// Group the images under dir into texture sets by the stem left after removing the map type,
+// then report incomplete sets, resolution mismatches and which files the profile would split
+pub fn inspect_sets(dir: &Path, profile: &Profile) -> Result<()> {
+    if !dir.is_dir() {
+        return Err(anyhow!("Not a directory: {}", dir.display()));
+    }
+    let matcher = FileMatcher::new(profile)?;
+    
+    let files = images_under(dir, profile, &matcher);
+    
+    println!("Scanning directory: {}", dir.display());
+    
+    // Keyed by the lowercase set path so sets differing only in case group together
+    let mut sets: BTreeMap<String, (String, Vec<SetMember>)> = BTreeMap::new();
+    let mut unrecognized = Vec::new();
+    for path in files {
+        let relative = relative_path(&path, dir);
+        let stem = Path::new(&relative).with_extension("").to_string_lossy().into_owned();
+        let Some((set_name, map_type)) = split_map_type(&stem) else {
+            unrecognized.push(relative);
+            continue;
+        };
+        
+        let dimensions = image_dimensions(&path).ok();
+        sets.entry(set_name.to_lowercase())
+            .or_insert_with(|| (set_name.to_string(), Vec::new()))
+            .1
+            .push(SetMember { map_type, path, dimensions });
+    }
+    
+    let member_count: usize = sets.values().map(|(_, members)| members.len()).sum();
+    println!("Found {} texture sets ({} files)", sets.len(), member_count);
+    
+    let (mut incomplete, mut mismatched, mut affected) = (0, 0, 0);
+    for (set_name, members) in sets.values_mut() {
+        members.sort_by_key(|member| MAP_TYPES.iter().position(|(map_type, _)| *map_type == member.map_type));
+        println!();
+        println!("  {}", set_name);
+        for member in members.iter() {
+            let size = member.dimensions
+                .map(|(width, height)| format!("{}x{}", width, height))
+                .unwrap_or_else(|| "unreadable".to_string());
+            println!("    {:<10} {} ({})", member.map_type, relative_path(&member.path, dir), size);
+        }
+        
+        let has = |map_type: &str| members.iter().any(|member| member.map_type == map_type);
+        let mut missing: Vec<&str> = ["BaseColor", "Normal"].into_iter().filter(|map_type| !has(map_type)).collect();
+        // Separate occlusion, roughness and metallic maps are as good as a packed ORM
+        let has_orm = has("ORM") || (has("Occlusion") && has("Roughness") && has("Metallic"));
+        if !has_orm {
+            missing.push("ORM");
+        }
+        if !missing.is_empty() {
+            incomplete += 1;
+            println!("    Warning: incomplete set, missing {}", missing.join(", "));
+        }
+        
+        let mut sizes: Vec<(u32, u32)> = members.iter().filter_map(|member| member.dimensions).collect();
+        sizes.sort();
+        sizes.dedup();
+        if sizes.len() > 1 {
+            mismatched += 1;
+            let sizes: Vec<String> = sizes.iter().map(|(width, height)| format!("{}x{}", width, height)).collect();
+            println!("    Warning: mismatched resolutions {}", sizes.join(", "));
+        }
+        
+        let matched: Vec<String> = members.iter()
+            .filter(|member| matcher.is_match(&member.path, dir))
+            .map(|member| relative_path(&member.path, dir))
+            .collect();
+        if !matched.is_empty() {
+            affected += 1;
+            println!("    Profile '{}' would split: {}", profile.name, matched.join(", "));
+        }
+    }
+    
+    if !unrecognized.is_empty() {
+        println!();
+        println!("  Files without a recognized map type:");
+        for relative in &unrecognized {
+            println!("    {}", relative);
+        }
+    }
+    
+    println!();
+    println!("{} sets complete, {} incomplete, {} with mismatched resolutions, {} affected by profile '{}'",
+        sets.len() - incomplete, incomplete, mismatched, affected, profile.name);
+    
+    Ok(())
+}
+
+// Images under dir with the profile's extensions, leaving out hidden and excluded paths and
+// outputs of the profile
+fn images_under(dir: &Path, profile: &Profile, matcher: &FileMatcher) -> Vec<PathBuf> {
+    let mut walker = WalkDir::new(dir).follow_links(profile.follow_symlinks);
+    if let Some(max_depth) = profile.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+    let mut files: Vec<PathBuf> = walker
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0 || (
+                (profile.include_hidden || !is_hidden(entry)) &&
+                !matcher.is_excluded(entry.path(), dir)
+            )
+        })
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_type().is_file() &&
+            matcher.has_allowed_extension(entry.path()) &&
+            !matcher.is_generated_output(entry.path())
+        })
+        .map(|entry| entry.into_path())
+        .collect();
+    files.sort();
+    files
+}
+
+// List every texture a scene references with the material and slot using it, and flag the ones
+// that look packed by their name, their slot or their channel statistics
+pub fn scan(scene: &Path, profile: &Profile) -> Result<()> {
+    let root = scene.parent().unwrap_or(Path::new("."));
+    let matcher = FileMatcher::new(profile)?;
+    let max_alloc = profile.max_alloc();
+    let found = scene::read(scene)?;
+    
+    println!("Scene: {}", scene.display());
+    println!("Found {} texture references", found.textures.len());
+    
+    let (mut missing, mut packed, mut matched) = (0, 0, 0);
+    for texture in &found.textures {
+        println!();
+        let material = if texture.material.is_empty() { "(no material)" } else { &texture.material };
+        let slot = if texture.slot.is_empty() { String::new() } else { format!(" / {}", texture.slot) };
+        println!("  {}{}: {}", material, slot, relative_path(&texture.path, root));
+        if !texture.path.is_file() {
+            missing += 1;
+            println!("    Missing");
+            continue;
+        }
+        
+        let stem = texture.path.file_stem().unwrap_or_default().to_string_lossy();
+        let by_name = split_map_type(&stem).is_some_and(|(_, map_type)| map_type == "ORM") || has_packed_word(&stem);
+        // glTF always packs roughness and metallic into one texture
+        let by_slot = texture.slot == "metallicRoughnessTexture";
+        let guess = match open_pages(&texture.path, max_alloc, profile.psd_layer.as_ref()) {
+            Ok(pages) => {
+                let page = &pages[0];
+                println!("    {}x{}, {} channels", page.width(), page.height(), page.color().channel_count());
+                let guess = guess_packing(page, &channel_stats(page));
+                println!("    Looks like: {}", guess);
+                Some(guess)
+            }
+            Err(e) => {
+                println!("    Unreadable: {}", e);
+                None
+            }
+        };
+        let by_stats = guess.is_some_and(|guess| PACKED_GUESSES.iter().any(|prefix| guess.starts_with(prefix)));
+        
+        let reasons: Vec<&str> = [(by_name, "name"), (by_slot, "slot"), (by_stats, "channel statistics")]
+            .into_iter()
+            .filter_map(|(flagged, reason)| flagged.then_some(reason))
+            .collect();
+        if !reasons.is_empty() {
+            packed += 1;
+            println!("    Packed (by {})", reasons.join(", "));
+        }
+        if matcher.has_allowed_extension(&texture.path) && matcher.is_match(&texture.path, root) {
+            matched += 1;
+            println!("    Profile '{}' would split it", profile.name);
+        }
+    }
+    
+    println!();
+    if found.embedded > 0 {
+        println!("{} images embedded in the scene aren't listed", found.embedded);
+    }
+    println!("{} references, {} missing, {} look packed, {} matched by profile '{}'",
+        found.textures.len(), missing, packed, matched, profile.name);
+    
+    Ok(())
+}
+
+// Guesses of guess_packing that are packed maps
+const PACKED_GUESSES: &[&str] = &["an ORM", "an MRAO", "a mask map"];
+
+// Packing abbreviations anywhere in a stem, as separate words like "Rock_ARM_2k"
+fn has_packed_word(stem: &str) -> bool {
+    stem.split(STEM_SEPARATORS)
+        .any(|word| matches!(word.to_lowercase().as_str(), "orm" | "arm" | "mrao" | "rma" | "mask" | "maskmap" | "packed"))
+}
+
+// Split "folder/Rock_Base_Color" into ("folder/Rock", "BaseColor"), trying the last two words before the last one
+fn split_map_type(stem: &str) -> Option<(&str, &'static str)> {
+    let separators: Vec<usize> = stem.match_indices(STEM_SEPARATORS).map(|(pos, _)| pos).collect();
+    for &pos in separators.iter().rev().take(2).rev() {
+        let suffix: String = stem[pos + 1..].chars()
+            .filter(|c| !STEM_SEPARATORS.contains(c))
+            .collect::<String>()
+            .to_lowercase();
+        let known = MAP_TYPES.iter()
+            .find(|(_, aliases)| aliases.contains(&suffix.as_str()))
+            .map(|(map_type, _)| *map_type);
+        if let Some(map_type) = known && pos > 0 {
+            return Some((&stem[..pos], map_type));
+        }
+    }
+    None
+}
+
+// Value statistics of one channel, in 0-255 units like levels and threshold
+struct ChannelStats {
+    min: f32,
+    max: f32,
+    mean: f32,
+    stddev: f32,
+    // Share of pixels within 1/255 of black or white, high for masks like metallic
+    binary: f32,
+}
+
+// Print format and per-channel statistics of every page of a file, and a guess at what is packed in it
+pub fn inspect_file(file: &Path, profile: &Profile, format: InfoFormat) -> Result<()> {
+    let max_alloc = profile.max_alloc();
+    let pages = open_pages(file, max_alloc, profile.psd_layer.as_ref())
+        .with_context(|| format!("Failed to open image: {}", file.display()))?;
+    
+    let root = file.parent().unwrap_or(Path::new("."));
+    let matcher = FileMatcher::new(profile)?;
+    let matches = matcher.is_match(file, root);
+    if format == InfoFormat::Json {
+        println!("{}", inspect_json(file, profile, matches, &pages));
+        return Ok(());
+    }
+    
+    println!("File: {}", file.display());
+    println!("  {} profile '{}'", if matches { "Matches" } else { "Doesn't match" }, profile.name);
+    
+    for (page_idx, page) in pages.iter().enumerate() {
+        if pages.len() > 1 {
+            println!("  Page {} of {}", page_idx + 1, pages.len());
+        }
+        
+        let color = page.color();
+        let channel_count = color.channel_count() as usize;
+        let stats = channel_stats(page);
+        println!("  Dimensions: {}x{}", page.width(), page.height());
+        println!("  Color type: {:?}, {} channels, {} bits per channel", color, channel_count, color.bits_per_pixel() / channel_count as u16);
+        
+        let alpha = match stats.get(3) {
+            None => "none".to_string(),
+            Some(alpha) if alpha.min >= 255.0 => "present, fully opaque".to_string(),
+            Some(alpha) => format!("present, {:.1}-{:.1}", alpha.min, alpha.max),
+        };
+        println!("  Alpha: {}", alpha);
+        
+        println!("  Channel       Min      Max     Mean   StdDev");
+        for (name, stats) in channel_names(channel_count).iter().zip(&stats) {
+            println!("  {:<7} {:>8.1} {:>8.1} {:>8.1} {:>8.1}", name, stats.min, stats.max, stats.mean, stats.stddev);
+        }
+        
+        println!("  Looks like: {}", guess_packing(page, &stats));
+    }
+    
+    Ok(())
+}
+
+fn channel_names(channel_count: usize) -> &'static [&'static str] {
+    if channel_count < 3 { &["Gray", "Alpha"] } else { &["R", "G", "B", "A"] }
+}
+
+// The same report as one JSON object, statistics in 0-255 units
+fn inspect_json(file: &Path, profile: &Profile, matches: bool, pages: &[DynamicImage]) -> String {
+    let pages: Vec<String> = pages.iter().map(|page| {
+        let color = page.color();
+        let channel_count = color.channel_count() as usize;
+        let stats = channel_stats(page);
+        let channels: Vec<String> = channel_names(channel_count).iter().zip(&stats).map(|(name, stats)| format!(
+            "{{\"name\":{},\"min\":{:.3},\"max\":{:.3},\"mean\":{:.3},\"stddev\":{:.3}}}",
+            json::string(name), stats.min, stats.max, stats.mean, stats.stddev,
+        )).collect();
+        format!(
+            "{{\"width\":{},\"height\":{},\"color_type\":{},\"bits_per_channel\":{},\"channels\":[{}],\"looks_like\":{}}}",
+            page.width(), page.height(), json::string(&format!("{:?}", color)), color.bits_per_pixel() / channel_count as u16,
+            channels.join(","), json::string(guess_packing(page, &stats)),
+        )
+    }).collect();
+    format!(
+        "{{\"file\":{},\"profile\":{},\"matches_profile\":{},\"pages\":[{}]}}",
+        json::string(&file.to_string_lossy()), json::string(&profile.name), matches, pages.join(","),
+    )
+}
+
+// Statistics of each channel the image actually has, luma images have one or two
+fn channel_stats(img: &DynamicImage) -> Vec<ChannelStats> {
+    let rgba = img.to_rgba32f();
+    let pixels = (rgba.width() as f64 * rgba.height() as f64).max(1.0);
+    
+    // Luma images are expanded to RGBA, so their alpha sits in the fourth sample
+    let sources: Vec<usize> = match img.color().channel_count() {
+        1 => vec![0],
+        2 => vec![0, 3],
+        count => (0..count as usize).collect(),
+    };
+    sources.into_iter().map(|index| {
+        let (mut min, mut max, mut sum, mut sum_sq, mut binary) = (f32::MAX, f32::MIN, 0.0f64, 0.0f64, 0usize);
+        for pixel in rgba.pixels() {
+            let value = pixel[index];
+            min = min.min(value);
+            max = max.max(value);
+            sum += value as f64;
+            sum_sq += value as f64 * value as f64;
+            if value <= 1.0 / 255.0 || value >= 254.0 / 255.0 {
+                binary += 1;
+            }
+        }
+        let mean = sum / pixels;
+        let variance = (sum_sq / pixels - mean * mean).max(0.0);
+        ChannelStats {
+            min: min * 255.0,
+            max: max * 255.0,
+            mean: (mean * 255.0) as f32,
+            stddev: (variance.sqrt() * 255.0) as f32,
+            binary: (binary as f64 / pixels) as f32,
+        }
+    }).collect()
+}
+
+// Heuristics only: normal maps average to a blue-ish 128/128/255 with unit-length vectors,
+// ORM maps are bright in occlusion with a mostly black or white metallic channel
+fn guess_packing(img: &DynamicImage, stats: &[ChannelStats]) -> &'static str {
+    if stats.len() < 3 {
+        return "a single grayscale map";
+    }
+    
+    let rgba = img.to_rgba32f();
+    if rgba.pixels().all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2]) {
+        return "a grayscale map stored as RGB";
+    }
+    
+    let near_half = |stats: &ChannelStats| (stats.mean - 127.5).abs() < 20.0;
+    if near_half(&stats[0]) && near_half(&stats[1]) {
+        let pixels = (rgba.width() as f64 * rgba.height() as f64).max(1.0);
+        let length: f64 = rgba.pixels()
+            .map(|pixel| {
+                let [x, y, z] = [pixel[0], pixel[1], pixel[2]].map(|v| v as f64 * 2.0 - 1.0);
+                (x * x + y * y + z * z).sqrt()
+            })
+            .sum::<f64>() / pixels;
+        if stats[2].mean > 180.0 && (length - 1.0).abs() < 0.1 {
+            return "a tangent-space normal map";
+        }
+        if stats[2].max - stats[2].min < 1.0 {
+            return "a two-channel normal map (X and Y in R and G, Z can be rebuilt with reconstruct_z)";
+        }
+    }
+    
+    if let Some(alpha) = stats.get(3) && alpha.max - alpha.min >= 1.0 && stats[0].binary > 0.9 {
+        return "a mask map (metallic, occlusion, detail mask, smoothness in alpha)";
+    }
+    if stats[0].mean > 127.5 && stats[2].binary > 0.9 {
+        return "an ORM map (occlusion, roughness, metallic)";
+    }
+    if stats[0].binary > 0.9 && stats[2].mean > 127.5 {
+        return "an MRAO-style map (metallic, roughness, occlusion)";
+    }
+    
+    "a color image, likely base color or an unknown packing"
+}
+
+// Packings detect tells apart, with what R, G and B hold
+const LAYOUTS: &[(&str, [&str; 3])] = &[
+    ("ORM", ["occlusion", "roughness", "metallic"]),
+    ("RMA", ["roughness", "metallic", "occlusion"]),
+    ("MRAO", ["metallic", "roughness", "occlusion"]),
+];
+
+// Words in channel names or semantics that mark a role, to find the profile splitting a layout
+const ROLE_WORDS: &[(&str, &[&str])] = &[
+    ("occlusion", &["occlusion", "ao"]),
+    ("roughness", &["rough"]),
+    ("metallic", &["metal"]),
+];
+
+enum Detection {
+    // Index into LAYOUTS, with how far it scored ahead of the next best layout
+    Packed { layout: usize, margin: f32 },
+    Normal,
+    Grayscale,
+    Color,
+}
+
+// How well a channel's histogram fits a role, 0-1. Metallic is mostly black with pure white spots,
+// occlusion bright with darker crevices, roughness a spread of values in between
+fn role_score(role: &str, stats: &ChannelStats) -> f32 {
+    let mean = stats.mean / 255.0;
+    match role {
+        "metallic" => stats.binary * (1.0 - mean * 0.5),
+        "occlusion" => mean * (1.0 - stats.binary * 0.5),
+        _ => (1.0 - stats.binary) * (1.0 - (mean - 0.5).abs()),
+    }
+}
+
+// Pearson correlation of two channels, 0 when either is constant
+fn correlation(rgba: &Rgba32FImage, first: usize, second: usize) -> f32 {
+    let pixels = (rgba.width() as f64 * rgba.height() as f64).max(1.0);
+    let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0f64, 0.0f64, 0.0f64, 0.0f64, 0.0f64);
+    for pixel in rgba.pixels() {
+        let (a, b) = (pixel[first] as f64, pixel[second] as f64);
+        sum_a += a;
+        sum_b += b;
+        sum_aa += a * a;
+        sum_bb += b * b;
+        sum_ab += a * b;
+    }
+    let covariance = sum_ab / pixels - (sum_a / pixels) * (sum_b / pixels);
+    let variance_a = sum_aa / pixels - (sum_a / pixels).powi(2);
+    let variance_b = sum_bb / pixels - (sum_b / pixels).powi(2);
+    if variance_a <= 1e-9 || variance_b <= 1e-9 {
+        return 0.0;
+    }
+    (covariance / (variance_a * variance_b).sqrt()) as f32
+}
+
+// Score every layout by how well each channel fits its role. Metal is usually smoother than the
+// rest, so roughness falling where metallic rises counts in a layout's favor. Base colors have
+// channels that follow each other closely, packed maps don't
+fn detect_layout(img: &DynamicImage, stats: &[ChannelStats]) -> (Detection, [f32; 3]) {
+    let rgba = img.to_rgba32f();
+    let correlations = [correlation(&rgba, 0, 1), correlation(&rgba, 0, 2), correlation(&rgba, 1, 2)];
+    let guess = guess_packing(img, stats);
+    if guess.contains("normal map") {
+        return (Detection::Normal, correlations);
+    }
+    if guess.contains("grayscale") {
+        return (Detection::Grayscale, correlations);
+    }
+    if correlations.iter().all(|correlation| *correlation > 0.8) {
+        return (Detection::Color, correlations);
+    }
+    
+    let pair = |first: usize, second: usize| match (first.min(second), first.max(second)) {
+        (0, 1) => correlations[0],
+        (0, 2) => correlations[1],
+        _ => correlations[2],
+    };
+    let mut scores: Vec<(usize, f32)> = LAYOUTS.iter().enumerate().map(|(index, (_, roles))| {
+        let fit: f32 = roles.iter().zip(stats).map(|(role, stats)| role_score(role, stats)).sum();
+        let roughness = roles.iter().position(|role| *role == "roughness").unwrap();
+        let metallic = roles.iter().position(|role| *role == "metallic").unwrap();
+        (index, fit + 0.25 * (-pair(roughness, metallic)).max(0.0))
+    }).collect();
+    scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+    // Below this no layout fits well enough to call it packed
+    if scores[0].1 < 1.5 {
+        return (Detection::Color, correlations);
+    }
+    (Detection::Packed { layout: scores[0].0, margin: scores[0].1 - scores[1].1 }, correlations)
+}
+
+fn confidence(margin: f32) -> &'static str {
+    if margin > 0.6 {
+        "high"
+    } else if margin > 0.25 {
+        "medium"
+    } else {
+        "low"
+    }
+}
+
+// Name of the first profile (alphabetically) whose channels split the layout's roles from the
+// right source channels
+fn matching_profile(layout: usize, profiles: &HashMap<String, Profile>) -> Option<&str> {
+    let roles = LAYOUTS[layout].1;
+    let mut names: Vec<&String> = profiles.keys().collect();
+    names.sort();
+    names.into_iter().find(|name| {
+        let profile = &profiles[*name];
+        roles.iter().enumerate().all(|(index, role)| {
+            let words = ROLE_WORDS.iter().find(|(known, _)| known == role).map_or(&[][..], |(_, words)| words);
+            profile.channels.iter().any(|channel| {
+                let semantic = channel.semantic();
+                channel.channel == index && channel.expression.is_none() && !channel.reconstruct_z &&
+                    words.iter().any(|word| semantic.contains(word))
+            })
+        })
+    }).map(String::as_str)
+}
+
+// A profile table splitting the layout, for config.toml
+fn profile_snippet(layout: usize) -> String {
+    let (name, roles) = LAYOUTS[layout];
+    let key = name.to_lowercase();
+    let mut snippet = format!("[profiles.{key}]\nname = \"{key}\"\nfile_regex = \"/{key}/i\"\noutput_format = \"png\"\n");
+    for (index, role) in roles.iter().enumerate() {
+        let mut channel_name = role.to_string();
+        channel_name[..1].make_ascii_uppercase();
+        snippet.push_str(&format!("\n[[profiles.{key}.channels]]\nname = \"{channel_name}\"\nchannel = {index}\n"));
+    }
+    snippet
+}
+
+// Guess what an image packs from its channel histograms and correlations, and name the profile
+// splitting it, or print one to add
+pub fn detect(file: &Path, profile: &Profile, profiles: &HashMap<String, Profile>) -> Result<()> {
+    let max_alloc = profile.max_alloc();
+    let pages = open_pages(file, max_alloc, profile.psd_layer.as_ref())
+        .with_context(|| format!("Failed to open image: {}", file.display()))?;
+    let page = &pages[0];
+    let stats = channel_stats(page);
+    
+    println!("File: {}", file.display());
+    println!("  Channel       Mean   Black/white");
+    let names: &[&str] = if stats.len() < 3 { &["Gray", "Alpha"] } else { &["R", "G", "B", "A"] };
+    for (name, stats) in names.iter().zip(&stats) {
+        println!("  {:<7} {:>10.1} {:>12.0}%", name, stats.mean, stats.binary * 100.0);
+    }
+    let (detection, correlations) = detect_layout(page, &stats);
+    if stats.len() >= 3 {
+        println!("  Correlation: R-G {:.2}, R-B {:.2}, G-B {:.2}", correlations[0], correlations[1], correlations[2]);
+    }
+    
+    match detection {
+        Detection::Packed { layout, margin } => {
+            let (name, roles) = LAYOUTS[layout];
+            println!("  Guess: {} ({}), {} confidence", name, roles.join(", "), confidence(margin));
+            match matching_profile(layout, profiles) {
+                Some(name) => println!("  Suggested profile: {}", name),
+                None => {
+                    println!("  No profile splits this layout, add one to config.toml:");
+                    println!();
+                    for line in profile_snippet(layout).lines() {
+                        if line.is_empty() { println!() } else { println!("    {}", line) }
+                    }
+                }
+            }
+        }
+        Detection::Normal => println!("  Guess: a normal map, not packed"),
+        Detection::Grayscale => println!("  Guess: a single grayscale map, not packed"),
+        Detection::Color => println!("  Guess: not packed, likely a base color or other color image"),
+    }
+    Ok(())
+}
+
+// One line per input image with the guessed packing and the profile splitting it
+pub fn detect_batch(inputs: &[PathBuf], profile: &Profile, profiles: &HashMap<String, Profile>) -> Result<()> {
+    let matcher = FileMatcher::new(profile)?;
+    let max_alloc = profile.max_alloc();
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut unsplit = Vec::new();
+    for input in inputs {
+        let (root, files) = if input.is_dir() {
+            (input.as_path(), images_under(input, profile, &matcher))
+        } else {
+            (input.parent().unwrap_or(Path::new(".")), vec![input.clone()])
+        };
+        for file in files {
+            let relative = relative_path(&file, root);
+            let page = match open_pages(&file, max_alloc, profile.psd_layer.as_ref()) {
+                Ok(mut pages) => pages.remove(0),
+                Err(e) => {
+                    println!("  {}: unreadable, {}", relative, e);
+                    *counts.entry("unreadable").or_default() += 1;
+                    continue;
+                }
+            };
+            let (detection, _) = detect_layout(&page, &channel_stats(&page));
+            let (guess, line) = match detection {
+                Detection::Packed { layout, margin } => {
+                    let name = LAYOUTS[layout].0;
+                    let profile = match matching_profile(layout, profiles) {
+                        Some(profile) => format!("profile '{}'", profile),
+                        None => {
+                            unsplit.push(relative.clone());
+                            "no matching profile".to_string()
+                        }
+                    };
+                    (name, format!("{} ({} confidence), {}", name, confidence(margin), profile))
+                }
+                Detection::Normal => ("normal map", "normal map, not packed".to_string()),
+                Detection::Grayscale => ("grayscale", "grayscale map, not packed".to_string()),
+                Detection::Color => ("not packed", "not packed".to_string()),
+            };
+            println!("  {}: {}", relative, line);
+            *counts.entry(guess).or_default() += 1;
+        }
+    }
+    
+    println!();
+    let summary: Vec<String> = counts.iter().map(|(guess, count)| format!("{} {}", count, guess)).collect();
+    println!("{}", if summary.is_empty() { "No images found".to_string() } else { summary.join(", ") });
+    if !unsplit.is_empty() {
+        println!("{} packed images have no profile splitting them, run detect on one to get a profile to add", unsplit.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,295 @@
+// Explorer right-click menu on Windows and a Finder Quick Action on macOS. Both are installed for
+// the current user only, so no administrator rights are needed
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Result, anyhow, Context};
+
+use crate::{Config, DROPPED_ENV, default_extensions};
+
+const CLASSES: &str = r"HKEY_CURRENT_USER\Software\Classes";
+const VERB: &str = "ormseparatev3";
+const MENU_TITLE: &str = "Split ORM channels";
+const QUICK_ACTION_TITLE: &str = "Split ORM";
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum IntegrationAction {
+    Install,
+    Uninstall,
+}
+
+pub fn shell_integration(action: IntegrationAction, exe: &Path, config: &Config) -> Result<()> {
+    if cfg!(windows) {
+        explorer(action, exe, config)
+    } else if cfg!(target_os = "macos") {
+        finder(action, exe)
+    } else {
+        Err(anyhow!("shell-integration is only available on Windows and macOS"))
+    }
+}
+
+// Registry entries under HKEY_CURRENT_USER\Software\Classes, imported with reg.exe from a generated .reg file
+fn explorer(action: IntegrationAction, exe: &Path, config: &Config) -> Result<()> {
+    // Installing replaces the old entries, so profiles removed from the config disappear too
+    let mut script = String::from("Windows Registry Editor Version 5.00\r\n\r\n");
+    for key in menu_keys() {
+        script.push_str(&format!("[-{}]\r\n\r\n", key));
+    }
+    if action == IntegrationAction::Install {
+        for key in menu_keys() {
+            script.push_str(&menu(&key, exe, config));
+        }
+    }
+    import(&script)?;
+    
+    match action {
+        IntegrationAction::Install => {
+            println!("Added \"{}\" to the Explorer menu of folders and {} files", MENU_TITLE, default_extensions().join(", "));
+            println!("Run shell-integration install again after adding or renaming profiles");
+        }
+        IntegrationAction::Uninstall => println!("Removed \"{}\" from the Explorer menu", MENU_TITLE),
+    }
+    Ok(())
+}
+
+// One menu for folders and one per supported image extension
+fn menu_keys() -> Vec<String> {
+    std::iter::once(format!(r"{}\Directory\shell\{}", CLASSES, VERB))
+        .chain(default_extensions().iter().map(|extension| format!(r"{}\SystemFileAssociations\.{}\shell\{}", CLASSES, extension, VERB)))
+        .collect()
+}
+
+// A cascading menu with one entry per profile, each running the exe on the clicked item.
+// The profile is always passed, so the run never stops to ask for one
+fn menu(key: &str, exe: &Path, config: &Config) -> String {
+    let exe = exe.display().to_string();
+    let mut menu = format!("[{}]\r\n\"MUIVerb\"=\"{}\"\r\n\"Icon\"=\"{}\"\r\n\"SubCommands\"=\"\"\r\n\r\n", key, MENU_TITLE, reg_string(&exe));
+    
+    let mut names: Vec<&String> = config.profiles.keys().collect();
+    names.sort();
+    for name in names {
+        let title = if name == &config.default_profile { format!("{} (default)", name) } else { name.clone() };
+        let command = format!("\"{}\" --profile \"{}\" \"%1\"", exe, name);
+        // Key names can't contain backslashes, the title shown is MUIVerb
+        let entry = format!(r"{}\shell\{}", key, name.replace('\\', "_"));
+        menu.push_str(&format!("[{}]\r\n\"MUIVerb\"=\"{}\"\r\n\r\n", entry, reg_string(&title)));
+        menu.push_str(&format!("[{}\\command]\r\n@=\"{}\"\r\n\r\n", entry, reg_string(&command)));
+    }
+    menu
+}
+
+fn reg_string(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// reg.exe expects .reg files as UTF-16LE with a byte order mark
+fn import(script: &str) -> Result<()> {
+    let path = std::env::temp_dir().join(format!("ormseparatev3-{}.reg", std::process::id()));
+    let bytes: Vec<u8> = std::iter::once(0xFEFF).chain(script.encode_utf16())
+        .flat_map(|unit: u16| unit.to_le_bytes())
+        .collect();
+    fs::write(&path, bytes)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    
+    let output = Command::new("reg").arg("import").arg(&path).output();
+    let _ = fs::remove_file(&path);
+    let output = output.context("Failed to run reg.exe")?;
+    if !output.status.success() {
+        return Err(anyhow!("reg import failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+// Quick Actions are Automator workflows in ~/Library/Services. Like a drop onto the executable, the
+// workflow passes only the selected paths, so the drag_drop profile is used and nothing is asked
+fn finder(action: IntegrationAction, exe: &Path) -> Result<()> {
+    let home = std::env::var_os("HOME").ok_or_else(|| anyhow!("HOME is not set"))?;
+    let workflow = PathBuf::from(home).join("Library/Services").join(format!("{}.workflow", QUICK_ACTION_TITLE));
+    if workflow.exists() {
+        fs::remove_dir_all(&workflow)
+            .with_context(|| format!("Failed to remove {}", workflow.display()))?;
+    }
+    
+    if action == IntegrationAction::Install {
+        let contents = workflow.join("Contents");
+        fs::create_dir_all(&contents)
+            .with_context(|| format!("Failed to create {}", contents.display()))?;
+        fs::write(contents.join("Info.plist"), quick_action_info())
+            .with_context(|| format!("Failed to write {}", workflow.display()))?;
+        fs::write(contents.join("document.wflow"), quick_action_document(exe))
+            .with_context(|| format!("Failed to write {}", workflow.display()))?;
+    }
+    // Make Finder pick up the change without logging out
+    let _ = Command::new("/System/Library/CoreServices/pbs").arg("-update").output();
+    
+    match action {
+        IntegrationAction::Install => {
+            println!("Installed the \"{}\" Quick Action to {}", QUICK_ACTION_TITLE, workflow.display());
+            println!("It uses the drag_drop profile from the config and logs to ~/Library/Logs/ormseparatev3.log");
+        }
+        IntegrationAction::Uninstall => println!("Removed the \"{}\" Quick Action", QUICK_ACTION_TITLE),
+    }
+    Ok(())
+}
+
+fn quick_action_info() -> String {
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>NSServices</key>
+	<array>
+		<dict>
+			<key>NSMenuItem</key>
+			<dict>
+				<key>default</key>
+				<string>{title}</string>
+			</dict>
+			<key>NSMessage</key>
+			<string>runWorkflowAsService</string>
+			<key>NSRequiredContext</key>
+			<dict>
+				<key>NSApplicationIdentifier</key>
+				<string>com.apple.finder</string>
+			</dict>
+			<key>NSSendFileTypes</key>
+			<array>
+				<string>public.image</string>
+				<string>public.folder</string>
+			</array>
+		</dict>
+	</array>
+</dict>
+</plist>
+"#, title = QUICK_ACTION_TITLE)
+}
+
+// A single Run Shell Script action, the output goes to a log and the result to a notification
+fn quick_action_document(exe: &Path) -> String {
+    let exe = exe.display().to_string().replace('\'', "'\\''");
+    let script = format!("{dropped}=1 '{exe}' \"$@\" > \"$HOME/Library/Logs/ormseparatev3.log\" 2>&1 \\
+    && osascript -e 'display notification \"Finished splitting\" with title \"{title}\"' \\
+    || osascript -e 'display notification \"Failed, see ~/Library/Logs/ormseparatev3.log\" with title \"{title}\"'",
+        dropped = DROPPED_ENV, exe = exe, title = QUICK_ACTION_TITLE);
+    let script = script.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+    
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>AMApplicationBuild</key>
+	<string>523</string>
+	<key>AMApplicationVersion</key>
+	<string>2.10</string>
+	<key>AMDocumentVersion</key>
+	<string>2</string>
+	<key>actions</key>
+	<array>
+		<dict>
+			<key>action</key>
+			<dict>
+				<key>AMAccepts</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Optional</key>
+					<true/>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.string</string>
+					</array>
+				</dict>
+				<key>AMActionVersion</key>
+				<string>2.0.3</string>
+				<key>AMApplication</key>
+				<array>
+					<string>Automator</string>
+				</array>
+				<key>AMParameterProperties</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<dict/>
+					<key>CheckedForUserDefaultShell</key>
+					<dict/>
+					<key>inputMethod</key>
+					<dict/>
+					<key>shell</key>
+					<dict/>
+					<key>source</key>
+					<dict/>
+				</dict>
+				<key>AMProvides</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.string</string>
+					</array>
+				</dict>
+				<key>ActionBundlePath</key>
+				<string>/System/Library/Automator/Run Shell Script.action</string>
+				<key>ActionName</key>
+				<string>Run Shell Script</string>
+				<key>ActionParameters</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<string>{script}</string>
+					<key>CheckedForUserDefaultShell</key>
+					<true/>
+					<key>inputMethod</key>
+					<integer>1</integer>
+					<key>shell</key>
+					<string>/bin/bash</string>
+					<key>source</key>
+					<string></string>
+				</dict>
+				<key>BundleIdentifier</key>
+				<string>com.apple.RunShellScript</string>
+				<key>CFBundleVersion</key>
+				<string>2.0.3</string>
+				<key>CanShowSelectedItemsWhenRun</key>
+				<false/>
+				<key>CanShowWhenRun</key>
+				<true/>
+				<key>Category</key>
+				<array>
+					<string>AMCategoryUtilities</string>
+				</array>
+				<key>Class Name</key>
+				<string>RunShellScriptAction</string>
+				<key>InputUUID</key>
+				<string>6C1B5E52-4D32-4B0F-9D0A-2F1C0E7A5B10</string>
+				<key>OutputUUID</key>
+				<string>9A7E3C41-0B6D-4E8F-A2C5-7D4B1F0E6C21</string>
+				<key>UUID</key>
+				<string>3E2D1C0B-5A4F-4C3B-8E7D-1F0A9B8C7D32</string>
+				<key>UnlocalizedApplications</key>
+				<array>
+					<string>Automator</string>
+				</array>
+				<key>isViewVisible</key>
+				<true/>
+			</dict>
+			<key>isViewVisible</key>
+			<true/>
+		</dict>
+	</array>
+	<key>connectors</key>
+	<dict/>
+	<key>workflowMetaData</key>
+	<dict>
+		<key>serviceInputTypeIdentifier</key>
+		<string>com.apple.Automator.fileSystemObject</string>
+		<key>serviceOutputTypeIdentifier</key>
+		<string>com.apple.Automator.nothing</string>
+		<key>serviceProcessesInput</key>
+		<integer>0</integer>
+		<key>workflowTypeIdentifier</key>
+		<string>com.apple.Automator.servicesMenu</string>
+	</dict>
+</dict>
+</plist>
+"#, script = script)
+}
@@ -0,0 +1,291 @@
+// The little JSON this crate writes is formatted by hand, strings go through here. Reading is
+// limited to what scene files like glTF need: a tree of values, no streaming or line numbers
+use anyhow::{Result, anyhow};
+
+pub fn string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    // Members in document order
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+    
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(text) => Some(text),
+            _ => None,
+        }
+    }
+    
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+    
+    pub fn as_array(&self) -> &[Value] {
+        match self {
+            Value::Array(items) => items,
+            _ => &[],
+        }
+    }
+    
+    pub fn as_index(&self) -> Option<usize> {
+        match self {
+            Value::Number(number) if *number >= 0.0 && number.fract() == 0.0 => Some(*number as usize),
+            _ => None,
+        }
+    }
+}
+
+// Deeper nesting is refused instead of recursing until the stack overflows
+const MAX_DEPTH: usize = 128;
+
+pub fn parse(text: &str) -> Result<Value> {
+    let mut parser = Parser { bytes: text.as_bytes(), position: 0, depth: 0 };
+    let value = parser.value()?;
+    parser.whitespace();
+    if parser.position < parser.bytes.len() {
+        return Err(parser.error("Unexpected data after the JSON value"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> anyhow::Error {
+        anyhow!("{} at byte {}", message, self.position)
+    }
+    
+    fn whitespace(&mut self) {
+        while self.bytes.get(self.position).is_some_and(|byte| byte.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+    }
+    
+    fn expect(&mut self, literal: &str, value: Value) -> Result<Value> {
+        if self.bytes[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            Ok(value)
+        } else {
+            Err(self.error("Invalid JSON literal"))
+        }
+    }
+    
+    fn value(&mut self) -> Result<Value> {
+        self.whitespace();
+        match self.bytes.get(self.position) {
+            Some(b'{' | b'[') => {
+                if self.depth == MAX_DEPTH {
+                    return Err(self.error("JSON nested too deeply"));
+                }
+                self.depth += 1;
+                let value = if self.bytes[self.position] == b'{' { self.object() } else { self.array() };
+                self.depth -= 1;
+                value
+            }
+            Some(b'"') => Ok(Value::String(self.string()?)),
+            Some(b't') => self.expect("true", Value::Bool(true)),
+            Some(b'f') => self.expect("false", Value::Bool(false)),
+            Some(b'n') => self.expect("null", Value::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("Unexpected character")),
+            None => Err(self.error("Unexpected end of JSON")),
+        }
+    }
+    
+    fn object(&mut self) -> Result<Value> {
+        self.position += 1;
+        let mut members = Vec::new();
+        self.whitespace();
+        if self.bytes.get(self.position) == Some(&b'}') {
+            self.position += 1;
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.whitespace();
+            if self.bytes.get(self.position) != Some(&b'"') {
+                return Err(self.error("Expected a member name"));
+            }
+            let name = self.string()?;
+            self.whitespace();
+            if self.bytes.get(self.position) != Some(&b':') {
+                return Err(self.error("Expected ':'"));
+            }
+            self.position += 1;
+            members.push((name, self.value()?));
+            self.whitespace();
+            match self.bytes.get(self.position) {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(Value::Object(members));
+                }
+                _ => return Err(self.error("Expected ',' or '}'")),
+            }
+        }
+    }
+    
+    fn array(&mut self) -> Result<Value> {
+        self.position += 1;
+        let mut items = Vec::new();
+        self.whitespace();
+        if self.bytes.get(self.position) == Some(&b']') {
+            self.position += 1;
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.whitespace();
+            match self.bytes.get(self.position) {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Value::Array(items));
+                }
+                _ => return Err(self.error("Expected ',' or ']'")),
+            }
+        }
+    }
+    
+    fn number(&mut self) -> Result<Value> {
+        let start = self.position;
+        while self.bytes.get(self.position).is_some_and(|byte| matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.position]).ok()
+            .and_then(|number| number.parse().ok())
+            .map(Value::Number)
+            .ok_or_else(|| self.error("Invalid number"))
+    }
+    
+    fn hex4(&mut self) -> Result<u32> {
+        let digits = self.bytes.get(self.position..self.position + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("Invalid \\u escape"))?;
+        self.position += 4;
+        Ok(digits)
+    }
+    
+    fn string(&mut self) -> Result<String> {
+        self.position += 1;
+        let mut text = Vec::new();
+        loop {
+            let Some(&byte) = self.bytes.get(self.position) else {
+                return Err(self.error("Unterminated string"));
+            };
+            self.position += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let Some(&escape) = self.bytes.get(self.position) else {
+                        return Err(self.error("Unterminated string"));
+                    };
+                    self.position += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            // Characters outside the BMP come as a surrogate pair
+                            if (0xD800..0xDC00).contains(&code) && self.bytes[self.position..].starts_with(b"\\u") {
+                                self.position += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return Err(self.error("Invalid escape")),
+                    };
+                    text.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                byte => text.push(byte),
+            }
+        }
+        // The input is a str, so bytes between escapes are valid UTF-8
+        Ok(String::from_utf8_lossy(&text).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn strings_escape_quotes_and_control_characters() {
+        assert_eq!(string("a\"b\\c\nd\u{1}"), "\"a\\\"b\\\\c\\nd\\u0001\"");
+    }
+    
+    #[test]
+    fn parses_nested_values() {
+        let value = parse(r#"{"images": [{"uri": "a\u00e9.png", "index": 2}], "flag": true, "none": null}"#).unwrap();
+        let image = &value.get("images").unwrap().as_array()[0];
+        assert_eq!(image.get("uri").and_then(Value::as_str), Some("a\u{e9}.png"));
+        assert_eq!(image.get("index").and_then(Value::as_index), Some(2));
+        assert_eq!(value.get("flag").and_then(Value::as_bool), Some(true));
+        assert!(matches!(value.get("none"), Some(Value::Null)));
+    }
+    
+    #[test]
+    fn surrogate_pairs_decode_to_one_character() {
+        assert_eq!(parse(r#""\ud83d\ude00""#).unwrap().as_str(), Some("\u{1F600}"));
+    }
+    
+    #[test]
+    fn rejects_malformed_documents() {
+        for text in ["", "{", "[1,]", r#"{"a" 1}"#, r#""open"#, "tru", "1 2", r#""\q""#] {
+            assert!(parse(text).is_err(), "{:?} parsed", text);
+        }
+    }
+    
+    #[test]
+    fn deep_nesting_fails_cleanly() {
+        let within = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(parse(&within).is_ok());
+        let beyond = "[".repeat(100_000);
+        assert!(parse(&beyond).err().unwrap().to_string().contains("nested too deeply"));
+        let objects = "{\"a\":".repeat(MAX_DEPTH + 1);
+        assert!(parse(&objects).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+// KTX2 array textures, one uncompressed 8-bit UNORM layer per channel or tile, so a renderer can
+// bind a texture set as one array. Layer names are listed in the ormseparatev3:layers key
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Result, anyhow, Context};
+
+const IDENTIFIER: [u8; 12] = [0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+pub const LAYERS_KEY: &str = "ormseparatev3:layers";
+
+// VK_FORMAT_R8_UNORM and VK_FORMAT_R8G8B8A8_UNORM
+const R8_UNORM: u32 = 9;
+const R8G8B8A8_UNORM: u32 = 37;
+
+// Identifier, header, index and one level
+const LEVEL_INDEX_END: usize = 12 + 9 * 4 + 4 * 4 + 2 * 8 + 3 * 8;
+
+// Layers of width×height pixels with 1 (gray) or 4 (RGBA) bytes each, all the same size
+pub fn write_array(path: &Path, width: u32, height: u32, channels: usize, layers: &[(String, &[u8])]) -> Result<()> {
+    let format = match channels {
+        1 => R8_UNORM,
+        4 => R8G8B8A8_UNORM,
+        _ => return Err(anyhow!("KTX2 arrays hold gray or RGBA layers, not {} channels", channels)),
+    };
+    let layer_size = width as usize * height as usize * channels;
+    if layers.is_empty() {
+        return Err(anyhow!("No layers to write"));
+    }
+    if let Some((name, _)) = layers.iter().find(|(_, data)| data.len() != layer_size) {
+        return Err(anyhow!("Layer {} isn't {}x{} like the first one, array layers must be the same size", name, width, height));
+    }
+    
+    // Basic data format descriptor: RGBSDA color model, BT.709 primaries, linear transfer, one
+    // sample of 8 bits per channel
+    let mut block = Vec::new();
+    block.extend_from_slice(&0u32.to_le_bytes());
+    block.extend_from_slice(&2u16.to_le_bytes());
+    block.extend_from_slice(&(24 + 16 * channels as u16).to_le_bytes());
+    block.extend_from_slice(&[1, 1, 1, 0]);
+    block.extend_from_slice(&[0; 4]);
+    block.extend_from_slice(&[channels as u8, 0, 0, 0, 0, 0, 0, 0]);
+    for (index, channel_id) in [0u8, 1, 2, 15].iter().take(channels).enumerate() {
+        block.extend_from_slice(&(index as u16 * 8).to_le_bytes());
+        block.extend_from_slice(&[7, *channel_id]);
+        block.extend_from_slice(&[0; 4]);
+        block.extend_from_slice(&0u32.to_le_bytes());
+        block.extend_from_slice(&255u32.to_le_bytes());
+    }
+    let mut dfd = ((block.len() + 4) as u32).to_le_bytes().to_vec();
+    dfd.extend_from_slice(&block);
+    
+    // Keys sorted by their bytes, each entry padded to 4 bytes
+    let names: Vec<&str> = layers.iter().map(|(name, _)| name.as_str()).collect();
+    let mut kvd = Vec::new();
+    for (key, value) in [("KTXwriter", format!("ormseparatev3 {}", env!("CARGO_PKG_VERSION"))), (LAYERS_KEY, names.join("\n"))] {
+        let entry = format!("{}\0{}\0", key, value);
+        kvd.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+        kvd.extend_from_slice(entry.as_bytes());
+        kvd.resize(kvd.len().next_multiple_of(4), 0);
+    }
+    
+    let dfd_offset = LEVEL_INDEX_END;
+    let kvd_offset = dfd_offset + dfd.len();
+    // Level data is aligned to the texel size and 4 bytes
+    let data_offset = (kvd_offset + kvd.len()).next_multiple_of(channels.max(4));
+    let data_size = layer_size * layers.len();
+    
+    let mut file = IDENTIFIER.to_vec();
+    for value in [format, 1, width, height, 0, layers.len() as u32, 1, 1, 0] {
+        file.extend_from_slice(&value.to_le_bytes());
+    }
+    for value in [dfd_offset as u32, dfd.len() as u32, kvd_offset as u32, kvd.len() as u32] {
+        file.extend_from_slice(&value.to_le_bytes());
+    }
+    file.extend_from_slice(&[0; 16]);
+    for value in [data_offset as u64, data_size as u64, data_size as u64] {
+        file.extend_from_slice(&value.to_le_bytes());
+    }
+    file.extend_from_slice(&dfd);
+    file.extend_from_slice(&kvd);
+    file.resize(data_offset, 0);
+    for (_, data) in layers {
+        file.extend_from_slice(data);
+    }
+    
+    fs::write(path, file).with_context(|| format!("Failed to write KTX2 file: {}", path.display()))
+}
+
+// Layers of a file written by write_array, as (width, height, channels, data of each layer)
+pub fn read_array(path: &Path) -> Result<(u32, u32, usize, Vec<Vec<u8>>)> {
+    let data = fs::read(path).with_context(|| format!("Failed to read KTX2 file: {}", path.display()))?;
+    if data.len() < LEVEL_INDEX_END || !data.starts_with(&IDENTIFIER) {
+        return Err(anyhow!("Not a KTX2 file: {}", path.display()));
+    }
+    let u32_at = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap()) as usize;
+    
+    let channels = match u32_at(12) {
+        R8_UNORM => 1,
+        R8G8B8A8_UNORM => 4,
+        format => return Err(anyhow!("Unsupported KTX2 format {} in {}", format, path.display())),
+    };
+    let (width, height, layer_count) = (u32_at(20), u32_at(24), u32_at(32).max(1) as usize);
+    let (offset, length) = (u64_at(80), u64_at(88));
+    let layer_size = width as usize * height as usize * channels;
+    let level = data.get(offset..offset + length)
+        .filter(|level| level.len() == layer_size * layer_count)
+        .ok_or_else(|| anyhow!("Truncated KTX2 level data in {}", path.display()))?;
+    Ok((width, height, channels, level.chunks(layer_size).map(<[u8]>::to_vec).collect()))
+}
@@ -0,0 +1,4021 @@
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write, BufRead, BufReader, BufWriter, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+use anyhow::{Result, Context, anyhow};
+use clap::{CommandFactory, Parser, Subcommand};
+use image::{DynamicImage, GrayImage, ImageBuffer, ImageError, Pixel, Primitive, Rgba, Rgba32FImage, RgbaImage};
+use image::io::Limits;
+use tiff::ColorType as TiffColorType;
+use tiff::decoder::DecodingResult;
+use tiff::encoder::{TiffEncoder, colortype, compression::Lzw};
+use tiff::tags::Tag;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+mod atomic;
+mod bench;
+mod budget;
+mod cancel;
+mod compare;
+mod completions;
+mod cubemap;
+mod daemon;
+mod docs;
+mod doctor;
+mod encode;
+mod expr;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod generate;
+mod hash;
+mod histogram;
+mod hooks;
+mod html;
+mod inspect;
+mod integration;
+mod json;
+mod ktx;
+mod lut;
+mod material;
+mod metadata;
+mod normal;
+mod pack;
+mod processor;
+mod profiles;
+mod provenance;
+mod progress;
+mod psd;
+mod report;
+mod scene;
+mod selfupdate;
+mod serve;
+mod sheet;
+mod sprites;
+mod substance;
+mod verify;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use budget::MemoryBudget;
+use encode::encode_image;
+use expr::Expression;
+use lut::Lut;
+use metadata::Metadata;
+use report::{FileSummary, OutputSummary};
+use psd::LayerSelector;
+use completions::Shell;
+use docs::DocsFormat;
+use integration::IntegrationAction;
+use daemon::DaemonAction;
+use profiles::ProfileAction;
+use progress::Console;
+use material::MaterialMap;
+use provenance::Provenance;
+
+pub use cancel::CancelToken;
+pub use processor::{ChannelImage, Job, Processor, ProcessorBuilder};
+pub use progress::{NoProgress, ProgressSink};
+
+// Configuration structures
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum MatchOn {
+    #[default]
+    Filename,
+    // Full path for regexes, path relative to the processed directory for globs
+    Path,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum PngCompression {
+    Fast,
+    #[default]
+    Default,
+    Best,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum PngFilter {
+    None,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    #[default]
+    Adaptive,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+enum JpegSubsampling {
+    // Single-component grayscale JPEG without chroma planes, for channel maps
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    // YCbCr with horizontally halved chroma, the only ratio the encoder supports
+    #[serde(rename = "4:2:2")]
+    Yuv422,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum TgaOrigin {
+    // Rows stored top-first with the descriptor's top-left bit set
+    #[default]
+    Top,
+    // Rows stored bottom-first, the layout older importers assume
+    Bottom,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+enum BitDepth {
+    #[default]
+    #[serde(rename = "8")]
+    #[value(name = "8")]
+    Eight,
+    #[serde(rename = "16")]
+    #[value(name = "16")]
+    Sixteen,
+    #[serde(rename = "32f")]
+    #[value(name = "32f")]
+    Float,
+}
+
+impl BitDepth {
+    fn bytes_per_sample(self) -> u64 {
+        match self {
+            BitDepth::Eight => 1,
+            BitDepth::Sixteen => 2,
+            BitDepth::Float => 4,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum MetadataMode {
+    // Outputs carry only what the encoder writes, for byte-identical results
+    #[default]
+    Strip,
+    // ICC, EXIF and XMP of the source are copied into PNG and JPEG outputs
+    Preserve,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum DuplicateMode {
+    // Every channel is written, duplicates aren't looked for
+    #[default]
+    Write,
+    // Only the first of identical outputs is written, the rest are reported
+    Skip,
+    // Identical outputs become links to the first one
+    Hardlink,
+    Symlink,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum OutputLayout {
+    // Sources keep their folder relative to the processed folder under output_dir
+    #[default]
+    Mirror,
+    // Every output goes straight into output_dir
+    Flatten,
+}
+
+// What happens to inputs whose R, G and B are the same nearly everywhere, most likely a single map
+// rather than a packed one, which would split into identical outputs
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum GrayscaleInput {
+    Split,
+    #[default]
+    Warn,
+    Skip,
+}
+
+// What happens to a source once every one of its outputs was written
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(try_from = "String", into = "String")]
+enum AfterSource {
+    #[default]
+    Keep,
+    Delete,
+    // Moved under this folder, keeping its path under the processed folder
+    Move(PathBuf),
+}
+
+impl AfterSource {
+    fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "keep" => Ok(AfterSource::Keep),
+            "delete" => Ok(AfterSource::Delete),
+            _ => match text.strip_prefix("move:") {
+                Some(dir) if !dir.is_empty() => Ok(AfterSource::Move(PathBuf::from(dir))),
+                _ => Err(format!("expected keep, delete or move:<dir>, got '{}'", text)),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for AfterSource {
+    type Error = String;
+    
+    fn try_from(text: String) -> Result<Self, String> {
+        AfterSource::parse(&text)
+    }
+}
+
+impl From<AfterSource> for String {
+    fn from(after: AfterSource) -> String {
+        match after {
+            AfterSource::Keep => "keep".to_string(),
+            AfterSource::Delete => "delete".to_string(),
+            AfterSource::Move(dir) => format!("move:{}", dir.display()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum HistogramFormat {
+    Png,
+    Csv,
+}
+
+// How the six faces of cubemap inputs are arranged
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum CubemapLayout {
+    // Picked from the aspect ratio: 4:3 hcross, 3:4 vcross, 6:1 hstrip, 1:6 vstrip
+    Auto,
+    // +Y above, -X +Z +X -Z in the middle row, -Y below
+    Hcross,
+    // +Y, then -X +Z +X, then -Y, then -Z upside down
+    Vcross,
+    // +X -X +Y -Y +Z -Z left to right
+    Hstrip,
+    // +X -X +Y -Y +Z -Z top to bottom
+    Vstrip,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum ProvenanceFormat {
+    // {output}.json next to each channel output
+    Json,
+    // A tEXt chunk in PNG outputs, other formats get the JSON sidecar
+    Png,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum ReportFormat {
+    Html,
+}
+
+// How informational commands print, json for wrapper tools instead of parsing the text
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+enum InfoFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+// Alpha of channel outputs: a constant (0-255 units) or a channel of the source
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+enum AlphaSource {
+    Fill(f32),
+    Channel(SourceChannel),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum SourceChannel {
+    R,
+    G,
+    B,
+    // The input's own alpha
+    #[serde(alias = "passthrough")]
+    A,
+}
+
+// What a channel of a texture packed with the pack command holds: a constant (0-255 units), a map
+// of the set or a copy of another channel of the packed texture
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+enum PackSource {
+    Fill(f32),
+    // File name pattern where the first * stands for the set name, e.g. "*_AO.*". Its red channel
+    // (the value of grayscale maps) is read
+    File(String),
+    // A file pattern with the channel to read, and the value used when a set has no such file
+    Map {
+        file: String,
+        #[serde(default)]
+        channel: Option<SourceChannel>,
+        #[serde(default)]
+        fill: Option<f32>,
+    },
+    Copy { copy: SourceChannel },
+}
+
+// Packing the separate maps of each texture set into one texture, the reverse of a split
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct PackConfig {
+    // Stem of the packed texture, {stem} is the set name
+    #[serde(default = "default_pack_name")]
+    output_name: String,
+    // Unset color channels are black, alpha is only written when a is set
+    #[serde(default)]
+    r: Option<PackSource>,
+    #[serde(default)]
+    g: Option<PackSource>,
+    #[serde(default)]
+    b: Option<PackSource>,
+    #[serde(default)]
+    a: Option<PackSource>,
+    // Policy for sets whose maps differ in size, unless size resizes every map to [width, height]
+    #[serde(default)]
+    mismatch: SizeMismatch,
+    #[serde(default)]
+    size: Option<[u32; 2]>,
+    // Resampling filter of resized maps
+    #[serde(default)]
+    filter: ResampleFilter,
+}
+
+impl PackConfig {
+    // Sources of the destination channels in RGBA order
+    fn sources(&self) -> [Option<&PackSource>; 4] {
+        [self.r.as_ref(), self.g.as_ref(), self.b.as_ref(), self.a.as_ref()]
+    }
+}
+
+// What the pack command does with a set whose maps have different sizes. Maps are resized to
+// the one with the most or fewest pixels
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+enum SizeMismatch {
+    #[default]
+    Error,
+    ResizeToLargest,
+    ResizeToSmallest,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum ResampleFilter {
+    Nearest,
+    Bilinear,
+    #[default]
+    Bicubic,
+    Lanczos,
+}
+
+impl ResampleFilter {
+    fn filter_type(self) -> image::imageops::FilterType {
+        match self {
+            ResampleFilter::Nearest => image::imageops::FilterType::Nearest,
+            ResampleFilter::Bilinear => image::imageops::FilterType::Triangle,
+            ResampleFilter::Bicubic => image::imageops::FilterType::CatmullRom,
+            ResampleFilter::Lanczos => image::imageops::FilterType::Lanczos3,
+        }
+    }
+}
+
+fn default_pack_name() -> String {
+    "{stem}_packed".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ColorSpace {
+    Linear,
+    Srgb,
+}
+
+impl ColorSpace {
+    fn name(self) -> &'static str {
+        match self {
+            ColorSpace::Linear => "linear",
+            ColorSpace::Srgb => "srgb",
+        }
+    }
+}
+
+// Remap [in_low, in_high] to [out_low, out_high], in 0-255 units whatever the bit depth
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+struct Levels {
+    #[serde(default)]
+    in_low: f32,
+    #[serde(default = "default_level_high")]
+    in_high: f32,
+    #[serde(default)]
+    out_low: f32,
+    #[serde(default = "default_level_high")]
+    out_high: f32,
+}
+
+fn default_level_high() -> f32 {
+    255.0
+}
+
+// How neighbours past the image edge are sampled, repeat suits tiling textures
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum WrapMode {
+    #[default]
+    Clamp,
+    Repeat,
+}
+
+// Tangent-space normal map generated from a channel's values taken as height
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+struct HeightToNormal {
+    // Scales the slopes, higher values give steeper normals
+    #[serde(default = "default_normal_strength")]
+    strength: f32,
+    #[serde(default)]
+    wrap: WrapMode,
+    // Y pointing down (DirectX) instead of up (OpenGL)
+    #[serde(default)]
+    directx: bool,
+}
+
+fn default_normal_strength() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct ChannelConfig {
+    name: String,
+    #[serde(default)]
+    channel: usize, // 0 = R, 1 = G, 2 = B
+    // Per-pixel math over r, g, b and a (0-1) used instead of channel, e.g. "1.0 - r * g"
+    #[serde(default)]
+    expr: Option<String>,
+    // Compute the Z of a two-channel (BC5-style) normal map from r and g, instead of channel
+    #[serde(default)]
+    reconstruct_z: bool,
+    // Read the source with green inverted, for normal maps in the other engine's Y convention
+    #[serde(default)]
+    flip_green: bool,
+    // Write a normal map computed from the channel's values (after adjustments) instead of grayscale
+    #[serde(default)]
+    height_to_normal: Option<HeightToNormal>,
+    // Overrides the profile's output_format for this channel only
+    #[serde(default)]
+    output_format: Option<String>,
+    // Sample depth of the output, converted from whatever the source uses
+    #[serde(default)]
+    bit_depth: Option<BitDepth>,
+    // Color space the output is tagged as, untagged if unset
+    #[serde(default)]
+    color_space: Option<ColorSpace>,
+    // Values are raised to 1/gamma, so 2.2 brightens and 0.4545 linearizes sRGB-authored data
+    #[serde(default)]
+    gamma: Option<f32>,
+    // Applied before gamma, values outside the input range are clipped
+    #[serde(default)]
+    levels: Option<Levels>,
+    // 1D LUT (.cube or CSV) applied after levels and gamma, relative paths start at the config folder
+    #[serde(default)]
+    lut: Option<PathBuf>,
+    // Turns the channel into a black/white mask, values >= threshold (0-255 units) become white
+    #[serde(default)]
+    threshold: Option<f32>,
+    // Write this channel into the alpha of the named channel's output instead of its own file
+    #[serde(default)]
+    into_alpha_of: Option<String>,
+    // Folder under the output root this channel's files go to, e.g. "Roughness/". Not used by
+    // layered formats, which write all channels into one file
+    #[serde(default)]
+    subdir: Option<PathBuf>,
+    // What the map is for in material_json, the lowercase channel name if unset
+    #[serde(default)]
+    semantic: Option<String>,
+    // Loaded from lut before processing
+    #[serde(skip)]
+    lut_curve: Option<Lut>,
+    // Parsed from expr before processing
+    #[serde(skip)]
+    expression: Option<Expression>,
+}
+
+impl ChannelConfig {
+    // Output format this channel is written in
+    fn format<'a>(&'a self, profile: &'a Profile) -> &'a str {
+        self.output_format.as_deref().unwrap_or(&profile.output_format)
+    }
+    
+    // What the map is for, e.g. "roughness"
+    fn semantic(&self) -> String {
+        self.semantic.clone().unwrap_or_else(|| self.name.to_lowercase())
+    }
+    
+    // Folder this channel's outputs and histograms are written to for a given output root
+    fn output_parent(&self, parent: &Path) -> PathBuf {
+        match &self.subdir {
+            Some(subdir) => parent.join(subdir),
+            None => parent.to_path_buf(),
+        }
+    }
+}
+
+// A profile table of config.toml, the settings one split runs with
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Profile {
+    name: String,
+    // Exactly one of file_regex and file_glob must be set
+    #[serde(default)]
+    file_regex: Option<String>,
+    #[serde(default)]
+    file_glob: Option<String>,
+    // Whether file_regex/file_glob see only the file name or the whole path
+    #[serde(default)]
+    match_on: MatchOn,
+    // Paths (relative to the processed directory) matching any of these are skipped
+    #[serde(default)]
+    exclude_regex: Vec<String>,
+    // Only files with these extensions (case-insensitive) are considered in directory walks
+    #[serde(default = "default_extensions")]
+    extensions: Vec<String>,
+    // Directory walk controls, None means unlimited depth
+    #[serde(default)]
+    max_depth: Option<usize>,
+    #[serde(default)]
+    follow_symlinks: bool,
+    // Dot files/folders (and hidden files on Windows) are skipped unless enabled
+    #[serde(default)]
+    include_hidden: bool,
+    // Images outside these [width, height] bounds are skipped with a warning
+    #[serde(default)]
+    min_size: Option<[u32; 2]>,
+    #[serde(default)]
+    max_size: Option<[u32; 2]>,
+    // Decode safety limits for untrusted inputs, files over a limit are skipped
+    #[serde(default)]
+    max_pixels: Option<u64>,
+    // Defaults to the decoder's own 512 MiB allocation limit
+    #[serde(default)]
+    max_memory_mb: Option<u64>,
+    // Estimated memory of all files split at once, parallel runs wait for room before decoding
+    #[serde(default)]
+    max_inflight_memory_mb: Option<u64>,
+    // Layer of PSD inputs to split (name or index), the merged composite if unset
+    #[serde(default)]
+    psd_layer: Option<LayerSelector>,
+    // Rotate/flip inputs upright according to their EXIF orientation before splitting
+    #[serde(default = "default_apply_orientation")]
+    apply_orientation: bool,
+    // Split inputs into a [columns, rows] grid of tiles before extracting channels, for trim sheets
+    // and atlases packing several materials. Tiles take atlas_names in row-major order (r1c1, r1c2
+    // and so on past the end) as {tile}, appended to output names that don't use it
+    #[serde(default)]
+    atlas: Option<[u32; 2]>,
+    #[serde(default)]
+    atlas_names: Vec<String>,
+    // Frame map of sprite sheet inputs, a path next to the source where {stem} is its file stem,
+    // e.g. "{stem}.json". Each frame is split like an atlas tile, sources without a map whole
+    #[serde(default)]
+    sprite_frames: Option<String>,
+    // Cut cubemap inputs into their six faces, named px, nx, py, ny, pz and nz as {tile}
+    #[serde(default)]
+    cubemap: Option<CubemapLayout>,
+    // Write the tiles, frames or faces themselves instead of their channels
+    #[serde(default)]
+    atlas_only: bool,
+    // WebP encoding: lossy output needs libwebp, so only lossless is available and false is rejected
+    #[serde(default = "default_lossless")]
+    lossless: bool,
+    // 0-100 for WebP output. Not a lossy quality: below 100 the low bits of each color value are
+    // dropped before lossless encoding, 1 bit at 80-99 up to 5 bits below 20
+    #[serde(default)]
+    quality: Option<u8>,
+    // PNG encoder speed/size trade-off and row filter strategy
+    #[serde(default)]
+    png_compression: PngCompression,
+    #[serde(default)]
+    png_filter: PngFilter,
+    // 1-100, higher is better quality and larger files
+    #[serde(default = "default_jpeg_quality")]
+    jpeg_quality: u8,
+    #[serde(default)]
+    jpeg_subsampling: JpegSubsampling,
+    // TGA run-length compression and row order
+    #[serde(default)]
+    tga_rle: bool,
+    #[serde(default)]
+    tga_origin: TgaOrigin,
+    output_format: String,
+    // Color space of the source data, channels tagged differently are converted when set
+    #[serde(default)]
+    input_color_space: Option<ColorSpace>,
+    #[serde(default)]
+    metadata: MetadataMode,
+    // Channels with a single value everywhere (like all-black metallic) are not written
+    #[serde(default)]
+    skip_constant: bool,
+    // Inputs that are grayscale, in color channels within 2/255 of each other in 99% of the pixels
+    #[serde(default)]
+    grayscale_input: GrayscaleInput,
+    // What to do with channel outputs of a page that are byte-identical to an earlier one
+    #[serde(default)]
+    duplicates: DuplicateMode,
+    // Alpha written into channel outputs, opaque if unset
+    #[serde(default)]
+    alpha: Option<AlphaSource>,
+    // Write a histogram of each channel output next to it, as {output}_histogram.png or .csv
+    #[serde(default)]
+    histogram: Option<HistogramFormat>,
+    // Record the source, its SHA-256, profile, channel, tool version and time of each channel output
+    #[serde(default)]
+    provenance: Option<ProvenanceFormat>,
+    // Grid of source and channel thumbnails. A path with {stem} (or other output_name variables)
+    // gives one sheet per file next to it, otherwise one sheet for the whole run
+    #[serde(default)]
+    contact_sheet: Option<String>,
+    // Per-source descriptor of the written maps, a path relative to the output folder that may use
+    // output_name variables, e.g. "{stem}_material.json"
+    #[serde(default)]
+    material_json: Option<String>,
+    // MaterialX document wiring the maps into a standard_surface, a path like material_json
+    #[serde(default)]
+    materialx: Option<String>,
+    // USD layer (.usda) with a UsdPreviewSurface material using the maps, a path like material_json
+    #[serde(default)]
+    usd: Option<String>,
+    // Python script building a Principled BSDF material from the maps in Blender, a path like material_json
+    #[serde(default)]
+    blender_script: Option<String>,
+    // CSV with one row per processed file: dimensions, timing and stats and size of each output
+    #[serde(default)]
+    stats_csv: Option<PathBuf>,
+    // Summary of the run with thumbnails, warnings and failures, written to report_path
+    #[serde(default)]
+    report: Option<ReportFormat>,
+    #[serde(default = "default_report_path")]
+    report_path: PathBuf,
+    // Sources are copied here (keeping their path under the processed folder) before their outputs
+    // are written, or hard-linked with backup_links where the filesystem allows
+    #[serde(default)]
+    backup_dir: Option<PathBuf>,
+    #[serde(default)]
+    backup_links: bool,
+    // Outputs are written under this folder instead of next to their source
+    #[serde(default)]
+    output_dir: Option<PathBuf>,
+    #[serde(default)]
+    output_layout: OutputLayout,
+    // keep, delete or move:<dir>, applied only after all outputs of a file were written
+    #[serde(default)]
+    after_source: AfterSource,
+    // Shell commands run before and after each output file is written. {input}, {output}, {dir},
+    // {stem} and {channel} are replaced with quoted values, so they go in unquoted; a failing
+    // command fails the file
+    #[serde(default)]
+    pre_command: Option<String>,
+    #[serde(default)]
+    post_command: Option<String>,
+    // Output file stem template. Supports {stem}, {channel} and any named capture from file_regex
+    #[serde(default = "default_output_name")]
+    output_name: String,
+    // Packing tokens removed from the end of {stem}, like "_ORM". Entries in /pattern/flags form
+    // are regexes; the first entry that matches is removed
+    #[serde(default)]
+    strip_suffix: Vec<String>,
+    // How the pack command combines separate maps into one texture in this profile's layout
+    #[serde(default)]
+    pack: Option<PackConfig>,
+    channels: Vec<ChannelConfig>,
+}
+
+impl Profile {
+    // One profile in config.toml syntax, without the [profiles.name] header
+    pub fn from_toml(text: &str) -> Result<Profile> {
+        toml::from_str(text).context("Failed to parse profile")
+    }
+    
+    // max_memory_mb in bytes, for the decoder limits
+    pub(crate) fn max_alloc(&self) -> Option<u64> {
+        self.max_memory_mb.map(|mb| mb.saturating_mul(1024 * 1024))
+    }
+    
+    // extensions without a leading '.', lowercase like the extensions they're compared to
+    pub(crate) fn allowed_extensions(&self) -> Vec<String> {
+        self.extensions.iter().map(|ext| ext.trim_start_matches('.').to_ascii_lowercase()).collect()
+    }
+}
+
+// The built-in ORM profile, as written to a new config.toml
+impl Default for Profile {
+    fn default() -> Self {
+        let config = Config::default();
+        config.profiles[&config.default_profile].clone()
+    }
+}
+
+fn default_extensions() -> Vec<String> {
+    ["png", "jpg", "jpeg", "tga", "tif", "tiff", "bmp", "webp", "exr", "hdr", "dds", "qoi", "psd", "psb"]
+        .iter()
+        .map(|ext| ext.to_string())
+        .collect()
+}
+
+fn default_lossless() -> bool {
+    true
+}
+
+fn default_apply_orientation() -> bool {
+    true
+}
+
+fn default_jpeg_quality() -> u8 {
+    90
+}
+
+fn default_output_name() -> String {
+    "{stem}_{channel}".to_string()
+}
+
+fn default_report_path() -> PathBuf {
+    PathBuf::from("report.html")
+}
+
+// When the console window stays open after files were dropped onto the executable
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+enum PauseMode {
+    Always,
+    #[default]
+    OnError,
+    Never,
+}
+
+// Runs started by dropping files onto the executable never prompt, they use this profile
+// (or the default one) and print a short summary at the end
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct DragDrop {
+    #[serde(default)]
+    profile: Option<String>,
+    #[serde(default)]
+    pause: PauseMode,
+}
+
+// A folder the daemon watches, new and changed images in it are split with profile
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct HotFolder {
+    path: PathBuf,
+    #[serde(default)]
+    profile: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct DaemonConfig {
+    #[serde(default)]
+    hot_folders: Vec<HotFolder>,
+    // Local address for status queries and submitted jobs
+    #[serde(default = "default_daemon_address")]
+    address: String,
+    #[serde(default)]
+    workers: Option<usize>,
+    #[serde(default = "default_poll_seconds")]
+    poll_seconds: u64,
+    // Queue and processed files, kept across restarts. Relative to the executable
+    #[serde(default = "default_state_file")]
+    state_file: PathBuf,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig {
+            hot_folders: Vec::new(),
+            address: default_daemon_address(),
+            workers: None,
+            poll_seconds: default_poll_seconds(),
+            state_file: default_state_file(),
+        }
+    }
+}
+
+fn default_daemon_address() -> String {
+    "127.0.0.1:7878".to_string()
+}
+
+fn default_poll_seconds() -> u64 {
+    2
+}
+
+fn default_state_file() -> PathBuf {
+    PathBuf::from("daemon-state.toml")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Config {
+    default_profile: String,
+    // Worker threads for splitting files in parallel, all logical cores if unset
+    #[serde(default)]
+    threads: Option<usize>,
+    #[serde(default)]
+    drag_drop: DragDrop,
+    #[serde(default)]
+    daemon: DaemonConfig,
+    profiles: HashMap<String, Profile>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let mut profiles = HashMap::new();
+        
+        // Default ORM profile
+        let default_profile = Profile {
+            name: "orm".to_string(),
+            file_regex: Some("/orm/i".to_string()),  // New format: /pattern/args
+            file_glob: None,
+            match_on: MatchOn::Filename,
+            exclude_regex: Vec::new(),
+            extensions: default_extensions(),
+            max_depth: None,
+            follow_symlinks: false,
+            include_hidden: false,
+            min_size: None,
+            max_size: None,
+            max_pixels: None,
+            max_memory_mb: None,
+            max_inflight_memory_mb: None,
+            psd_layer: None,
+            apply_orientation: default_apply_orientation(),
+            atlas: None,
+            atlas_names: Vec::new(),
+            sprite_frames: None,
+            cubemap: None,
+            atlas_only: false,
+            lossless: default_lossless(),
+            quality: None,
+            png_compression: PngCompression::Default,
+            png_filter: PngFilter::Adaptive,
+            jpeg_quality: default_jpeg_quality(),
+            jpeg_subsampling: JpegSubsampling::None,
+            tga_rle: false,
+            tga_origin: TgaOrigin::Top,
+            output_format: "png".to_string(),
+            input_color_space: None,
+            metadata: MetadataMode::Strip,
+            skip_constant: false,
+            grayscale_input: GrayscaleInput::Warn,
+            duplicates: DuplicateMode::Write,
+            alpha: None,
+            histogram: None,
+            provenance: None,
+            contact_sheet: None,
+            material_json: None,
+            materialx: None,
+            usd: None,
+            blender_script: None,
+            stats_csv: None,
+            report: None,
+            report_path: default_report_path(),
+            backup_dir: None,
+            backup_links: false,
+            output_dir: None,
+            output_layout: OutputLayout::Mirror,
+            after_source: AfterSource::Keep,
+            pre_command: None,
+            post_command: None,
+            output_name: default_output_name(),
+            strip_suffix: Vec::new(),
+            pack: Some(PackConfig {
+                output_name: "{stem}_ORM".to_string(),
+                r: Some(PackSource::Map { file: "*_{AO,Occlusion}.*".to_string(), channel: None, fill: Some(255.0) }),
+                g: Some(PackSource::File("*_{Roughness,Rough}.*".to_string())),
+                b: Some(PackSource::Map { file: "*_{Metallic,Metalness}.*".to_string(), channel: None, fill: Some(0.0) }),
+                a: None,
+                mismatch: SizeMismatch::Error,
+                size: None,
+                filter: ResampleFilter::Bicubic,
+            }),
+            channels: vec![
+                ChannelConfig { name: "Occlusion".to_string(), channel: 0, ..Default::default() },
+                ChannelConfig { name: "Roughness".to_string(), channel: 1, ..Default::default() },
+                ChannelConfig { name: "Metallic".to_string(), channel: 2, ..Default::default() },
+            ],
+        };
+        
+        profiles.insert("orm".to_string(), default_profile);
+        
+        Config {
+            default_profile: "orm".to_string(),
+            threads: None,
+            drag_drop: DragDrop::default(),
+            daemon: DaemonConfig::default(),
+            profiles,
+        }
+    }
+}
+
+impl Config {
+    fn load(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+            
+            let config: Config = toml::from_str(&content)
+                .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+            
+            Ok(config)
+        } else {
+            // Create default config if it doesn't exist
+            println!("Config file not found. Creating default config at: {}", path.display());
+            
+            let config = Config::default();
+            let toml = toml::to_string_pretty(&config)
+                .context("Failed to serialize default config")?;
+            
+            fs::write(path, toml)
+                .with_context(|| format!("Failed to write default config to: {}", path.display()))?;
+            
+            println!("Default config created successfully with 'orm' profile.");
+            
+            Ok(config)
+        }
+    }
+    
+    fn get_profile(&self, profile_name: Option<&str>) -> Result<Profile> {
+        let profile_name = profile_name.unwrap_or(&self.default_profile);
+        
+        self.profiles.get(profile_name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Profile '{}' not found in config", profile_name))
+    }
+}
+
+// CLI Arguments
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+    
+    /// Input files or directories to process
+    inputs: Vec<PathBuf>,
+    
+    /// Profile to use for processing
+    #[arg(short, long)]
+    profile: Option<String>,
+    
+    /// Skip paths matching this regex (can be repeated)
+    #[arg(short = 'x', long = "exclude", value_name = "REGEX")]
+    exclude: Vec<String>,
+    
+    /// Maximum directory depth to recurse into (1 = only the given folder)
+    #[arg(long)]
+    max_depth: Option<usize>,
+    
+    /// Follow symbolic links while walking directories
+    #[arg(long)]
+    follow_symlinks: bool,
+    
+    /// Include hidden files and folders
+    #[arg(long)]
+    hidden: bool,
+    
+    /// Layer of PSD inputs to split, by name or index (default: merged image)
+    #[arg(long, value_name = "LAYER")]
+    psd_layer: Option<String>,
+    
+    /// Keep pixels as stored instead of applying the EXIF orientation
+    #[arg(long)]
+    ignore_orientation: bool,
+    
+    /// Split inputs into a grid of COLUMNSxROWS tiles and process each on its own
+    #[arg(long, value_name = "COLUMNSxROWS", value_parser = generate::parse_size)]
+    atlas: Option<(u32, u32)>,
+    
+    /// Names of the atlas tiles in row-major order, used as {tile} (default: r1c1, r1c2, ...)
+    #[arg(long, value_name = "NAMES", value_delimiter = ',')]
+    atlas_names: Vec<String>,
+    
+    /// Split the frames of sprite sheets listed in a TexturePacker JSON or XML map next to each source ({stem}.json if no path is given)
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "{stem}.json")]
+    sprite_frames: Option<String>,
+    
+    /// Split cubemap inputs into their six faces first (auto picks the layout from the aspect ratio)
+    #[arg(long, value_enum, value_name = "LAYOUT", num_args = 0..=1, default_missing_value = "auto")]
+    cubemap: Option<CubemapLayout>,
+    
+    /// Write the atlas tiles, sprite frames or cubemap faces instead of splitting them into channels
+    #[arg(long)]
+    atlas_only: bool,
+    
+    /// Write a histogram next to each channel output (png if no format is given)
+    #[arg(long, value_enum, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "png")]
+    histogram: Option<HistogramFormat>,
+    
+    /// Record where each channel output came from, in a JSON sidecar or a PNG text chunk (json if no format is given)
+    #[arg(long, value_enum, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "json")]
+    provenance: Option<ProvenanceFormat>,
+    
+    /// Write a grid of labeled thumbnails of every processed file and its channels
+    #[arg(long, value_name = "PATH")]
+    contact_sheet: Option<String>,
+    
+    /// Write a JSON descriptor of each source's maps for material importers ({stem}_material.json if no path is given)
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "{stem}_material.json")]
+    material_json: Option<String>,
+    
+    /// Write a MaterialX document with a standard_surface using each source's maps ({stem}.mtlx if no path is given)
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "{stem}.mtlx")]
+    materialx: Option<String>,
+    
+    /// Write a USD layer with a UsdPreviewSurface material using each source's maps ({stem}.usda if no path is given)
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "{stem}.usda")]
+    usd: Option<String>,
+    
+    /// Write a Python script that builds a Principled BSDF material from each source's maps in Blender ({stem}_blender.py if no path is given)
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "{stem}_blender.py")]
+    blender_script: Option<String>,
+    
+    /// Write a CSV with dimensions, timing and per-channel stats of every processed file
+    #[arg(long, value_name = "PATH")]
+    stats_csv: Option<PathBuf>,
+    
+    /// Write a summary of the run with thumbnails, warnings and failures
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    report: Option<ReportFormat>,
+    
+    /// Where to write the report (default: report.html)
+    #[arg(long, value_name = "PATH")]
+    report_path: Option<PathBuf>,
+    
+    /// Estimated memory in MiB of the files split at the same time (default: max_inflight_memory_mb of the profile, unlimited)
+    #[arg(long, value_name = "MB")]
+    max_inflight_memory: Option<u64>,
+    
+    /// Write outputs under this folder instead of next to each source
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+    
+    /// Keep each source's folder relative to the processed folder under --output-dir (default)
+    #[arg(long, conflicts_with = "flatten")]
+    mirror_structure: bool,
+    
+    /// Write all outputs directly into --output-dir
+    #[arg(long)]
+    flatten: bool,
+    
+    /// Copy each source into this folder before writing its outputs
+    #[arg(long, value_name = "DIR")]
+    backup_dir: Option<PathBuf>,
+    
+    /// What to do with each source once all its outputs are written: keep, delete or move:<dir>
+    #[arg(long, value_name = "ACTION", value_parser = AfterSource::parse)]
+    after_source: Option<AfterSource>,
+    
+    /// Guess the packing of every input image and the profile splitting it instead of processing them
+    #[arg(long)]
+    detect: bool,
+    
+    /// Print decode, extract, encode and write times of every file and percentiles over the run
+    #[arg(short, long)]
+    verbose: bool,
+    
+    /// Worker threads for splitting files in parallel (default: threads from the config, or all logical cores)
+    #[arg(long, value_name = "N")]
+    threads: Option<usize>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// List available profiles
+    ListProfiles {
+        /// Print only the profile names, one per line
+        #[arg(long)]
+        names: bool,
+        
+        /// json lists every profile with its file pattern, output settings and channel layout
+        #[arg(long, value_enum, default_value = "text", conflicts_with = "names")]
+        format: InfoFormat,
+    },
+    /// Split a file, re-pack the decoded outputs and compare them with the source
+    Verify {
+        /// Packed image to check the profile with
+        file: PathBuf,
+        
+        /// Largest allowed difference per pixel, in 0-255 units
+        #[arg(long, default_value_t = 0.5)]
+        tolerance: f32,
+    },
+    /// Compare two images or directory trees per channel with PSNR and SSIM
+    Diff {
+        /// Reference file or directory
+        first: PathBuf,
+        
+        /// File or directory compared against the reference
+        second: PathBuf,
+        
+        /// Write the difference as an image (a directory of them when comparing directories)
+        #[arg(long, value_name = "PATH")]
+        diff_image: Option<PathBuf>,
+        
+        /// Multiplier for differences in the difference image, so small ones become visible
+        #[arg(long, default_value_t = 10.0)]
+        gain: f32,
+    },
+    /// Print format, channel statistics and the likely packing of an image
+    Inspect {
+        /// Image to inspect
+        file: PathBuf,
+        
+        #[arg(long, value_enum, default_value = "text")]
+        format: InfoFormat,
+    },
+    /// Guess how an image is packed from its channel statistics and suggest the profile splitting it
+    Detect {
+        /// Image to analyze
+        file: PathBuf,
+    },
+    /// List the textures a glTF, OBJ/MTL or ASCII FBX scene references and flag the packed ones
+    Scan {
+        /// Scene file
+        scene: PathBuf,
+        
+        /// Process the scene's textures after listing them, as if it was given as input
+        #[arg(long)]
+        process: bool,
+    },
+    /// Pack the separate maps of each texture set into one texture, as the profile's pack table maps them
+    Pack {
+        /// Map files or directories holding them
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        
+        /// What to do with sets whose maps differ in size (default: mismatch of the pack table)
+        #[arg(long, value_enum, value_name = "POLICY")]
+        mismatch: Option<SizeMismatch>,
+        
+        /// Resize every map to WIDTHxHEIGHT first
+        #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = generate::parse_size)]
+        size: Option<(u32, u32)>,
+        
+        /// Resampling filter of resized maps (default: filter of the pack table)
+        #[arg(long, value_enum)]
+        filter: Option<ResampleFilter>,
+        
+        /// Pack metallic, roughness and optional AO maps as a glTF metallicRoughness texture instead of with the pack table
+        #[arg(long)]
+        gltf: bool,
+    },
+    /// Group the images of a directory into texture sets and report problems
+    InspectSets {
+        /// Directory to scan
+        dir: PathBuf,
+    },
+    /// Invert the green channel of normal maps, converting between DirectX and OpenGL
+    NormalFlip {
+        /// Normal map files or directories
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        
+        /// Files in directories are only flipped when their name matches this regex
+        #[arg(long, value_name = "REGEX", default_value = "/normal|nrm/i")]
+        file_regex: String,
+        
+        /// Appended to the file stem of each flipped copy
+        #[arg(long, default_value = "_flipped")]
+        suffix: String,
+    },
+    /// Convert equirectangular panoramas into cubemaps
+    EquirectToCubemap {
+        /// Panorama files or directories
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        
+        /// Files in directories are only converted when their name matches this regex
+        #[arg(long, value_name = "REGEX", default_value = "/equirect|latlong|pano|env/i")]
+        file_regex: String,
+        
+        /// Edge length of each face in pixels (default: a quarter of the panorama width)
+        #[arg(long)]
+        size: Option<u32>,
+        
+        /// How the six faces are packed into one image (auto is hcross)
+        #[arg(long, value_enum, default_value_t = CubemapLayout::Hcross)]
+        layout: CubemapLayout,
+        
+        /// Write each face as its own file, named with _px, _nx, _py, _ny, _pz or _nz after the suffix
+        #[arg(long)]
+        faces: bool,
+        
+        /// Appended to the file stem of each cubemap
+        #[arg(long, default_value = "_cubemap")]
+        suffix: String,
+    },
+    /// Convert cross or strip cubemaps into equirectangular panoramas
+    CubemapToEquirect {
+        /// Cubemap files or directories
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        
+        /// Files in directories are only converted when their name matches this regex
+        #[arg(long, value_name = "REGEX", default_value = "/cube|probe|sky/i")]
+        file_regex: String,
+        
+        /// How the faces are arranged in the input (auto picks it from the aspect ratio)
+        #[arg(long, value_enum, default_value_t = CubemapLayout::Auto)]
+        layout: CubemapLayout,
+        
+        /// Width of the panorama in pixels, its height is half of it (default: four face widths)
+        #[arg(long)]
+        width: Option<u32>,
+        
+        /// Appended to the file stem of each panorama
+        #[arg(long, default_value = "_equirect")]
+        suffix: String,
+    },
+    /// Generate tangent-space normal maps from height maps
+    HeightToNormal {
+        /// Height map files or directories
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        
+        /// Files in directories are only converted when their name matches this regex
+        #[arg(long, value_name = "REGEX", default_value = "/height|disp|bump/i")]
+        file_regex: String,
+        
+        /// Source channel holding the height (0 = R, 1 = G, 2 = B, 3 = A)
+        #[arg(long, default_value_t = 0)]
+        channel: usize,
+        
+        /// Scales the slopes, higher values give steeper normals
+        #[arg(long, default_value_t = 1.0)]
+        strength: f32,
+        
+        /// How pixels past the image edge are sampled
+        #[arg(long, value_enum, default_value_t = WrapMode::Clamp)]
+        wrap: WrapMode,
+        
+        /// Write Y pointing down (DirectX) instead of up (OpenGL)
+        #[arg(long)]
+        directx: bool,
+        
+        /// Appended to the file stem of each generated normal map
+        #[arg(long, default_value = "_normal")]
+        suffix: String,
+    },
+    
+    /// Write synthetic packed textures to try profiles on or benchmark with
+    GenerateTest {
+        /// Directory to write the test images to
+        #[arg(default_value = "test-textures")]
+        output: PathBuf,
+        
+        /// Sizes to generate, square like 1024 or width x height like 1000x600
+        #[arg(long, value_delimiter = ',', value_parser = generate::parse_size, default_value = "256,1024")]
+        sizes: Vec<(u32, u32)>,
+        
+        /// Bit depths to generate, 8 and 16 bit as PNG, 32f as EXR
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "8,16")]
+        depths: Vec<BitDepth>,
+        
+        /// Add an alpha channel with its own pattern
+        #[arg(long)]
+        alpha: bool,
+        
+        /// Seed of the noise pattern
+        #[arg(long, default_value_t = 1)]
+        seed: u32,
+    },
+    
+    /// Print a completion script for bash, zsh, fish or PowerShell
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    
+    /// Check the config, every profile, available formats and CPU features
+    Doctor,
+    
+    /// Print the CLI reference as a man page or Markdown
+    #[command(hide = true)]
+    Docs {
+        #[arg(value_enum)]
+        format: DocsFormat,
+    },
+    
+    /// Time the decode, extract and encode stages of the profile at different thread counts
+    Bench {
+        /// Files or directories to process (default: a generated set of test images)
+        inputs: Vec<PathBuf>,
+        
+        /// How many times the inputs are processed per thread count
+        #[arg(long, default_value_t = 3)]
+        iterations: usize,
+        
+        /// Thread counts to compare (default: 1 and all cores)
+        #[arg(long, value_delimiter = ',')]
+        threads: Vec<usize>,
+    },
+    
+    /// Download the latest release for this platform, check its SHA-256 against corruption and replace the executable
+    SelfUpdate {
+        /// Only report whether a newer release is available
+        #[arg(long)]
+        check: bool,
+        
+        /// Reinstall the latest release even if it isn't newer
+        #[arg(long)]
+        force: bool,
+    },
+    
+    /// Serve an HTTP API to submit images, poll jobs and download the split channels
+    Serve {
+        /// Address to listen on, use 0.0.0.0 to accept connections from other machines
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: String,
+        
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+        
+        /// Jobs processed at the same time (default: all cores)
+        #[arg(long)]
+        workers: Option<usize>,
+        
+        /// Largest accepted request body in MiB
+        #[arg(long, default_value_t = 512)]
+        max_upload_mb: u64,
+        
+        /// Folder whose images jobs may name with ?path= instead of uploading them (default: ?path= is refused)
+        #[arg(long, value_name = "DIR")]
+        path_root: Option<PathBuf>,
+    },
+    
+    /// Watch the configured hot folders and process queued jobs until stopped
+    Daemon {
+        #[arg(value_enum, default_value = "run")]
+        action: DaemonAction,
+        
+        /// Files to queue with submit
+        inputs: Vec<PathBuf>,
+    },
+    
+    /// Write a Substance 3D Painter export preset that packs maps the way the profile splits them
+    SubstancePreset {
+        /// Preset file to write (default: <profile>.spexp)
+        output: Option<PathBuf>,
+        
+        /// Name of the exported texture, with Painter's variables
+        #[arg(long, default_value = "$textureSet_ORM")]
+        file_name: String,
+        
+        /// File format Painter exports the packed texture in
+        #[arg(long, default_value = "png")]
+        format: String,
+    },
+    
+    /// Add or remove the right-click menu entry in Explorer (Windows) or Finder (macOS)
+    ShellIntegration {
+        #[arg(value_enum)]
+        action: IntegrationAction,
+    },
+    
+    /// Manage the profiles in config.toml
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
+}
+
+// Display help information when no arguments are provided
+fn display_help() {
+    println!("ORM Separator V3");
+    println!("=================");
+    println!("Made with ❤️ by Darwin");
+    println!();
+    // Usage, commands and options come from the clap definitions so they can't drift from the real flags
+    let _ = Cli::command().print_help();
+    println!();
+    println!("Examples:");
+    let width = docs::EXAMPLES.iter().map(|(command, _)| command.len()).max().unwrap_or(0);
+    for (command, description) in docs::EXAMPLES {
+        println!("  {:<width$}  # {}", command, description);
+    }
+    println!();
+    println!("You can also drag and drop files or folders onto the executable.");
+}
+
+// Wait for a keypress from the user
+fn wait_for_keypress() -> Result<()> {
+    println!("\nPress any key to continue...");
+    io::stdout().flush().context("Failed to flush stdout")?;
+    
+    // Read a single byte from stdin
+    let mut buffer = [0; 1];
+    io::stdin().read(&mut buffer).context("Failed to read user input")?;
+    
+    Ok(())
+}
+
+// Parse regex in /pattern/args format
+// Inside the delimiters a literal slash is written as \/
+fn parse_regex_format(regex_str: &str) -> Result<(String, String)> {
+    // Check if the string follows the /pattern/args format
+    if let Some(rest) = regex_str.strip_prefix('/') {
+        let mut pattern = String::with_capacity(rest.len());
+        let mut chars = rest.char_indices();
+        
+        while let Some((pos, c)) = chars.next() {
+            match c {
+                // Closing delimiter, everything after it is flags
+                '/' => return Ok((pattern, rest[pos + 1..].to_string())),
+                '\\' => match chars.next() {
+                    Some((_, '/')) => pattern.push('/'),
+                    Some((_, escaped)) => {
+                        pattern.push('\\');
+                        pattern.push(escaped);
+                    }
+                    None => pattern.push('\\'),
+                },
+                _ => pattern.push(c),
+            }
+        }
+    }
+    
+    // If the string doesn't follow the /pattern/args format, treat it as a regular regex pattern
+    Ok((regex_str.to_string(), String::new()))
+}
+
+// Compile a regex string, honoring the /pattern/args format
+fn compile_regex(regex_str: &str) -> Result<Regex> {
+    let (pattern, flags) = parse_regex_format(regex_str)?;
+    
+    // Create regex with appropriate options
+    let mut builder = RegexBuilder::new(&pattern);
+    for flag in flags.chars() {
+        match flag {
+            'i' => builder.case_insensitive(true),
+            'm' => builder.multi_line(true),
+            's' => builder.dot_matches_new_line(true),
+            'x' => builder.ignore_whitespace(true),
+            'U' => builder.swap_greed(true),
+            _ => return Err(anyhow!(
+                "Unknown regex flag '{}' in: {} (supported flags: i, m, s, x, U)",
+                flag, regex_str
+            )),
+        };
+    }
+    
+    builder.build()
+        .with_context(|| format!("Invalid regex pattern: {}", regex_str))
+}
+
+// Translate a glob such as **/*_ORM.{png,tga} into an anchored regex. Class contents are taken
+// literally apart from ranges, and braces can't be nested
+fn glob_to_regex(glob: &str) -> Result<Regex> {
+    let mut pattern = String::from("^");
+    let mut chars = glob.chars().peekable();
+    let mut in_braces = false;
+    
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    // **/ matches zero or more whole directories
+                    chars.next();
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            '[' => {
+                pattern.push('[');
+                if matches!(chars.peek(), Some('!') | Some('^')) {
+                    chars.next();
+                    pattern.push('^');
+                }
+                // A ] right after the opening bracket is a literal, like in shells
+                let mut members = Vec::new();
+                if chars.peek() == Some(&']') {
+                    members.push(chars.next().unwrap());
+                }
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(c) => members.push(c),
+                        None => return Err(anyhow!("Unclosed '[' in glob pattern: {}", glob)),
+                    }
+                }
+                if members.is_empty() {
+                    return Err(anyhow!("Empty '[]' in glob pattern: {}", glob));
+                }
+                // Everything is escaped, so &&, ~~ and ^ stay literal. A - between two members is a
+                // range, anywhere else a literal
+                for (index, &member) in members.iter().enumerate() {
+                    if member == '-' && index > 0 && index + 1 < members.len() && members[index - 1] != '-' {
+                        pattern.push('-');
+                    } else {
+                        pattern.push_str(&regex::escape(&member.to_string()));
+                    }
+                }
+                pattern.push(']');
+            }
+            '{' if in_braces => return Err(anyhow!("Nested '{{' in glob pattern, list every alternative at one level: {}", glob)),
+            '{' => {
+                in_braces = true;
+                pattern.push_str("(?:");
+            }
+            ',' if in_braces => pattern.push('|'),
+            '}' if in_braces => {
+                in_braces = false;
+                pattern.push(')');
+            }
+            // Accept Windows separators in patterns
+            '\\' => pattern.push('/'),
+            _ => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    
+    if in_braces {
+        return Err(anyhow!("Unclosed '{{' in glob pattern: {}", glob));
+    }
+    pattern.push('$');
+    
+    Regex::new(&pattern)
+        .with_context(|| format!("Invalid glob pattern: {}", glob))
+}
+
+// Whether file_regex or file_glob mentions a folder, which never matches a file name alone. A
+// leading **/ matches no folder too, so globs like **/*_ORM.png are fine
+fn pattern_names_folders(profile: &Profile) -> bool {
+    if let Some(glob) = &profile.file_glob {
+        let mut rest = glob.as_str();
+        while let Some(stripped) = rest.strip_prefix("**/").or_else(|| rest.strip_prefix("**\\")) {
+            rest = stripped;
+        }
+        return rest.contains(['/', '\\']);
+    }
+    let Some(regex) = &profile.file_regex else {
+        return false;
+    };
+    let pattern = regex.strip_prefix('/').and_then(|rest| rest.rsplit_once('/')).map_or(regex.as_str(), |(pattern, _)| pattern);
+    pattern.contains('/') || pattern.contains("\\\\")
+}
+
+// Compiled file pattern of a profile
+#[derive(Clone)]
+struct FileMatcher {
+    regex: Regex,
+    is_glob: bool,
+    match_on: MatchOn,
+    excludes: Vec<Regex>,
+    extensions: Vec<String>,
+    // Recognizes files this profile generated itself so they are never re-split
+    outputs: Regex,
+    contact_sheet: Option<Regex>,
+    strip_suffix: Vec<Regex>,
+    // Materials textures of scene inputs belong to, their outputs are named after them
+    materials: HashMap<PathBuf, String>,
+}
+
+impl FileMatcher {
+    fn new(profile: &Profile) -> Result<Self> {
+        let (regex, is_glob) = match (&profile.file_regex, &profile.file_glob) {
+            (Some(_), Some(_)) => return Err(anyhow!(
+                "Profile '{}' sets both file_regex and file_glob, use only one", profile.name
+            )),
+            (Some(regex), None) => (compile_regex(regex)?, false),
+            (None, Some(glob)) => (glob_to_regex(glob)?, true),
+            (None, None) => return Err(anyhow!(
+                "Profile '{}' needs either file_regex or file_glob", profile.name
+            )),
+        };
+        
+        let excludes = profile.exclude_regex.iter()
+            .map(|exclude| compile_regex(exclude))
+            .collect::<Result<Vec<_>>>()?;
+        
+        let extensions = profile.allowed_extensions();
+        
+        let strip_suffix = profile.strip_suffix.iter()
+            .map(|suffix| if suffix.starts_with('/') {
+                compile_regex(suffix)
+            } else {
+                Ok(Regex::new(&format!("{}$", regex::escape(suffix)))?)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        
+        Ok(FileMatcher {
+            regex,
+            is_glob,
+            match_on: profile.match_on,
+            excludes,
+            extensions,
+            outputs: output_name_regex(profile)?,
+            contact_sheet: profile.contact_sheet.as_deref().map(contact_sheet_regex).transpose()?,
+            strip_suffix,
+            materials: HashMap::new(),
+        })
+    }
+    
+    // The {stem} of a file: the material using it for textures of scene inputs, otherwise its
+    // file stem without the first packing suffix that matches. A stem that is nothing but the
+    // suffix is kept
+    fn stem(&self, path: &Path) -> String {
+        if let Some(material) = self.materials.get(path) {
+            return material.clone();
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        for suffix in &self.strip_suffix {
+            if let Some(found) = suffix.find_iter(&stem).last() {
+                if found.start() > 0 {
+                    return format!("{}{}", &stem[..found.start()], &stem[found.end()..]);
+                }
+                break;
+            }
+        }
+        stem.into_owned()
+    }
+    
+    // The string the pattern is matched against for a file found under root
+    fn haystack(&self, path: &Path, root: &Path) -> String {
+        match self.match_on {
+            MatchOn::Filename => path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            MatchOn::Path if self.is_glob => relative_path(path, root),
+            MatchOn::Path => path.to_string_lossy().into_owned(),
+        }
+    }
+    
+    fn is_match(&self, path: &Path, root: &Path) -> bool {
+        self.regex.is_match(&self.haystack(path, root))
+    }
+    
+    fn has_allowed_extension(&self, path: &Path) -> bool {
+        path.extension()
+            .map(|ext| {
+                let ext = ext.to_string_lossy().to_ascii_lowercase();
+                self.extensions.contains(&ext)
+            })
+            .unwrap_or(false)
+    }
+    
+    fn is_excluded(&self, path: &Path, root: &Path) -> bool {
+        let relative = relative_path(path, root);
+        self.excludes.iter().any(|exclude| exclude.is_match(&relative))
+    }
+    
+    fn is_generated_output(&self, path: &Path) -> bool {
+        path.file_name()
+            .map(|name| {
+                let name = name.to_string_lossy();
+                self.outputs.is_match(&name) || self.contact_sheet.as_ref().is_some_and(|sheet| sheet.is_match(&name))
+            })
+            .unwrap_or(false)
+    }
+}
+
+// Dot files, plus files with the hidden attribute on Windows
+fn is_hidden(entry: &walkdir::DirEntry) -> bool {
+    if entry.file_name().to_string_lossy().starts_with('.') {
+        return true;
+    }
+    
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        if let Ok(metadata) = entry.metadata() {
+            return metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0;
+        }
+    }
+    
+    false
+}
+
+// Path of a file relative to root, always with forward slashes
+fn relative_path(path: &Path, root: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+// Build a regex matching the file names that output_name renders for this profile
+fn output_name_regex(profile: &Profile) -> Result<Regex> {
+    // Only the file name part matters, templates may contain directories
+    let template = profile.output_name.rsplit(['/', '\\']).next().unwrap_or("");
+    let mut channels: Vec<String> = profile.channels.iter()
+        .map(|c| regex::escape(&c.name))
+        .collect();
+    if is_layered_format(&profile.output_format) {
+        channels.push(LAYERED_CHANNEL_NAME.to_string());
+    }
+    if profile.atlas_only {
+        channels.push(ATLAS_CHANNEL_NAME.to_string());
+    }
+    let channels = channels.join("|");
+    
+    let mut pattern = String::from("^");
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        pattern.push_str(&regex::escape(&rest[..start]));
+        let end = rest[start..].find('}')
+            .ok_or_else(|| anyhow!("Unclosed '{{' in output name template: {}", profile.output_name))?;
+        match &rest[start + 1..start + end] {
+            "channel" => pattern.push_str(&format!("(?:{})", channels)),
+            _ => pattern.push_str(".*"),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    pattern.push_str(&regex::escape(rest));
+    // Optional page suffix of multi-page inputs, then any extension this profile writes
+    let mut extensions = vec![regex::escape(output_extension(&profile.output_format))];
+    for channel in &profile.channels {
+        extensions.push(regex::escape(output_extension(channel.format(profile))));
+    }
+    // Histograms are recognized whether or not they are enabled now
+    let cut = profile.atlas.is_some() || profile.sprite_frames.is_some() || profile.cubemap.is_some();
+    let tile = if cut && !template.contains("{tile}") { "(?:_[^.]+)?" } else { "" };
+    pattern.push_str(&format!("(?:_p\\d+)?{}(?:\\.(?:{})|_histogram\\.(?:png|csv))$", tile, extensions.join("|")));
+    
+    RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .with_context(|| format!("Invalid output name template: {}", profile.output_name))
+}
+
+// File names a contact_sheet path produces, with any template variable matching anything
+fn contact_sheet_regex(template: &str) -> Result<Regex> {
+    let name = template.rsplit(['/', '\\']).next().unwrap_or("");
+    let mut pattern = String::from("^");
+    for (index, part) in name.split(['{', '}']).enumerate() {
+        // Odd parts sit between braces
+        pattern.push_str(&if index % 2 == 1 { ".*".to_string() } else { regex::escape(part) });
+    }
+    pattern.push('$');
+    
+    RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .with_context(|| format!("Invalid contact sheet path: {}", template))
+}
+
+// Collect named capture groups of the file regex for use in output names
+fn named_captures(regex: &Regex, haystack: &str) -> HashMap<String, String> {
+    let mut vars = HashMap::new();
+    
+    if let Some(caps) = regex.captures(haystack) {
+        for name in regex.capture_names().flatten() {
+            let value = caps.name(name).map(|m| m.as_str()).unwrap_or("");
+            vars.insert(name.to_string(), value.to_string());
+        }
+    }
+    
+    vars
+}
+
+// Expand {placeholder} tokens in an output name template
+fn render_output_name(template: &str, vars: &HashMap<String, String>) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let end = rest[start..].find('}')
+            .ok_or_else(|| anyhow!("Unclosed '{{' in output name template: {}", template))?;
+        let key = &rest[start + 1..start + end];
+        let value = vars.get(key)
+            .ok_or_else(|| anyhow!("Unknown placeholder '{{{}}}' in output name template: {}", key, template))?;
+        output.push_str(value);
+        rest = &rest[start + end + 1..];
+    }
+    output.push_str(rest);
+    
+    Ok(output)
+}
+
+// Prompt user to select a profile when multiple profiles exist
+fn prompt_profile_selection(config: &Config) -> Result<String> {
+    println!("\nMultiple profiles available. Please select a profile to use:");
+    
+    // Display available profiles
+    let mut profile_names: Vec<String> = config.profiles.keys().cloned().collect();
+    profile_names.sort(); // Sort alphabetically for consistent display
+    
+    for (i, name) in profile_names.iter().enumerate() {
+        let is_default = if name == &config.default_profile { " (default)" } else { "" };
+        println!("  {}. {}{}", i + 1, name, is_default);
+    }
+    
+    // Prompt for selection
+    print!("\nEnter profile number or name (default is {}): ", config.default_profile);
+    io::stdout().flush().context("Failed to flush stdout")?;
+    
+    // Read user input
+    let mut input = String::new();
+    io::stdin().lock().read_line(&mut input).context("Failed to read user input")?;
+    let input = input.trim();
+    
+    // If empty input, use default
+    if input.is_empty() {
+        return Ok(config.default_profile.clone());
+    }
+    
+    // Try to parse as number
+    if let Ok(num) = input.parse::<usize>() {
+        if num > 0 && num <= profile_names.len() {
+            return Ok(profile_names[num - 1].clone());
+        } else {
+            println!("Invalid profile number. Using default profile: {}", config.default_profile);
+            return Ok(config.default_profile.clone());
+        }
+    }
+    
+    // Try as profile name
+    if config.profiles.contains_key(input) {
+        return Ok(input.to_string());
+    }
+    
+    // If not found, use default
+    println!("Profile '{}' not found. Using default profile: {}", input, config.default_profile);
+    Ok(config.default_profile.clone())
+}
+
+// Entry point of the ormseparatev3 executable
+pub fn cli_main() -> Result<()> {
+    // Parse CLI arguments first
+    let mut cli = Cli::parse();
+    
+    // Completion scripts don't depend on the config, so they work even when it's broken
+    if let Some(Commands::Completions { shell }) = &cli.command {
+        print!("{}", completions::completions(*shell));
+        return Ok(());
+    }
+    if let Some(Commands::Docs { format }) = &cli.command {
+        print!("{}", docs::render(*format));
+        return Ok(());
+    }
+    if let Some(Commands::SelfUpdate { check, force }) = &cli.command {
+        return selfupdate::self_update(*check, *force);
+    }
+    
+    // Get the directory where the executable is located
+    let exe_path = std::env::current_exe()
+        .with_context(|| "Failed to get executable path")?;
+    let exe_dir = exe_path.parent()
+        .unwrap_or_else(|| Path::new("."));
+    
+    // Store config file in the same directory as the binary
+    let config_path = exe_dir.join("config.toml");
+    
+    // Create parent directories if they don't exist
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create config directory: {}", parent.display()))?;
+    }
+    
+    if let Some(Commands::Doctor) = &cli.command {
+        return doctor::doctor(exe_dir, &config_path);
+    }
+    
+    // Load or create config - this will create the config file if it doesn't exist
+    let config = Config::load(&config_path)?;
+    
+    // Handle subcommands
+    if let Some(Commands::ListProfiles { names, format }) = cli.command {
+        if format == InfoFormat::Json {
+            println!("{}", profiles::list_json(&config));
+            return Ok(());
+        }
+        if names {
+            let mut names: Vec<&String> = config.profiles.keys().collect();
+            names.sort();
+            names.iter().for_each(|name| println!("{}", name));
+            return Ok(());
+        }
+        println!("Available profiles:");
+        for (name, profile) in &config.profiles {
+            match (&profile.file_regex, &profile.file_glob) {
+                (Some(regex), _) => println!("  {} - Regex: '{}', Format: '{}'", name, regex, profile.output_format),
+                (None, Some(glob)) => println!("  {} - Glob: '{}', Format: '{}'", name, glob, profile.output_format),
+                (None, None) => println!("  {} - Format: '{}'", name, profile.output_format),
+            }
+            println!("    Channels:");
+            for channel in &profile.channels {
+                match &channel.expr {
+                    Some(expr) => println!("      {} (Expression: {})", channel.name, expr),
+                    None if channel.reconstruct_z => println!("      {} (Normal Z)", channel.name),
+                    None => println!("      {} (Channel: {})", channel.name, channel.channel),
+                }
+            }
+        }
+        println!("\nDefault profile: {}", config.default_profile);
+        return Ok(());
+    }
+    if let Some(Commands::Verify { file, tolerance }) = &cli.command {
+        let mut profile = config.get_profile(cli.profile.as_deref())?;
+        load_channel_resources(&mut profile, exe_dir)?;
+        validate_profile(&profile)?;
+        return verify::verify(file, &profile, *tolerance);
+    }
+    if let Some(Commands::Diff { first, second, diff_image, gain }) = &cli.command {
+        let profile = config.get_profile(cli.profile.as_deref())?;
+        return compare::diff(first, second, diff_image.as_deref(), *gain, &profile);
+    }
+    if let Some(Commands::Inspect { file, format }) = &cli.command {
+        let profile = config.get_profile(cli.profile.as_deref())?;
+        return inspect::inspect_file(file, &profile, *format);
+    }
+    if let Some(Commands::Detect { file }) = &cli.command {
+        let profile = config.get_profile(cli.profile.as_deref())?;
+        return inspect::detect(file, &profile, &config.profiles);
+    }
+    if let Some(Commands::Scan { scene, process }) = &cli.command {
+        let (scene, process) = (scene.clone(), *process);
+        let profile = config.get_profile(cli.profile.as_deref())?;
+        inspect::scan(&scene, &profile)?;
+        if !process {
+            return Ok(());
+        }
+        println!();
+        cli.inputs = vec![scene];
+    }
+    if let Some(Commands::Pack { inputs, mismatch, size, filter, gltf }) = &cli.command {
+        let mut profile = config.get_profile(cli.profile.as_deref())?;
+        if *gltf {
+            profile.pack = Some(pack::gltf_table(profile.pack.as_ref()));
+        }
+        if let Some(pack) = &mut profile.pack {
+            pack.mismatch = mismatch.unwrap_or(pack.mismatch);
+            if let Some((width, height)) = size {
+                pack.size = Some([*width, *height]);
+            }
+            pack.filter = filter.unwrap_or(pack.filter);
+        }
+        load_channel_resources(&mut profile, exe_dir)?;
+        validate_profile(&profile)?;
+        return pack::pack(inputs, &profile, *gltf);
+    }
+    if let Some(Commands::InspectSets { dir }) = &cli.command {
+        let profile = config.get_profile(cli.profile.as_deref())?;
+        return inspect::inspect_sets(dir, &profile);
+    }
+    if let Some(Commands::EquirectToCubemap { inputs, file_regex, size, layout, faces, suffix }) = &cli.command {
+        let profile = config.get_profile(cli.profile.as_deref())?;
+        return equirect_to_cubemap(inputs, &compile_regex(file_regex)?, suffix, *size, *layout, *faces, &profile);
+    }
+    if let Some(Commands::CubemapToEquirect { inputs, file_regex, layout, width, suffix }) = &cli.command {
+        let profile = config.get_profile(cli.profile.as_deref())?;
+        return cubemap_to_equirect(inputs, &compile_regex(file_regex)?, suffix, *layout, *width, &profile);
+    }
+    if let Some(Commands::NormalFlip { inputs, file_regex, suffix }) = &cli.command {
+        let profile = config.get_profile(cli.profile.as_deref())?;
+        return normal_flip(inputs, &compile_regex(file_regex)?, suffix, &profile);
+    }
+    if let Some(Commands::HeightToNormal { inputs, file_regex, channel, strength, wrap, directx, suffix }) = &cli.command {
+        let profile = config.get_profile(cli.profile.as_deref())?;
+        let settings = HeightToNormal { strength: *strength, wrap: *wrap, directx: *directx };
+        return height_to_normal(inputs, &compile_regex(file_regex)?, suffix, *channel, &settings, &profile);
+    }
+    
+    if let Some(Commands::GenerateTest { output, sizes, depths, alpha, seed }) = &cli.command {
+        let profile = config.get_profile(cli.profile.as_deref())?;
+        return generate::generate_test(output, sizes, depths, *alpha, *seed, &profile);
+    }
+    
+    if let Some(Commands::Serve { bind, port, workers, max_upload_mb, path_root }) = &cli.command {
+        return serve::serve(bind, *port, *workers, *max_upload_mb, path_root.as_deref(), &config, exe_dir);
+    }
+    
+    if let Some(Commands::Daemon { action, inputs }) = &cli.command {
+        return daemon::daemon(*action, inputs, cli.profile.as_deref(), &config, exe_dir);
+    }
+    
+    if let Some(Commands::SubstancePreset { output, file_name, format }) = &cli.command {
+        let profile = config.get_profile(cli.profile.as_deref())?;
+        let output = output.clone().unwrap_or_else(|| PathBuf::from(format!("{}.spexp", profile.name)));
+        return substance::substance_preset(&profile, &output, file_name, format);
+    }
+    
+    if let Some(Commands::ShellIntegration { action }) = &cli.command {
+        return integration::shell_integration(*action, &exe_path, &config);
+    }
+    
+    if let Some(Commands::Profile { action }) = &cli.command {
+        return profiles::profile(action, &config, &config_path, exe_dir);
+    }
+    
+    if let Some(Commands::Bench { inputs, iterations, threads }) = &cli.command {
+        let mut profile = config.get_profile(cli.profile.as_deref())?;
+        load_channel_resources(&mut profile, exe_dir)?;
+        validate_profile(&profile)?;
+        return bench::bench(inputs, *iterations, threads, &profile);
+    }
+    
+    // Display help if no inputs were provided
+    if cli.inputs.is_empty() {
+        display_help();
+        // Wait for keypress before exiting when showing help due to no arguments
+        wait_for_keypress()?;
+        return Ok(());
+    }
+    
+    if cli.detect {
+        let profile = config.get_profile(cli.profile.as_deref())?;
+        return inspect::detect_batch(&cli.inputs, &profile, &config.profiles);
+    }
+    
+    // Dropped files can't answer a prompt, and the window closes as soon as the run ends
+    let dropped = is_drag_and_drop();
+    let profile_name = if dropped {
+        config.drag_drop.profile.clone()
+    } else if cli.profile.is_none() && config.profiles.len() > 1 {
+        // If no profile specified and multiple profiles exist, prompt for selection
+        let selected_profile = prompt_profile_selection(&config)?;
+        Some(selected_profile)
+    } else {
+        cli.profile.clone()
+    };
+    
+    // Ctrl-C stops scheduling files, what was done so far is summarized
+    let cancel = CancelToken::new();
+    cancel::cancel_on_ctrl_c(&cancel);
+    
+    let started = Instant::now();
+    let mut summaries = Vec::new();
+    let result = run(cli, &config, profile_name.as_deref(), exe_dir, &mut summaries, &cancel);
+    if cancel.is_cancelled() {
+        print_run_summary("Cancelled after", started, &summaries);
+        std::process::exit(130);
+    }
+    if !dropped {
+        return result;
+    }
+    
+    print_run_summary("Done in", started, &summaries);
+    if let Err(err) = &result {
+        println!("Error: {:#}", err);
+    }
+    let pause = match config.drag_drop.pause {
+        PauseMode::Always => true,
+        PauseMode::OnError => result.is_err(),
+        PauseMode::Never => false,
+    };
+    if pause {
+        wait_for_keypress()?;
+    }
+    if result.is_err() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn print_run_summary(outcome: &str, started: Instant, summaries: &[FileSummary]) {
+    let count = |status: fn(&FileSummary) -> bool| summaries.iter().filter(|summary| status(summary)).count();
+    println!();
+    println!("{} {:.1}s: {} split, {} skipped, {} failed",
+        outcome,
+        started.elapsed().as_secs_f64(),
+        count(|summary| summary.error.is_none() && summary.skipped.is_none()),
+        count(|summary| summary.skipped.is_some()),
+        count(|summary| summary.error.is_some()));
+}
+
+// Explorer and Finder pass only the absolute paths of the dropped items, typed command lines
+// almost always have a relative path or an option in them. Scripts and CI runs naming absolute
+// paths are told apart by their console
+fn is_drag_and_drop() -> bool {
+    let args: Vec<_> = std::env::args_os().skip(1).collect();
+    !args.is_empty() && args.iter().all(|arg| Path::new(arg).is_absolute() && Path::new(arg).exists()) && was_dropped()
+}
+
+// Set by the Finder Quick Action, the only way to drop onto the executable outside Windows
+pub(crate) const DROPPED_ENV: &str = "ORMSEPARATEV3_DROPPED";
+
+// A console Windows opened for this process alone, not the one of a shell it was started from
+#[cfg(windows)]
+fn was_dropped() -> bool {
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetConsoleProcessList(processes: *mut u32, count: u32) -> u32;
+    }
+    
+    let mut processes = [0u32; 2];
+    unsafe { GetConsoleProcessList(processes.as_mut_ptr(), processes.len() as u32) == 1 }
+}
+
+// Terminals can't tell a typed absolute path from a drop, so only the Quick Action counts
+#[cfg(not(windows))]
+fn was_dropped() -> bool {
+    std::env::var_os(DROPPED_ENV).is_some()
+}
+
+// Apply the command line overrides to the profile and process every input
+fn run(cli: Cli, config: &Config, profile_name: Option<&str>, exe_dir: &Path, summaries: &mut Vec<FileSummary>, cancel: &CancelToken) -> Result<()> {
+    let mut profile = config.get_profile(profile_name)?;
+    profile.exclude_regex.extend(cli.exclude);
+    if cli.max_depth.is_some() {
+        profile.max_depth = cli.max_depth;
+    }
+    profile.follow_symlinks |= cli.follow_symlinks;
+    profile.include_hidden |= cli.hidden;
+    if let Some(layer) = &cli.psd_layer {
+        profile.psd_layer = Some(LayerSelector::parse(layer));
+    }
+    if cli.ignore_orientation {
+        profile.apply_orientation = false;
+    }
+    if let Some((columns, rows)) = cli.atlas {
+        profile.atlas = Some([columns, rows]);
+    }
+    if !cli.atlas_names.is_empty() {
+        profile.atlas_names = cli.atlas_names;
+    }
+    if cli.sprite_frames.is_some() {
+        profile.sprite_frames = cli.sprite_frames;
+    }
+    if cli.cubemap.is_some() {
+        profile.cubemap = cli.cubemap;
+    }
+    profile.atlas_only |= cli.atlas_only;
+    if cli.histogram.is_some() {
+        profile.histogram = cli.histogram;
+    }
+    if cli.provenance.is_some() {
+        profile.provenance = cli.provenance;
+    }
+    if cli.contact_sheet.is_some() {
+        profile.contact_sheet = cli.contact_sheet;
+    }
+    if cli.material_json.is_some() {
+        profile.material_json = cli.material_json;
+    }
+    if cli.materialx.is_some() {
+        profile.materialx = cli.materialx;
+    }
+    if cli.usd.is_some() {
+        profile.usd = cli.usd;
+    }
+    if cli.blender_script.is_some() {
+        profile.blender_script = cli.blender_script;
+    }
+    if cli.stats_csv.is_some() {
+        profile.stats_csv = cli.stats_csv;
+    }
+    if cli.report.is_some() {
+        profile.report = cli.report;
+    }
+    if let Some(path) = cli.report_path {
+        profile.report_path = path;
+    }
+    if cli.backup_dir.is_some() {
+        profile.backup_dir = cli.backup_dir;
+    }
+    if cli.output_dir.is_some() {
+        profile.output_dir = cli.output_dir;
+    }
+    if cli.mirror_structure {
+        profile.output_layout = OutputLayout::Mirror;
+    }
+    if cli.flatten {
+        profile.output_layout = OutputLayout::Flatten;
+    }
+    if let Some(after) = cli.after_source {
+        profile.after_source = after;
+    }
+    if cli.max_inflight_memory.is_some() {
+        profile.max_inflight_memory_mb = cli.max_inflight_memory;
+    }
+    
+    load_channel_resources(&mut profile, exe_dir)?;
+    
+    println!("Using profile: {}", profile.name);
+    
+    validate_profile(&profile)?;
+    
+    let jpeg = |format: &str| matches!(format.to_ascii_lowercase().as_str(), "jpg" | "jpeg");
+    if profile.channels.iter().any(|channel| jpeg(channel.format(&profile))) {
+        println!("Warning: JPEG is lossy, compression artifacts will alter data maps like roughness or metallic");
+    }
+    if profile.match_on == MatchOn::Filename && pattern_names_folders(&profile) {
+        println!("Warning: the file pattern of profile '{}' contains a folder separator, but only file names are matched. Set match_on = \"path\" to match folders", profile.name);
+    }
+    
+    // Process inputs in a pool of their own size, run-wide outputs are written even when a file failed
+    let threads = cli.threads.or(config.threads);
+    if threads == Some(0) {
+        return Err(anyhow!("threads must be at least 1"));
+    }
+    check_output_collisions(&cli.inputs, &profile)?;
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads.unwrap_or(0)).build()?;
+    let result = pool.install(|| cli.inputs.iter().try_for_each(|input| process_input(input, &profile, summaries, &Console, cancel)));
+    if cli.verbose {
+        report::print_timings(summaries);
+    }
+    
+    if let Some(path) = &profile.contact_sheet && !path.contains('{') {
+        write_contact_sheet(Path::new(path), summaries, &profile)?;
+    }
+    if let Some(path) = &profile.stats_csv {
+        report::write_stats_csv(path, summaries)?;
+        println!("Saved stats to: {}", path.display());
+    }
+    if profile.report == Some(ReportFormat::Html) {
+        html::write_report(&profile.report_path, summaries, &profile.name)?;
+        println!("Saved report to: {}", profile.report_path.display());
+    }
+    
+    result
+}
+
+// Load LUTs and parse expressions of the profile's channels, relative paths start at exe_dir
+fn load_channel_resources(profile: &mut Profile, exe_dir: &Path) -> Result<()> {
+    for channel in &mut profile.channels {
+        if let Some(path) = &channel.lut {
+            channel.lut_curve = Some(Lut::load(&exe_dir.join(path))?);
+        }
+        if channel.reconstruct_z && channel.expr.is_some() {
+            return Err(anyhow!("Channel {} sets both reconstruct_z and expr", channel.name));
+        }
+        let expr = if channel.reconstruct_z { Some(RECONSTRUCT_Z_EXPR) } else { channel.expr.as_deref() };
+        if let Some(expr) = expr {
+            channel.expression = Some(Expression::parse(expr)
+                .with_context(|| format!("Invalid expr for channel {}", channel.name))?);
+        }
+    }
+    
+    Ok(())
+}
+
+// Every profile that loads and validates, for long-running modes that skip broken profiles
+// instead of failing every job that uses them
+fn usable_profiles(config: &Config, exe_dir: &Path) -> HashMap<String, Profile> {
+    let mut profiles = HashMap::new();
+    for (name, profile) in &config.profiles {
+        let mut profile = profile.clone();
+        let checked = load_channel_resources(&mut profile, exe_dir)
+            .and_then(|_| validate_profile(&profile))
+            .and_then(|_| FileMatcher::new(&profile).map(|_| ()));
+        match checked {
+            Ok(()) => {
+                profiles.insert(name.clone(), profile);
+            }
+            Err(err) => println!("Warning: profile '{}' is unavailable: {:#}", name, err),
+        }
+    }
+    profiles
+}
+
+// Reject channel settings that contradict each other or the profile's output format
+fn validate_profile(profile: &Profile) -> Result<()> {
+    let cuts = [profile.atlas.is_some(), profile.sprite_frames.is_some(), profile.cubemap.is_some()];
+    if cuts.iter().filter(|set| **set).count() > 1 {
+        return Err(anyhow!("Only one of atlas, sprite_frames and cubemap can be set, sources are cut one way"));
+    }
+    if profile.atlas_only && !cuts.contains(&true) {
+        return Err(anyhow!("atlas_only is set without atlas, sprite_frames or cubemap"));
+    }
+    if profile.atlas_only && is_layered_format(&profile.output_format) && profile.output_format != "ktx2-array" {
+        return Err(anyhow!("atlas_only writes tiles in color, of the layered formats only ktx2-array can hold them"));
+    }
+    if let Some([columns, rows]) = profile.atlas {
+        if columns == 0 || rows == 0 {
+            return Err(anyhow!("atlas is {}x{}, it needs at least one column and row", columns, rows));
+        }
+        if profile.atlas_names.len() > (columns * rows) as usize {
+            return Err(anyhow!("atlas_names has {} names for {} tiles", profile.atlas_names.len(), columns * rows));
+        }
+        let names = atlas_tile_names(profile);
+        if let Some(name) = names.iter().find(|name| names.iter().filter(|other| other == name).count() > 1) {
+            return Err(anyhow!("Atlas tile name '{}' is used more than once", name));
+        }
+    }
+    if let Some(pack) = &profile.pack {
+        pack::validate(pack, profile)?;
+    }
+    let writes_webp = profile.channels.iter().any(|channel| channel.format(profile) == "webp");
+    if writes_webp && !profile.lossless {
+        return Err(anyhow!("lossless = false, but lossy WebP needs libwebp, which this build doesn't include. \
+            Set lossless = true, and quality below 100 for smaller near-lossless files"));
+    }
+    if let Some(quality) = profile.quality && quality > 100 {
+        return Err(anyhow!("quality is {}, it must be 0-100 (below 100 WebP output drops 1 to 5 low bits of each color value)", quality));
+    }
+    for channel in &profile.channels {
+        if channel.channel > 2 && channel.expression.is_none() {
+            return Err(anyhow!("Invalid channel index: {}", channel.channel));
+        }
+        if let Some(target_name) = &channel.into_alpha_of {
+            let target = profile.channels.iter()
+                .find(|other| &other.name == target_name && other.into_alpha_of.is_none())
+                .ok_or_else(|| anyhow!("Channel {} is routed into the alpha of '{}', which isn't a channel with its own output", channel.name, target_name))?;
+            if is_layered_format(target.format(profile)) {
+                return Err(anyhow!("Channel {} can't be routed into the alpha of {}, layered outputs have no alpha", channel.name, target_name));
+            }
+            if profile.channels.iter().filter(|other| other.into_alpha_of.as_ref() == Some(target_name)).count() > 1 {
+                return Err(anyhow!("More than one channel is routed into the alpha of {}", target_name));
+            }
+        }
+        if let Some(settings) = &channel.height_to_normal {
+            if is_layered_format(channel.format(profile)) {
+                return Err(anyhow!("Channel {} sets height_to_normal, but layered formats only store grayscale layers", channel.name));
+            }
+            if channel.into_alpha_of.is_some() {
+                return Err(anyhow!("Channel {} sets height_to_normal, so it can't be routed into an alpha", channel.name));
+            }
+            if !settings.strength.is_finite() {
+                return Err(anyhow!("Channel {} has height_to_normal strength {}, it must be a number", channel.name, settings.strength));
+            }
+        }
+        if let Some(format) = &channel.output_format && is_layered_format(format) {
+            return Err(anyhow!("Channel {} can't use layered format '{}', set it as the profile's output_format instead", channel.name, format));
+        }
+        if channel.format(profile) == "exr-multichannel" && channel.bit_depth.is_some_and(|depth| depth != BitDepth::Float) {
+            return Err(anyhow!("Channel {} sets bit_depth, but exr-multichannel always writes 32-bit float channels", channel.name));
+        } else if is_layered_format(channel.format(profile)) && channel.format(profile) != "exr-multichannel"
+            && channel.bit_depth.unwrap_or_default() != BitDepth::Eight {
+            return Err(anyhow!("Channel {} sets bit_depth, but layered formats other than exr-multichannel are always written with 8 bits", channel.name));
+        }
+        if let Some(gamma) = channel.gamma && !(gamma > 0.0 && gamma.is_finite()) {
+            return Err(anyhow!("Channel {} has gamma {}, it must be a positive number", channel.name, gamma));
+        }
+        if let Some(levels) = channel.levels && levels.in_high <= levels.in_low {
+            return Err(anyhow!("Channel {} has levels in_high {} at or below in_low {}", channel.name, levels.in_high, levels.in_low));
+        }
+        if channel.color_space == Some(ColorSpace::Srgb) && output_extension(channel.format(profile)) == "exr" {
+            return Err(anyhow!("Channel {} is tagged srgb, but EXR always stores linear values", channel.name));
+        }
+    }
+    
+    Ok(())
+}
+
+// Process a single input (file or directory)
+fn process_input(input: &Path, profile: &Profile, summaries: &mut Vec<FileSummary>, progress: &dyn ProgressSink, cancel: &CancelToken) -> Result<()> {
+    if cancel.is_cancelled() {
+        return Ok(());
+    }
+    let mut matcher = FileMatcher::new(profile)?;
+    
+    if input.is_dir() {
+        process_directory(input, profile, &matcher, summaries, progress, cancel)
+    } else if scene::is_scene(input) {
+        progress.log(&format!("Processing scene: {}", input.display()));
+        let mut log = Vec::new();
+        let files = scene_files(input, &mut matcher, &mut log)?;
+        log.iter().for_each(|line| progress.log(line));
+        progress.log(&format!("Found {} matching textures", files.len()));
+        process_files(&files, input.parent().unwrap_or(Path::new(".")), profile, &matcher, summaries, progress, cancel)
+    } else {
+        let root = input.parent().unwrap_or(Path::new("."));
+        let mut log = Vec::new();
+        let (summary, result) = summarized_process_file(input, root, profile, &matcher, &mut log, progress, (0, 1), cancel);
+        log.iter().for_each(|line| progress.log(line));
+        summaries.push(summary);
+        result
+    }
+}
+
+// Images under dir the profile would process, sorted so processing and log order don't depend on the filesystem
+fn matching_files(dir: &Path, profile: &Profile, matcher: &FileMatcher) -> Vec<PathBuf> {
+    let mut walker = WalkDir::new(dir).follow_links(profile.follow_symlinks);
+    if let Some(max_depth) = profile.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+    
+    // Backed up and moved sources inside the processed folder would be split again on the next
+    // run, and outputs written under it would be checked against the sources again
+    let mut kept_dirs = Vec::new();
+    kept_dirs.extend(profile.backup_dir.as_ref().and_then(|dir| fs::canonicalize(dir).ok()));
+    kept_dirs.extend(profile.output_dir.as_ref().and_then(|dir| fs::canonicalize(dir).ok()));
+    if let AfterSource::Move(dir) = &profile.after_source {
+        kept_dirs.extend(fs::canonicalize(dir).ok());
+    }
+    let is_kept_dir = |entry: &walkdir::DirEntry| {
+        entry.file_type().is_dir() && !kept_dirs.is_empty()
+            && fs::canonicalize(entry.path()).is_ok_and(|path| kept_dirs.contains(&path))
+    };
+    
+    let mut files: Vec<PathBuf> = walker
+        .into_iter()
+        // Prune excluded, hidden, backup and move directories instead of walking into them
+        .filter_entry(|entry| {
+            entry.depth() == 0 || (
+                (profile.include_hidden || !is_hidden(entry)) &&
+                !matcher.is_excluded(entry.path(), dir) &&
+                !is_kept_dir(entry)
+            )
+        })
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_type().is_file() && 
+            matcher.has_allowed_extension(entry.path()) &&
+            !matcher.is_generated_output(entry.path()) &&
+            matcher.is_match(entry.path(), dir)
+        })
+        .map(|entry| entry.path().to_path_buf())
+        .collect();
+    
+    files.sort();
+    files
+}
+
+// Fail before anything is written when two outputs of the run would land on the same path, or
+// an output on one of the inputs. Parallel runs would otherwise overwrite each other silently.
+// Page suffixes of multi-page files aren't planned, they only make names longer
+fn check_output_collisions(inputs: &[PathBuf], profile: &Profile) -> Result<()> {
+    let mut matcher = FileMatcher::new(profile)?;
+    let mut files: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            files.extend(matching_files(input, profile, &matcher).into_iter().map(|file| (file, input.clone())));
+        } else if scene::is_scene(input) {
+            let root = input.parent().unwrap_or(Path::new(".")).to_path_buf();
+            files.extend(scene_files(input, &mut matcher, &mut Vec::new())?.into_iter().map(|file| (file, root.clone())));
+        } else {
+            files.push((input.clone(), input.parent().unwrap_or(Path::new(".")).to_path_buf()));
+        }
+    }
+    files.sort();
+    files.dedup_by(|a, b| a.0 == b.0);
+    
+    // Default filesystems of Windows and macOS ignore case
+    let key = |path: &Path| {
+        let path = path.to_string_lossy().into_owned();
+        if cfg!(any(windows, target_os = "macos")) { path.to_lowercase() } else { path }
+    };
+    let mut claimed: HashMap<String, String> = files.iter()
+        .map(|(file, _)| (key(file), format!("the input {}", file.display())))
+        .collect();
+    let mut collisions = Vec::new();
+    let layered = profile.channels.iter().any(|channel| channel.into_alpha_of.is_none() && is_layered_format(channel.format(profile)));
+    
+    for (file, root) in &files {
+        let parent = &output_root(file, root, profile);
+        let mut vars = named_captures(&matcher.regex, &matcher.haystack(file, root));
+        vars.insert("stem".to_string(), matcher.stem(file));
+        if let Some(material) = matcher.materials.get(file) {
+            vars.insert("material".to_string(), material.clone());
+        }
+        
+        let mut outputs = Vec::new();
+        if profile.atlas_only {
+            outputs.push((ATLAS_CHANNEL_NAME, profile.output_format.as_str(), parent.to_path_buf()));
+        } else {
+            for channel in profile.channels.iter().filter(|channel| channel.into_alpha_of.is_none() && !is_layered_format(channel.format(profile))) {
+                outputs.push((channel.name.as_str(), channel.format(profile), channel.output_parent(parent)));
+            }
+            if layered {
+                outputs.push((LAYERED_CHANNEL_NAME, profile.output_format.as_str(), parent.to_path_buf()));
+            }
+        }
+        // Without an atlas, frames or cubemap the file is its only tile
+        // and tiles written into one array texture are a single output
+        let tiles = match sprite_frames(file, profile)? {
+            _ if profile.atlas_only && is_layered_format(&profile.output_format) => Vec::new(),
+            Some(frames) => frames.into_iter().map(|frame| frame.name).collect(),
+            None if profile.cubemap.is_some() => cubemap::FACES.map(str::to_string).to_vec(),
+            None => atlas_tile_names(profile),
+        };
+        let tiles: Vec<Option<&String>> = if tiles.is_empty() { vec![None] } else { tiles.iter().map(Some).collect() };
+        for tile in tiles {
+            let suffix = tile.map(|tile| tile_suffix(profile, tile)).unwrap_or_default();
+            if let Some(tile) = tile {
+                vars.insert("tile".to_string(), tile.clone());
+            }
+            for (channel, format, parent) in &outputs {
+                vars.insert("channel".to_string(), channel.to_string());
+                let path = output_file(parent, profile, &vars, &suffix, format)?;
+                let owner = match tile {
+                    Some(tile) => format!("channel {} of tile {} of {}", channel, tile, file.display()),
+                    None => format!("channel {} of {}", channel, file.display()),
+                };
+                if let Some(other) = claimed.get(&key(&path)) {
+                    collisions.push(format!("  {} and {} both use {}", other, owner, path.display()));
+                } else {
+                    claimed.insert(key(&path), owner);
+                }
+            }
+        }
+    }
+    
+    if collisions.is_empty() {
+        return Ok(());
+    }
+    const SHOWN: usize = 10;
+    let more = if collisions.len() > SHOWN { format!("\n  and {} more", collisions.len() - SHOWN) } else { String::new() };
+    collisions.truncate(SHOWN);
+    let hint = if profile.output_dir.is_some() && profile.output_layout == OutputLayout::Flatten {
+        ", rename the inputs or use --mirror-structure"
+    } else {
+        " or rename the inputs"
+    };
+    Err(anyhow!("Outputs would overwrite each other, change output_name{}:\n{}{}", hint, collisions.join("\n"), more))
+}
+
+// Process a directory recursively
+fn process_directory(dir: &Path, profile: &Profile, matcher: &FileMatcher, summaries: &mut Vec<FileSummary>, progress: &dyn ProgressSink,
+    cancel: &CancelToken) -> Result<()>
+{
+    progress.log(&format!("Processing directory: {}", dir.display()));
+    
+    let files = matching_files(dir, profile, matcher);
+    progress.log(&format!("Found {} matching files", files.len()));
+    process_files(&files, dir, profile, matcher, summaries, progress, cancel)
+}
+
+// Textures of a scene the profile would process, registering their materials with matcher.
+// Referenced textures that don't exist are noted in log
+fn scene_files(scene: &Path, matcher: &mut FileMatcher, log: &mut Vec<String>) -> Result<Vec<PathBuf>> {
+    let root = scene.parent().unwrap_or(Path::new("."));
+    let scene = scene::read(scene)?;
+    if scene.embedded > 0 {
+        log.push(format!("  Skipping {} images embedded in the scene", scene.embedded));
+    }
+    let mut files = Vec::new();
+    // A texture shared by materials is split once, named after the first. Textures no material
+    // uses keep their own name
+    for scene::SceneTexture { material, path: texture, .. } in scene.textures {
+        if files.contains(&texture) || !matcher.has_allowed_extension(&texture) || !matcher.is_match(&texture, root) {
+            continue;
+        }
+        if !texture.is_file() {
+            let used_by = if material.is_empty() { String::new() } else { format!(" of material {}", material) };
+            log.push(format!("  Texture {}{} doesn't exist", texture.display(), used_by));
+            continue;
+        }
+        if !material.is_empty() {
+            matcher.materials.insert(texture.clone(), material);
+        }
+        files.push(texture);
+    }
+    Ok(files)
+}
+
+// Process files found under root in parallel
+fn process_files(files: &[PathBuf], root: &Path, profile: &Profile, matcher: &FileMatcher, summaries: &mut Vec<FileSummary>, progress: &dyn ProgressSink,
+    cancel: &CancelToken) -> Result<()>
+{
+    // Process files in parallel on the current pool, printing each file's log in input order
+    let failed = AtomicBool::new(false);
+    let budget = profile.max_inflight_memory_mb.map(|mb| MemoryBudget::new(mb.saturating_mul(1024 * 1024)));
+    let (sender, receiver) = mpsc::channel::<(usize, Vec<String>, Result<()>, Option<FileSummary>)>();
+    
+    thread::scope(|scope| {
+        let printer = scope.spawn(move || {
+            let mut pending = BTreeMap::new();
+            let mut next = 0;
+            let mut first_error = None;
+            
+            for (index, log, result, summary) in receiver {
+                pending.insert(index, (log, result, summary));
+                while let Some((log, result, summary)) = pending.remove(&next) {
+                    log.iter().for_each(|line| progress.log(line));
+                    summaries.extend(summary);
+                    if let Err(err) = result {
+                        first_error.get_or_insert(err);
+                    }
+                    next += 1;
+                }
+            }
+            
+            first_error.map_or(Ok(()), Err)
+        });
+        
+        files.par_iter()
+            .enumerate()
+            .for_each_with(sender, |sender, (index, file)| {
+                let mut log = Vec::new();
+                // Stop processing new files once one has failed or the run was cancelled, those get no summary
+                let (summary, result) = if failed.load(Ordering::Relaxed) || cancel.is_cancelled() {
+                    (None, Ok(()))
+                } else {
+                    let _reservation = budget.as_ref().map(|budget| budget.reserve(estimated_memory(file)));
+                    let (summary, result) = summarized_process_file(file, root, profile, matcher, &mut log, progress, (index, files.len()), cancel);
+                    (Some(summary), result)
+                };
+                if result.is_err() {
+                    failed.store(true, Ordering::Relaxed);
+                }
+                let _ = sender.send((index, log, result, summary));
+            });
+        
+        printer.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+// Decoded page, its 8-bit RGBA copy and a channel image being encoded, at up to 8 bytes per pixel each
+const ESTIMATED_BYTES_PER_PIXEL: u64 = 24;
+
+// Memory splitting a file needs, estimated from its header. Unreadable headers count as nothing,
+// those files fail before decoding
+fn estimated_memory(file: &Path) -> u64 {
+    image_dimensions(file).map_or(0, |(width, height)| width as u64 * height as u64 * ESTIMATED_BYTES_PER_PIXEL)
+}
+
+// Write a copy of each normal map with green inverted next to it, in the same format.
+// The profile only provides encoder settings and the directory walk options
+fn normal_flip(inputs: &[PathBuf], file_regex: &Regex, suffix: &str, profile: &Profile) -> Result<()> {
+    for file in collect_images(inputs, file_regex, suffix, profile) {
+        let mut img = open_image(&file, profile.max_alloc())
+            .with_context(|| format!("Failed to open image: {}", file.display()))?;
+        normal::flip_green(&mut img)
+            .with_context(|| format!("Failed to flip normal map: {}", file.display()))?;
+        
+        let output = write_next_to(&file, img, suffix, profile)?;
+        println!("Flipped {} to: {}", file.display(), output.display());
+    }
+    
+    Ok(())
+}
+
+// Write a normal map generated from one channel of each height map next to it
+fn height_to_normal(inputs: &[PathBuf], file_regex: &Regex, suffix: &str, channel: usize, settings: &HeightToNormal, profile: &Profile) -> Result<()> {
+    if channel > 3 {
+        return Err(anyhow!("Invalid channel index: {}", channel));
+    }
+    
+    for file in collect_images(inputs, file_regex, suffix, profile) {
+        let img = open_image(&file, profile.max_alloc())
+            .with_context(|| format!("Failed to open image: {}", file.display()))?;
+        
+        // Keep the source's precision, 8-bit heights give visibly stepped normals otherwise
+        let height = grayscale_rgba(&img.to_rgba32f(), channel);
+        let normals = with_depth_of(DynamicImage::ImageRgba32F(normal::height_to_normal(&height, settings)), &img);
+        
+        let output = write_next_to(&file, normals, suffix, profile)?;
+        println!("Generated normal map from {}: {}", file.display(), output.display());
+    }
+    
+    Ok(())
+}
+
+// Write the cubemap of each equirectangular panorama next to it, packed with layout or as six files
+fn equirect_to_cubemap(inputs: &[PathBuf], file_regex: &Regex, suffix: &str, size: Option<u32>, layout: CubemapLayout, separate: bool, profile: &Profile) -> Result<()> {
+    if size == Some(0) {
+        return Err(anyhow!("The face size must be at least 1 pixel"));
+    }
+    let layout = if layout == CubemapLayout::Auto { CubemapLayout::Hcross } else { layout };
+    
+    for file in collect_images(inputs, file_regex, suffix, profile) {
+        let img = open_image(&file, profile.max_alloc())
+            .with_context(|| format!("Failed to open image: {}", file.display()))?;
+        if img.width() != img.height() * 2 {
+            println!("Warning: {} is {}x{}, panoramas are twice as wide as high", file.display(), img.width(), img.height());
+        }
+        
+        let size = size.unwrap_or((img.width() / 4).max(1));
+        let faces: Vec<DynamicImage> = cubemap::from_equirect(&img.to_rgba32f(), size).into_iter()
+            .map(|face| with_depth_of(DynamicImage::ImageRgba32F(face), &img))
+            .collect();
+        if separate {
+            for (name, face) in cubemap::FACES.iter().zip(faces) {
+                let output = write_next_to(&file, face, &format!("{}_{}", suffix, name), profile)?;
+                println!("Converted {} face {} to: {}", file.display(), name, output.display());
+            }
+        } else {
+            let packed = with_depth_of(cubemap::assemble(&faces, layout), &img);
+            let output = write_next_to(&file, packed, suffix, profile)?;
+            println!("Converted {} to: {}", file.display(), output.display());
+        }
+    }
+    
+    Ok(())
+}
+
+// Write the equirectangular panorama of each cross or strip cubemap next to it
+fn cubemap_to_equirect(inputs: &[PathBuf], file_regex: &Regex, suffix: &str, layout: CubemapLayout, width: Option<u32>, profile: &Profile) -> Result<()> {
+    if width.is_some_and(|width| width < 2) {
+        return Err(anyhow!("The panorama width must be at least 2 pixels"));
+    }
+    
+    for file in collect_images(inputs, file_regex, suffix, profile) {
+        let img = open_image(&file, profile.max_alloc())
+            .with_context(|| format!("Failed to open image: {}", file.display()))?;
+        let Some(faces) = cubemap::faces(&img, layout) else {
+            let expected = match layout {
+                CubemapLayout::Auto => "the proportions of a cubemap layout".to_string(),
+                layout => format!("the proportions of the {:?} layout", layout).to_lowercase(),
+            };
+            return Err(anyhow!("{} is {}x{}, which doesn't have {}", file.display(), img.width(), img.height(), expected));
+        };
+        
+        let faces: Vec<_> = faces.iter().map(DynamicImage::to_rgba32f).collect();
+        let width = width.unwrap_or(faces[0].width() * 4);
+        let panorama = with_depth_of(DynamicImage::ImageRgba32F(cubemap::to_equirect(&faces, width)), &img);
+        let output = write_next_to(&file, panorama, suffix, profile)?;
+        println!("Converted {} to: {}", file.display(), output.display());
+    }
+    
+    Ok(())
+}
+
+// A float result in the bit depth of the image it was made from
+fn with_depth_of(img: DynamicImage, source: &DynamicImage) -> DynamicImage {
+    match source.color().bytes_per_pixel() / source.color().channel_count() {
+        1 => DynamicImage::ImageRgba8(img.to_rgba8()),
+        2 => DynamicImage::ImageRgba16(img.to_rgba16()),
+        _ => img,
+    }
+}
+
+// Files given directly, plus the images in directories whose name matches file_regex.
+// Earlier outputs (stems ending in suffix) are left out of directory walks
+fn collect_images(inputs: &[PathBuf], file_regex: &Regex, suffix: &str, profile: &Profile) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if !input.is_dir() {
+            files.push(input.clone());
+            continue;
+        }
+        
+        let mut walker = WalkDir::new(input).follow_links(profile.follow_symlinks);
+        if let Some(max_depth) = profile.max_depth {
+            walker = walker.max_depth(max_depth);
+        }
+        let extensions = profile.allowed_extensions();
+        let mut found: Vec<_> = walker
+            .into_iter()
+            .filter_entry(|entry| entry.depth() == 0 || profile.include_hidden || !is_hidden(entry))
+            .filter_map(Result::ok)
+            .map(|entry| entry.into_path())
+            .filter(|path| {
+                let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+                path.is_file() &&
+                path.extension().is_some_and(|ext| extensions.contains(&ext.to_string_lossy().to_ascii_lowercase())) &&
+                file_regex.is_match(&path.file_name().unwrap_or_default().to_string_lossy()) &&
+                !stem.ends_with(suffix)
+            })
+            .collect();
+        found.sort();
+        files.extend(found);
+    }
+    files
+}
+
+// Encode a color image in the format of the file it was made from and write it alongside as {stem}{suffix}
+fn write_next_to(file: &Path, mut img: DynamicImage, suffix: &str, profile: &Profile) -> Result<PathBuf> {
+    let format = file.extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .ok_or_else(|| anyhow!("File has no extension to pick an output format from: {}", file.display()))?;
+    if matches!(format.as_str(), "jpg" | "jpeg" | "tga" | "webp") {
+        img = DynamicImage::ImageRgba8(img.to_rgba8());
+    }
+    
+    // Color images can only be stored subsampled in JPEG
+    let mut profile = profile.clone();
+    profile.jpeg_subsampling = JpegSubsampling::Yuv422;
+    let data = encode_image(&img, &format, &profile, None, None)
+        .with_context(|| format!("Failed to encode output of: {}", file.display()))?;
+    
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let output = file.with_file_name(format!("{}{}.{}", stem, suffix, format));
+    atomic::write(&output, data)
+        .with_context(|| format!("Failed to write file: {}", output.display()))?;
+    Ok(output)
+}
+
+// Process a file, recording how long it took and whether it failed in its summary. position is
+// the index of the file and the number of files for progress events
+#[allow(clippy::too_many_arguments)]
+fn summarized_process_file(file: &Path, root: &Path, profile: &Profile, matcher: &FileMatcher, log: &mut Vec<String>,
+    progress: &dyn ProgressSink, (index, total): (usize, usize), cancel: &CancelToken) -> (FileSummary, Result<()>)
+{
+    let mut summary = FileSummary::new(file.to_path_buf());
+    progress.file_started(file, index, total);
+    let started = Instant::now();
+    let result = process_file(file, root, profile, matcher, log, &mut summary, progress, cancel);
+    summary.duration = started.elapsed();
+    match &result {
+        Ok(()) => progress.file_done(file, index, total, summary.duration),
+        Err(err) => {
+            summary.error = Some(format!("{:#}", err));
+            progress.file_failed(file, index, total, err, summary.duration);
+        }
+    }
+    (summary, result)
+}
+
+fn print_log(log: &[String]) {
+    for line in log {
+        println!("{}", line);
+    }
+}
+
+// Process a single file, root is the directory it was found under
+// Output is collected in log so parallel runs can print it in a stable order
+#[allow(clippy::too_many_arguments)]
+fn process_file(file: &Path, root: &Path, profile: &Profile, matcher: &FileMatcher, log: &mut Vec<String>, summary: &mut FileSummary,
+    progress: &dyn ProgressSink, cancel: &CancelToken) -> Result<()>
+{
+    log.push(format!("Processing file: {}", file.display()));
+    
+    // Check dimensions from the header before decoding the whole image
+    let (width, height) = image_dimensions(file)
+        .with_context(|| format!("Failed to read image dimensions: {}", file.display()))?;
+    summary.dimensions = Some((width, height));
+    if !width.is_power_of_two() || !height.is_power_of_two() {
+        summary.warnings.push(format!("{}x{} is not a power of two, mipmaps and compressed formats may suffer", width, height));
+    }
+    
+    if let Some([min_width, min_height]) = profile.min_size
+        && (width < min_width || height < min_height)
+    {
+        let reason = format!("{}x{} is smaller than the minimum size {}x{}", width, height, min_width, min_height);
+        log.push(format!("  Warning: skipping, {}", reason));
+        summary.skipped = Some(reason);
+        return Ok(());
+    }
+    if let Some([max_width, max_height]) = profile.max_size
+        && (width > max_width || height > max_height)
+    {
+        let reason = format!("{}x{} is larger than the maximum size {}x{}", width, height, max_width, max_height);
+        log.push(format!("  Warning: skipping, {}", reason));
+        summary.skipped = Some(reason);
+        return Ok(());
+    }
+    
+    let pixels = width as u64 * height as u64;
+    if let Some(max_pixels) = profile.max_pixels
+        && pixels > max_pixels
+    {
+        let reason = format!("{} pixels exceeds the limit of {}", pixels, max_pixels);
+        log.push(format!("  Warning: skipping, {}", reason));
+        summary.skipped = Some(reason);
+        return Ok(());
+    }
+    
+    // Each extracted channel needs an RGBA buffer of its output depth on top of the decoded image
+    let max_alloc = profile.max_alloc();
+    let sample_bytes: u64 = 1 + profile.channels.iter().map(|channel| channel.bit_depth.unwrap_or_default().bytes_per_sample()).sum::<u64>();
+    if let Some(max_alloc) = max_alloc
+        && pixels.saturating_mul(4).saturating_mul(sample_bytes) > max_alloc
+    {
+        let reason = format!("{}x{} needs more than the {} MiB memory limit", width, height, max_alloc / 1024 / 1024);
+        log.push(format!("  Warning: skipping, {}", reason));
+        summary.skipped = Some(reason);
+        return Ok(());
+    }
+    
+    // Load the image, multi-page TIFFs yield one image per page
+    let decode_started = Instant::now();
+    let mut pages = match open_pages(file, max_alloc, profile.psd_layer.as_ref()) {
+        Ok(pages) => pages,
+        Err(err) if is_limit_error(&err) => {
+            let reason = format!("decoder limit exceeded: {}", err);
+            log.push(format!("  Warning: skipping, {}", reason));
+            summary.skipped = Some(reason);
+            return Ok(());
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to open image: {}", file.display()));
+        }
+    };
+    summary.pages = pages.len();
+    summary.stages.decode = decode_started.elapsed();
+    
+    // Tiles written whole keep their colors, so only splits check for grayscale inputs
+    if profile.grayscale_input != GrayscaleInput::Split && !profile.atlas_only && pages.iter().all(is_grayscale) {
+        let reason = "R, G and B are nearly identical, so it's likely a single map rather than a packed one".to_string();
+        if profile.grayscale_input == GrayscaleInput::Skip {
+            log.push(format!("  Warning: skipping, {}", reason));
+            summary.skipped = Some(reason);
+            return Ok(());
+        }
+        log.push(format!("  Warning: {}, its channels will come out the same", reason));
+        summary.warnings.push(reason);
+    }
+    
+    // Check the file name and get the folder outputs go to
+    if file.file_stem().and_then(|s| s.to_str()).is_none() {
+        return Err(anyhow!("Invalid file name: {}", file.display()));
+    }
+    
+    let parent = &output_root(file, root, profile);
+    
+    // Template variables: named captures first, so built-ins can't be shadowed
+    let mut vars = named_captures(&matcher.regex, &matcher.haystack(file, root));
+    vars.insert("stem".to_string(), matcher.stem(file));
+    if let Some(material) = matcher.materials.get(file) {
+        vars.insert("material".to_string(), material.clone());
+    }
+    
+    let preserve = profile.metadata == MetadataMode::Preserve;
+    let mut source_metadata = if preserve { metadata::read(file)? } else { Metadata::default() };
+    
+    // Turn pages upright, and mark preserved EXIF as upright so viewers don't rotate again. Without
+    // preserved metadata only the EXIF block is read for the orientation
+    let orientation = match (preserve, profile.apply_orientation) {
+        (_, false) => None,
+        (true, true) => source_metadata.exif.as_deref().and_then(metadata::orientation),
+        (false, true) => metadata::read_orientation(file)?,
+    };
+    if let Some(orientation) = orientation && orientation != 1 {
+        log.push(format!("  Applying EXIF orientation {}", orientation));
+        pages = pages.into_iter().map(|page| apply_orientation(page, orientation)).collect();
+        if let Some(exif) = &mut source_metadata.exif {
+            metadata::reset_orientation(exif);
+        }
+    }
+    
+    let metadata = (profile.metadata == MetadataMode::Preserve).then_some(source_metadata);
+    
+    if let Some(backup_dir) = &profile.backup_dir {
+        let backup = backup_source(file, root, backup_dir, profile.backup_links)
+            .with_context(|| format!("Failed to back up {} to {}", file.display(), backup_dir.display()))?;
+        log.push(format!("  Backed up to: {}", backup.display()));
+    }
+    
+    let provenance = profile.provenance.map(|_| Provenance::of(file, &profile.name)).transpose()?;
+    let mut material_maps = Vec::new();
+    
+    // Atlases are cut into tiles, sprite sheets into frames and cubemaps into faces first, each is
+    // processed like a page of its own
+    let page_count = pages.len();
+    let frames = sprite_frames(file, profile)?;
+    if let Some(frames) = &frames {
+        log.push(format!("  Sprite sheet with {} frames", frames.len()));
+    }
+    let mut parts: Vec<(usize, Option<String>, DynamicImage)> = Vec::new();
+    for (page_idx, img) in pages.into_iter().enumerate() {
+        if let Some(frames) = &frames {
+            let (width, height) = (img.width(), img.height());
+            if let Some(frame) = frames.iter().find(|frame| frame.x as u64 + frame.width as u64 > width as u64 || frame.y as u64 + frame.height as u64 > height as u64) {
+                let reason = format!("frame {} reaches past the {}x{} sheet", frame.name, width, height);
+                log.push(format!("  Warning: skipping, {}", reason));
+                summary.skipped = Some(reason);
+                return Ok(());
+            }
+            for frame in frames {
+                let region = img.crop_imm(frame.x, frame.y, frame.width, frame.height);
+                let region = if frame.rotated { region.rotate270() } else { region };
+                parts.push((page_idx, Some(frame.name.clone()), region));
+            }
+            continue;
+        }
+        if let Some(layout) = profile.cubemap {
+            let Some(faces) = cubemap::faces(&img, layout) else {
+                let reason = match layout {
+                    CubemapLayout::Auto => format!("{}x{} has the proportions of no cubemap layout", img.width(), img.height()),
+                    layout => format!("{}x{} doesn't fit the {:?} cubemap layout", img.width(), img.height(), layout).to_lowercase(),
+                };
+                log.push(format!("  Warning: skipping, {}", reason));
+                summary.skipped = Some(reason);
+                return Ok(());
+            };
+            for (name, face) in cubemap::FACES.iter().zip(faces) {
+                parts.push((page_idx, Some(name.to_string()), face));
+            }
+            continue;
+        }
+        let Some([columns, rows]) = profile.atlas else {
+            parts.push((page_idx, None, img));
+            continue;
+        };
+        if img.width() % columns != 0 || img.height() % rows != 0 {
+            let reason = format!("{}x{} can't be split into an even {}x{} atlas grid", img.width(), img.height(), columns, rows);
+            log.push(format!("  Warning: skipping, {}", reason));
+            summary.skipped = Some(reason);
+            return Ok(());
+        }
+        let (tile_width, tile_height) = (img.width() / columns, img.height() / rows);
+        for (index, name) in atlas_tile_names(profile).into_iter().enumerate() {
+            let (row, column) = (index as u32 / columns, index as u32 % columns);
+            let tile = img.crop_imm(column * tile_width, row * tile_height, tile_width, tile_height);
+            parts.push((page_idx, Some(name), tile));
+        }
+    }
+    
+    let mut logged_page = None;
+    let mut tile_layers = Vec::new();
+    for (page_idx, tile, img) in &parts {
+        // Pages are numbered from 1, single images get no suffix. Tiles add theirs after it
+        let mut page_suffix = if page_count > 1 {
+            if logged_page != Some(page_idx) {
+                log.push(format!("  Page {} of {}", page_idx + 1, page_count));
+                logged_page = Some(page_idx);
+            }
+            format!("_p{}", page_idx + 1)
+        } else {
+            String::new()
+        };
+        let page = (page_count > 1).then_some(page_idx + 1);
+        if let Some(name) = tile {
+            log.push(format!("  Tile {}", name));
+            vars.insert("tile".to_string(), name.clone());
+            page_suffix.push_str(&tile_suffix(profile, name));
+        }
+        
+        // Tiles are written as they are, in the profile's output format
+        if profile.atlas_only && is_layered_format(&profile.output_format) {
+            let name = tile.clone().unwrap_or_default();
+            let name = if page_count > 1 { format!("{}_p{}", name, page_idx + 1) } else { name };
+            tile_layers.push((name, img.to_rgba8()));
+            continue;
+        }
+        if profile.atlas_only {
+            let tile_started = Instant::now();
+            vars.insert("channel".to_string(), ATLAS_CHANNEL_NAME.to_string());
+            let output_path = output_path(parent, profile, &vars, &page_suffix, &profile.output_format)?;
+            let encoded = encode_image(img, &profile.output_format, profile, None, metadata.as_ref())
+                .with_context(|| format!("Failed to encode tile: {}", output_path.display()))?;
+            atomic::write(&output_path, &encoded)
+                .with_context(|| format!("Failed to save tile: {}", output_path.display()))?;
+            log.push(format!("  Saved tile to: {}", output_path.display()));
+            progress.channel_done(file, ATLAS_CHANNEL_NAME, &output_path, tile_started.elapsed());
+            continue;
+        }
+        
+        let mut sources = PageSources::new(img);
+        let thumbnails = profile.contact_sheet.is_some() || profile.report.is_some();
+        let stats = profile.stats_csv.is_some();
+        if thumbnails {
+            summary.thumbnails.push((format!("Source{}", page_suffix), sheet::thumbnail(img)));
+        }
+        let mut layers = Vec::new();
+        let mut written: Vec<(Vec<u8>, &str, PathBuf)> = Vec::new();
+        
+        let extract_started = Instant::now();
+        let alpha = page_alpha(&mut sources, profile);
+        let routed_alpha = routed_alpha(&mut sources, profile);
+        summary.stages.extract += extract_started.elapsed();
+        
+        // Process each channel
+        for channel_config in &profile.channels {
+            if channel_config.into_alpha_of.is_some() {
+                continue;
+            }
+            if stop_cancelled(cancel, log, summary) {
+                return Ok(());
+            }
+            let channel_started = Instant::now();
+            vars.insert("channel".to_string(), channel_config.name.clone());
+            
+            // Layered formats write all channels into one file below
+            let format = channel_config.format(profile);
+            if is_layered_format(format) {
+                let layer = channel_image(&mut sources, channel_config, profile, layer_depth(format));
+                summary.add_extract(format!("{}{}", channel_config.name, page_suffix), channel_started.elapsed());
+                if stats {
+                    summary.outputs.push(OutputSummary::of_channel(format!("{}{}", channel_config.name, page_suffix), &layer));
+                }
+                let constant = if profile.skip_constant || profile.report.is_some() { constant_value(&layer) } else { None };
+                if let Some(value) = constant {
+                    summary.warnings.push(format!("Channel {}{} is constant (value {})", channel_config.name, page_suffix, value));
+                    if profile.skip_constant {
+                        log.push(format!("  Skipping constant channel {} (value {})", channel_config.name, value));
+                        continue;
+                    }
+                }
+                if let Some(histogram_format) = profile.histogram {
+                    write_histogram(&layer, histogram_format, parent, profile, &vars, &page_suffix, log)?;
+                }
+                if thumbnails {
+                    summary.thumbnails.push((format!("{}{}", channel_config.name, page_suffix), sheet::thumbnail(&layer)));
+                }
+                layers.push((channel_config.name.clone(), gray_layer(layer)));
+                continue;
+            }
+            
+            let depth = channel_config.bit_depth.unwrap_or_default();
+            let mut channel_img = channel_image(&mut sources, channel_config, profile, depth);
+            summary.add_extract(format!("{}{}", channel_config.name, page_suffix), channel_started.elapsed());
+            if stats {
+                summary.outputs.push(OutputSummary::of_channel(format!("{}{}", channel_config.name, page_suffix), &channel_img));
+            }
+            
+            let constant = if profile.skip_constant || profile.report.is_some() { constant_value(&channel_img) } else { None };
+            if let Some(value) = constant {
+                summary.warnings.push(format!("Channel {}{} is constant (value {})", channel_config.name, page_suffix, value));
+                if profile.skip_constant {
+                    log.push(format!("  Skipping constant channel {} (value {})", channel_config.name, value));
+                    continue;
+                }
+            }
+            
+            let channel_parent = channel_config.output_parent(parent);
+            if let Some(histogram_format) = profile.histogram {
+                write_histogram(&channel_img, histogram_format, &channel_parent, profile, &vars, &page_suffix, log)?;
+            }
+            if thumbnails {
+                summary.thumbnails.push((format!("{}{}", channel_config.name, page_suffix), sheet::thumbnail(&channel_img)));
+            }
+            
+            if let Some(alpha) = routed_alpha.get(channel_config.name.as_str()).or(alpha.as_ref()) {
+                set_alpha(&mut channel_img, alpha);
+            }
+            
+            let output_path = output_path(&channel_parent, profile, &vars, &page_suffix, format)?;
+            
+            let encode_started = Instant::now();
+            let encoded = encode_image(&channel_img, format, profile, channel_config.color_space, metadata.as_ref())
+                .with_context(|| format!("Failed to encode channel image: {}", output_path.display()))?;
+            summary.stages.encode += encode_started.elapsed();
+            let write_started = Instant::now();
+            
+            if profile.duplicates != DuplicateMode::Write
+                && let Some((_, original_name, original_path)) = written.iter().find(|(bytes, ..)| *bytes == encoded)
+            {
+                // The entry pushed for this channel above
+                if stats && let Some(output) = summary.outputs.last_mut() {
+                    output.duplicate_of = Some(original_path.clone());
+                }
+                if profile.duplicates == DuplicateMode::Skip {
+                    log.push(format!("  Channel {} is identical to {}, not written", channel_config.name, original_name));
+                    material_maps.push(material_map(channel_config, &channel_img, original_path, page));
+                } else {
+                    link_duplicate(original_path, &output_path, profile.duplicates == DuplicateMode::Symlink)
+                        .with_context(|| format!("Failed to link {} to {}", output_path.display(), original_path.display()))?;
+                    summary.stages.write += write_started.elapsed();
+                    log.push(format!("  Channel {} is identical to {}, linked: {}", channel_config.name, original_name, output_path.display()));
+                    if let Some(provenance) = &provenance && profile.provenance == Some(ProvenanceFormat::Json) {
+                        write_provenance_sidecar(&output_path, &provenance.to_json(&channel_config.name, source_channel(channel_config)))?;
+                    }
+                    progress.channel_done(file, &channel_config.name, &output_path, channel_started.elapsed());
+                    material_maps.push(material_map(channel_config, &channel_img, &output_path, page));
+                }
+                continue;
+            }
+            
+            // Save the channel image
+            if let Some(command) = &profile.pre_command {
+                hooks::run(command, file, &output_path, &channel_config.name, log)?;
+            }
+            // Stamped after the duplicate check, the chunk names the channel
+            let mut stamped = None;
+            let mut sidecar = None;
+            if let Some(provenance) = &provenance {
+                let channel_index = source_channel(channel_config);
+                if profile.provenance == Some(ProvenanceFormat::Png) && output_extension(format) == "png" {
+                    let mut data = encoded.clone();
+                    encode::insert_png_chunks(&mut data, &[provenance.png_chunk(&channel_config.name, channel_index)]);
+                    stamped = Some(data);
+                } else {
+                    sidecar = Some(provenance.to_json(&channel_config.name, channel_index));
+                }
+            }
+            atomic::write(&output_path, stamped.as_ref().unwrap_or(&encoded))
+                .with_context(|| format!("Failed to save channel image: {}", output_path.display()))?;
+            if let Some(json) = sidecar {
+                write_provenance_sidecar(&output_path, &json)?;
+            }
+            
+            log.push(format!("  Saved channel {} to: {}", channel_config.name, output_path.display()));
+            if let Some(command) = &profile.post_command {
+                hooks::run(command, file, &output_path, &channel_config.name, log)?;
+            }
+            summary.stages.write += write_started.elapsed();
+            progress.channel_done(file, &channel_config.name, &output_path, channel_started.elapsed());
+            // The entry pushed for this channel above
+            if stats && let Some(output) = summary.outputs.last_mut() {
+                output.bytes = Some(encoded.len() as u64);
+            }
+            material_maps.push(material_map(channel_config, &channel_img, &output_path, page));
+            
+            if profile.duplicates != DuplicateMode::Write {
+                written.push((encoded, &channel_config.name, output_path));
+            }
+        }
+        
+        if !layers.is_empty() {
+            if stop_cancelled(cancel, log, summary) {
+                return Ok(());
+            }
+            let layers_started = Instant::now();
+            vars.insert("channel".to_string(), LAYERED_CHANNEL_NAME.to_string());
+            let output_path = output_path(parent, profile, &vars, &page_suffix, &profile.output_format)?;
+            
+            if let Some(command) = &profile.pre_command {
+                hooks::run(command, file, &output_path, LAYERED_CHANNEL_NAME, log)?;
+            }
+            // Layered writers encode straight into the file, so that counts as encoding
+            let encode_started = Instant::now();
+            atomic::write_with(&output_path, |path| write_layered(path, &layers, &profile.output_format))
+                .with_context(|| format!("Failed to save layered image: {}", output_path.display()))?;
+            summary.stages.encode += encode_started.elapsed();
+            
+            let names: Vec<&str> = layers.iter().map(|(name, _)| name.as_str()).collect();
+            log.push(format!("  Saved layers {} to: {}", names.join(", "), output_path.display()));
+            if let Some(command) = &profile.post_command {
+                hooks::run(command, file, &output_path, LAYERED_CHANNEL_NAME, log)?;
+            }
+            progress.channel_done(file, LAYERED_CHANNEL_NAME, &output_path, layers_started.elapsed());
+            if stats {
+                let bytes = fs::metadata(&output_path).map(|metadata| metadata.len()).ok();
+                summary.outputs.push(OutputSummary { name: format!("{}{}", LAYERED_CHANNEL_NAME, page_suffix), mean: None, stddev: None, bytes, duplicate_of: None });
+            }
+        }
+    }
+    
+    // Tiles of all pages as layers of one array texture
+    if let Some((_, first)) = tile_layers.first() {
+        let layers_started = Instant::now();
+        vars.remove("tile");
+        vars.insert("channel".to_string(), ATLAS_CHANNEL_NAME.to_string());
+        let output_path = output_path(parent, profile, &vars, "", &profile.output_format)?;
+        let (width, height) = first.dimensions();
+        let layers: Vec<(String, &[u8])> = tile_layers.iter().map(|(name, tile)| (name.clone(), tile.as_raw().as_slice())).collect();
+        atomic::write_with(&output_path, |path| ktx::write_array(path, width, height, 4, &layers))
+            .with_context(|| format!("Failed to save tiles: {}", output_path.display()))?;
+        log.push(format!("  Saved {} tiles to: {}", layers.len(), output_path.display()));
+        progress.channel_done(file, ATLAS_CHANNEL_NAME, &output_path, layers_started.elapsed());
+    }
+    
+    // Per-file contact sheets are named like outputs, the run-wide one is written by main
+    if let Some(template) = &profile.contact_sheet && template.contains('{') {
+        let path = parent.join(render_output_name(template, &vars)?);
+        write_contact_sheet(&path, std::slice::from_ref(summary), profile)?;
+        log.push(format!("  Saved contact sheet to: {}", path.display()));
+    }
+    
+    if let Some(template) = &profile.material_json {
+        write_material_file(parent, template, &vars, "material descriptor", log,
+            |dir| material::to_json(&vars["stem"], file, &material_maps, dir))?;
+    }
+    if let Some(template) = &profile.materialx {
+        write_material_file(parent, template, &vars, "MaterialX document", log,
+            |dir| material::to_materialx(&vars["stem"], &material_maps, dir))?;
+    }
+    if let Some(template) = &profile.usd {
+        write_material_file(parent, template, &vars, "USD material", log,
+            |dir| material::to_usda(&vars["stem"], &material_maps, dir))?;
+    }
+    if let Some(template) = &profile.blender_script {
+        write_material_file(parent, template, &vars, "Blender script", log,
+            |_| material::to_blender_script(&vars["stem"], &material_maps))?;
+    }
+    
+    // Only reached when every output was written, failures and cancellations return earlier
+    match &profile.after_source {
+        AfterSource::Keep => {}
+        AfterSource::Delete => {
+            fs::remove_file(file).with_context(|| format!("Failed to delete source: {}", file.display()))?;
+            log.push("  Deleted source".to_string());
+        }
+        AfterSource::Move(dir) => {
+            let moved = move_source(file, root, dir)
+                .with_context(|| format!("Failed to move {} to {}", file.display(), dir.display()))?;
+            log.push(format!("  Moved source to: {}", moved.display()));
+        }
+    }
+    
+    Ok(())
+}
+
+// Move a source under dir at its path relative to root, copying where a rename can't cross filesystems
+fn move_source(file: &Path, root: &Path, dir: &Path) -> Result<PathBuf> {
+    let moved = path_under(dir, file, root);
+    if let Some(parent) = moved.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(file, &moved).is_err() {
+        atomic::write_with(&moved, |temp| Ok(fs::copy(file, temp).map(|_| ())?))?;
+        fs::remove_file(file)?;
+    }
+    Ok(moved)
+}
+
+// Stop a file in flight once the run is cancelled. Outputs written so far are complete, the rest
+// of the file is left out and it counts as skipped
+fn stop_cancelled(cancel: &CancelToken, log: &mut Vec<String>, summary: &mut FileSummary) -> bool {
+    if !cancel.is_cancelled() {
+        return false;
+    }
+    log.push("  Cancelled, remaining outputs not written".to_string());
+    summary.skipped = Some("cancelled".to_string());
+    true
+}
+
+// Where file goes under dir, keeping its path relative to root (its name if it isn't under root)
+fn path_under(dir: &Path, file: &Path, root: &Path) -> PathBuf {
+    dir.join(file.strip_prefix(root).unwrap_or(Path::new(file.file_name().unwrap_or_default())))
+}
+
+// Folder the outputs of a file found under root are written to, before channel subdirs
+fn output_root(file: &Path, root: &Path, profile: &Profile) -> PathBuf {
+    let source_dir = file.parent().unwrap_or(Path::new("."));
+    match (&profile.output_dir, profile.output_layout) {
+        (None, _) => source_dir.to_path_buf(),
+        (Some(dir), OutputLayout::Flatten) => dir.clone(),
+        (Some(dir), OutputLayout::Mirror) => path_under(dir, file, root).parent().unwrap_or(dir).to_path_buf(),
+    }
+}
+
+// Copy (or hard-link) a source under backup_dir at its path relative to root
+fn backup_source(file: &Path, root: &Path, backup_dir: &Path, link: bool) -> Result<PathBuf> {
+    let backup = path_under(backup_dir, file, root);
+    if let Some(dir) = backup.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Hard links fail across filesystems, those get a copy
+    atomic::write_with(&backup, |temp| {
+        if !(link && fs::hard_link(file, temp).is_ok()) {
+            fs::copy(file, temp)?;
+        }
+        Ok(())
+    })?;
+    Ok(backup)
+}
+
+// Render and save a contact sheet in the format of its extension, PNG if it has none
+fn write_contact_sheet(path: &Path, summaries: &[FileSummary], profile: &Profile) -> Result<()> {
+    let Some(sheet) = sheet::render(summaries) else {
+        println!("No files were processed, contact sheet not written");
+        return Ok(());
+    };
+    
+    let format = path.extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_else(|| "png".to_string());
+    let encoded = encode_image(&DynamicImage::ImageRgba8(sheet), &format, profile, None, None)
+        .with_context(|| format!("Failed to encode contact sheet: {}", path.display()))?;
+    if let Some(dir) = path.parent() && !dir.as_os_str().is_empty() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create output directory: {}", dir.display()))?;
+    }
+    atomic::write(path, encoded)
+        .with_context(|| format!("Failed to save contact sheet: {}", path.display()))?;
+    if summaries.len() > 1 {
+        println!("Saved contact sheet to: {}", path.display());
+    }
+    Ok(())
+}
+
+// Write a file describing the maps of a source at a path relative to parent, contents gets the
+// folder it's written to so map paths can be made relative
+fn write_material_file(parent: &Path, template: &str, vars: &HashMap<String, String>, what: &str, log: &mut Vec<String>, contents: impl FnOnce(&Path) -> String) -> Result<()> {
+    let path = parent.join(render_output_name(template, vars)?);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create output directory: {}", dir.display()))?;
+    atomic::write(&path, contents(dir))
+        .with_context(|| format!("Failed to write {}: {}", what, path.display()))?;
+    log.push(format!("  Saved {} to: {}", what, path.display()));
+    Ok(())
+}
+
+fn material_map(channel: &ChannelConfig, img: &DynamicImage, path: &Path, page: Option<usize>) -> MaterialMap {
+    MaterialMap {
+        channel: channel.name.clone(),
+        semantic: channel.semantic(),
+        path: path.to_path_buf(),
+        color_space: channel.color_space.map(ColorSpace::name),
+        width: img.width(),
+        height: img.height(),
+        page,
+    }
+}
+
+// Source channel a channel reads, None when it's computed from several
+fn source_channel(channel: &ChannelConfig) -> Option<usize> {
+    (channel.expr.is_none() && !channel.reconstruct_z).then_some(channel.channel)
+}
+
+fn write_provenance_sidecar(output: &Path, json: &str) -> Result<()> {
+    let mut name = output.as_os_str().to_os_string();
+    name.push(".json");
+    let path = PathBuf::from(name);
+    atomic::write(&path, json)
+        .with_context(|| format!("Failed to write provenance: {}", path.display()))
+}
+
+// Write the histogram of a channel image under the channel's output name
+fn write_histogram(channel_img: &DynamicImage, format: HistogramFormat, parent: &Path, profile: &Profile, vars: &HashMap<String, String>, page_suffix: &str, log: &mut Vec<String>) -> Result<()> {
+    let bins = histogram::count(channel_img);
+    let suffix = format!("{}_histogram", page_suffix);
+    let (path, data) = match format {
+        HistogramFormat::Png => {
+            let path = output_path(parent, profile, vars, &suffix, "png")?;
+            let img = DynamicImage::ImageRgba8(histogram::render(&bins));
+            (path, encode_image(&img, "png", profile, None, None)?)
+        }
+        HistogramFormat::Csv => (output_path(parent, profile, vars, &suffix, "csv")?, histogram::to_csv(&bins).into_bytes()),
+    };
+    
+    atomic::write(&path, data)
+        .with_context(|| format!("Failed to save histogram: {}", path.display()))?;
+    log.push(format!("  Saved histogram to: {}", path.display()));
+    Ok(())
+}
+
+// Replace path with a hard or symbolic link to original. Symlinks are relative when both share a folder
+fn link_duplicate(original: &Path, path: &Path, symbolic: bool) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+        _ => {}
+    }
+    
+    if !symbolic {
+        fs::hard_link(original, path)?;
+        return Ok(());
+    }
+    
+    let target = match (original.parent() == path.parent(), original.file_name()) {
+        (true, Some(name)) => PathBuf::from(name),
+        _ => fs::canonicalize(original)?,
+    };
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&target, path)?;
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_file(&target, path)?;
+    
+    Ok(())
+}
+
+// Stands in for {channel} in the name of layered outputs
+const LAYERED_CHANNEL_NAME: &str = "Layers";
+
+// Stands in for {channel} in the name of atlas tiles written with atlas_only
+const ATLAS_CHANNEL_NAME: &str = "Tile";
+
+// Names of the tiles of the profile's atlas in row-major order, empty without one
+fn atlas_tile_names(profile: &Profile) -> Vec<String> {
+    let Some([columns, rows]) = profile.atlas else {
+        return Vec::new();
+    };
+    (0..rows * columns)
+        .map(|index| profile.atlas_names.get(index as usize).cloned()
+            .unwrap_or_else(|| format!("r{}c{}", index / columns + 1, index % columns + 1)))
+        .collect()
+}
+
+// Frames of a sprite sheet source, None when the profile reads no frame maps or it has none
+fn sprite_frames(file: &Path, profile: &Profile) -> Result<Option<Vec<sprites::Frame>>> {
+    let Some(template) = &profile.sprite_frames else {
+        return Ok(None);
+    };
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+    let vars = HashMap::from([("stem".to_string(), stem)]);
+    let path = file.parent().unwrap_or(Path::new(".")).join(render_output_name(template, &vars)?);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    sprites::read(&path).map(Some)
+}
+
+// Suffix of a tile's outputs, unless output_name places {tile} itself
+fn tile_suffix(profile: &Profile, tile: &str) -> String {
+    if profile.output_name.contains("{tile}") { String::new() } else { format!("_{}", tile) }
+}
+
+// Formats writing every channel as a named layer of a single file
+fn is_layered_format(format: &str) -> bool {
+    matches!(format, "tiff-layered" | "psd-layered" | "exr-multichannel" | "ktx2-array")
+}
+
+// File extension written for an output format
+fn output_extension(format: &str) -> &str {
+    match format {
+        "tiff-layered" => "tif",
+        "psd-layered" => "psd",
+        "exr-multichannel" => "exr",
+        "ktx2-array" => "ktx2",
+        _ => format,
+    }
+}
+
+// Render the output name template and make sure its directory exists
+fn output_path(parent: &Path, profile: &Profile, vars: &HashMap<String, String>, suffix: &str, format: &str) -> Result<PathBuf> {
+    let output_path = output_file(parent, profile, vars, suffix, format)?;
+    
+    // Captures may introduce subdirectories
+    if let Some(output_dir) = output_path.parent() {
+        fs::create_dir_all(output_dir)
+            .with_context(|| format!("Failed to create output directory: {}", output_dir.display()))?;
+    }
+    
+    Ok(output_path)
+}
+
+// Path of an output, without creating its directory
+fn output_file(parent: &Path, profile: &Profile, vars: &HashMap<String, String>, suffix: &str, format: &str) -> Result<PathBuf> {
+    let output_stem = render_output_name(&profile.output_name, vars)?;
+    Ok(parent.join(format!("{}{}.{}", output_stem, suffix, output_extension(format))))
+}
+
+// Undo EXIF orientation 2-8, each is a mirror and/or a rotation of the stored pixels
+fn apply_orientation(img: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+// Apply the value adjustments configured for a channel, then any color space conversion
+fn adjust_channel(img: &mut DynamicImage, channel: &ChannelConfig, profile: &Profile) {
+    if let Some(levels) = channel.levels {
+        map_values(img, |value| {
+            let value = ((value * 255.0 - levels.in_low) / (levels.in_high - levels.in_low)).clamp(0.0, 1.0);
+            (levels.out_low + value * (levels.out_high - levels.out_low)) / 255.0
+        });
+    }
+    
+    if let Some(gamma) = channel.gamma {
+        map_values(img, |value| value.max(0.0).powf(1.0 / gamma));
+    }
+    
+    if let Some(curve) = &channel.lut_curve {
+        map_values(img, |value| curve.apply(value));
+    }
+    
+    if let Some(threshold) = channel.threshold {
+        map_values(img, |value| if value * 255.0 >= threshold { 1.0 } else { 0.0 });
+    }
+    
+    if let (Some(from), Some(to)) = (profile.input_color_space, channel.color_space) && from != to {
+        map_values(img, match to {
+            ColorSpace::Srgb => linear_to_srgb,
+            ColorSpace::Linear => srgb_to_linear,
+        });
+    }
+}
+
+// Unit normal Z from X and Y stored in r and g, clamped to 0 where X/Y are longer than 1
+const RECONSTRUCT_Z_EXPR: &str = "sqrt(max(1 - (r * 2 - 1) ^ 2 - (g * 2 - 1) ^ 2, 0)) * 0.5 + 0.5";
+
+// A decoded page and its conversions to the sample types channels are built from, made on first use
+struct PageSources<'a> {
+    page: &'a DynamicImage,
+    rgba8: RgbaImage,
+    rgba16: Option<ImageBuffer<Rgba<u16>, Vec<u16>>>,
+    rgba32f: Option<Rgba32FImage>,
+}
+
+impl<'a> PageSources<'a> {
+    fn new(page: &'a DynamicImage) -> Self {
+        PageSources { page, rgba8: page.to_rgba8(), rgba16: None, rgba32f: None }
+    }
+    
+    fn rgba16(&mut self) -> &ImageBuffer<Rgba<u16>, Vec<u16>> {
+        self.rgba16.get_or_insert_with(|| self.page.to_rgba16())
+    }
+    
+    fn rgba32f(&mut self) -> &Rgba32FImage {
+        self.rgba32f.get_or_insert_with(|| self.page.to_rgba32f())
+    }
+}
+
+// Build the grayscale output image of a channel at the given depth, with its adjustments applied.
+// Higher depths convert from the decoded page, so no precision is lost to 8-bit first
+fn channel_image(sources: &mut PageSources, channel: &ChannelConfig, profile: &Profile, depth: BitDepth) -> DynamicImage {
+    let mut img = if let Some(expression) = &channel.expression {
+        // Expression channels are computed in float from the whole page
+        let source = sources.rgba32f();
+        let values = if channel.flip_green {
+            let mut flipped = source.as_raw().clone();
+            flipped.iter_mut().skip(1).step_by(4).for_each(|g| *g = 1.0 - *g);
+            expression.eval(&flipped)
+        } else {
+            expression.eval(source.as_raw())
+        };
+        let width = source.width();
+        let computed = DynamicImage::ImageRgba32F(Rgba32FImage::from_fn(width, source.height(), |x, y| {
+            let value = values[(y * width + x) as usize];
+            Rgba([value, value, value, 1.0])
+        }));
+        match depth {
+            BitDepth::Eight => DynamicImage::ImageRgba8(computed.to_rgba8()),
+            BitDepth::Sixteen => DynamicImage::ImageRgba16(computed.to_rgba16()),
+            BitDepth::Float => computed,
+        }
+    } else {
+        let mut img = match depth {
+            BitDepth::Eight => DynamicImage::ImageRgba8(grayscale_rgba(&sources.rgba8, channel.channel)),
+            BitDepth::Sixteen => DynamicImage::ImageRgba16(grayscale_rgba(sources.rgba16(), channel.channel)),
+            BitDepth::Float => DynamicImage::ImageRgba32F(grayscale_rgba(sources.rgba32f(), channel.channel)),
+        };
+        if channel.flip_green && channel.channel == 1 {
+            map_values(&mut img, |value| 1.0 - value);
+        }
+        img
+    };
+    
+    adjust_channel(&mut img, channel, profile);
+    
+    if let Some(settings) = &channel.height_to_normal {
+        let normals = DynamicImage::ImageRgba32F(normal::height_to_normal(&img.to_rgba32f(), settings));
+        img = match depth {
+            BitDepth::Eight => DynamicImage::ImageRgba8(normals.to_rgba8()),
+            BitDepth::Sixteen => DynamicImage::ImageRgba16(normals.to_rgba16()),
+            BitDepth::Float => normals,
+        };
+    }
+    img
+}
+
+// Normalized alpha plane shared by all channel outputs of a page
+fn page_alpha(sources: &mut PageSources, profile: &Profile) -> Option<Vec<f32>> {
+    match profile.alpha {
+        Some(AlphaSource::Fill(value)) => Some(vec![value / 255.0; (sources.page.width() * sources.page.height()) as usize]),
+        Some(AlphaSource::Channel(channel)) => {
+            Some(sources.rgba32f().pixels().map(|pixel| pixel[channel as usize]).collect())
+        }
+        None => None,
+    }
+}
+
+// Channels routed into the alpha of another output, by target name
+fn routed_alpha<'a>(sources: &mut PageSources, profile: &'a Profile) -> HashMap<&'a str, Vec<f32>> {
+    let mut routed = HashMap::new();
+    for channel_config in &profile.channels {
+        if let Some(target) = &channel_config.into_alpha_of {
+            let plane = channel_image(sources, channel_config, profile, BitDepth::Float).into_rgba32f();
+            routed.insert(target.as_str(), plane.pixels().map(|pixel| pixel[0]).collect());
+        }
+    }
+    routed
+}
+
+// Replace the alpha of an RGBA image with a normalized plane, rounded to the image's depth
+fn set_alpha(img: &mut DynamicImage, alpha: &[f32]) {
+    let samples = alpha.iter().map(|value| value.clamp(0.0, 1.0));
+    match img {
+        DynamicImage::ImageRgba8(buffer) => buffer.pixels_mut().zip(samples)
+            .for_each(|(pixel, value)| pixel[3] = (value * 255.0).round() as u8),
+        DynamicImage::ImageRgba16(buffer) => buffer.pixels_mut().zip(samples)
+            .for_each(|(pixel, value)| pixel[3] = (value * 65535.0).round() as u16),
+        // Float outputs keep the source's range
+        DynamicImage::ImageRgba32F(buffer) => buffer.pixels_mut().zip(alpha)
+            .for_each(|(pixel, &value)| pixel[3] = value),
+        _ => {}
+    }
+}
+
+// The value of a channel image that has the same value in every pixel, formatted at its bit depth
+fn constant_value(img: &DynamicImage) -> Option<String> {
+    // Whole pixels are compared, since channels written as normal maps aren't grayscale
+    fn uniform<T: PartialEq + Copy>(samples: &[T], step: usize) -> Option<T> {
+        let first = samples.get(..step)?;
+        samples.chunks(step).all(|pixel| pixel == first).then_some(first[0])
+    }
+    
+    match img {
+        DynamicImage::ImageLuma8(buffer) => uniform(buffer.as_raw(), 1).map(|v| v.to_string()),
+        DynamicImage::ImageRgba8(buffer) => uniform(buffer.as_raw(), 4).map(|v| v.to_string()),
+        DynamicImage::ImageRgba16(buffer) => uniform(buffer.as_raw(), 4).map(|v| v.to_string()),
+        DynamicImage::ImageRgba32F(buffer) => uniform(buffer.as_raw(), 4).map(|v| v.to_string()),
+        _ => None,
+    }
+}
+
+// Map the color values of an image as normalized floats, alpha is left as is.
+// Integer images go through a lookup table of every possible value
+fn map_values(img: &mut DynamicImage, transfer: impl Fn(f32) -> f32) {
+    match img {
+        DynamicImage::ImageLuma8(buffer) => {
+            let lut: Vec<u8> = (0..=255).map(|v| (transfer(v as f32 / 255.0).clamp(0.0, 1.0) * 255.0).round() as u8).collect();
+            buffer.pixels_mut().for_each(|pixel| pixel.0[0] = lut[pixel.0[0] as usize]);
+        }
+        DynamicImage::ImageRgba8(buffer) => {
+            let lut: Vec<u8> = (0..=255).map(|v| (transfer(v as f32 / 255.0).clamp(0.0, 1.0) * 255.0).round() as u8).collect();
+            buffer.pixels_mut().for_each(|pixel| pixel.0[..3].iter_mut().for_each(|v| *v = lut[*v as usize]));
+        }
+        DynamicImage::ImageRgba16(buffer) => {
+            let lut: Vec<u16> = (0..=65535).map(|v| (transfer(v as f32 / 65535.0).clamp(0.0, 1.0) * 65535.0).round() as u16).collect();
+            buffer.pixels_mut().for_each(|pixel| pixel.0[..3].iter_mut().for_each(|v| *v = lut[*v as usize]));
+        }
+        DynamicImage::ImageRgba32F(buffer) => {
+            buffer.pixels_mut().for_each(|pixel| pixel.0[..3].iter_mut().for_each(|v| *v = transfer(*v)));
+        }
+        _ => {}
+    }
+}
+
+// sRGB transfer functions (IEC 61966-2-1)
+fn linear_to_srgb(value: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Copy one channel into R, G and B of an opaque image, so it reads as grayscale
+fn grayscale_rgba<T: Primitive>(img: &ImageBuffer<Rgba<T>, Vec<T>>, channel_idx: usize) -> ImageBuffer<Rgba<T>, Vec<T>>
+where
+    Rgba<T>: Pixel<Subpixel = T>,
+{
+    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        let value = img.get_pixel(x, y)[channel_idx];
+        Rgba([value, value, value, T::DEFAULT_MAX_VALUE])
+    })
+}
+
+// Luma images, and color images whose R, G and B are within 2/255 of each other in 99% of the
+// pixels, leaving room for compression noise
+fn is_grayscale(img: &DynamicImage) -> bool {
+    if img.color().channel_count() < 3 {
+        return true;
+    }
+    let rgb = img.to_rgb8();
+    let colored = rgb.pixels()
+        .filter(|pixel| pixel.0.iter().max().unwrap() - pixel.0.iter().min().unwrap() > 2)
+        .count();
+    (colored as u64) * 100 <= rgb.width() as u64 * rgb.height() as u64
+}
+
+// Decode an image, optionally with a custom allocation limit
+fn open_image(file: &Path, max_alloc: Option<u64>) -> image::ImageResult<DynamicImage> {
+    let mut reader = image::io::Reader::open(file)?.with_guessed_format()?;
+    
+    if let Some(max_alloc) = max_alloc {
+        let mut limits = Limits::default();
+        limits.max_alloc = Some(max_alloc);
+        reader.limits(limits);
+    }
+    
+    reader.decode()
+}
+
+fn has_extension(file: &Path, extensions: &[&str]) -> bool {
+    file.extension()
+        .map(|ext| extensions.contains(&ext.to_string_lossy().to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+// A channel image with the format it's encoded in
+type ChannelOutput<'a> = (&'a ChannelConfig, &'a str, DynamicImage);
+
+// The channels of one decoded page as the profile extracts them, before encoding: the regular
+// outputs with their format and alpha applied, and the grayscale layers of a layered output
+fn extract_page<'a>(img: &DynamicImage, profile: &'a Profile) -> (Vec<ChannelOutput<'a>>, Vec<(String, DynamicImage)>) {
+    let mut sources = PageSources::new(img);
+    let alpha = page_alpha(&mut sources, profile);
+    let routed_alpha = routed_alpha(&mut sources, profile);
+    let (mut outputs, mut layers) = (Vec::new(), Vec::new());
+    for channel in profile.channels.iter().filter(|channel| channel.into_alpha_of.is_none()) {
+        let format = channel.format(profile);
+        if is_layered_format(format) {
+            let layer = channel_image(&mut sources, channel, profile, layer_depth(format));
+            layers.push((channel.name.clone(), gray_layer(layer)));
+            continue;
+        }
+        let mut channel_img = channel_image(&mut sources, channel, profile, channel.bit_depth.unwrap_or_default());
+        if let Some(alpha) = routed_alpha.get(channel.name.as_str()).or(alpha.as_ref()) {
+            set_alpha(&mut channel_img, alpha);
+        }
+        outputs.push((channel, format, channel_img));
+    }
+    (outputs, layers)
+}
+
+// Image size read from the file header
+fn image_dimensions(file: &Path) -> Result<(u32, u32)> {
+    if has_extension(file, &["psd", "psb"]) {
+        return psd::dimensions(file);
+    }
+    
+    Ok(image::image_dimensions(file)?)
+}
+
+// Decode all pages of a file. Only TIFFs can have more than one
+fn open_pages(file: &Path, max_alloc: Option<u64>, psd_layer: Option<&LayerSelector>) -> Result<Vec<DynamicImage>> {
+    if has_extension(file, &["psd", "psb"]) {
+        return Ok(vec![psd::open(file, psd_layer)?]);
+    }
+    
+    if has_extension(file, &["tif", "tiff"]) && count_tiff_pages(file)? > 1 {
+        return decode_tiff_pages(file, max_alloc);
+    }
+    
+    Ok(vec![open_image(file, max_alloc)?])
+}
+
+fn count_tiff_pages(file: &Path) -> Result<usize> {
+    let mut decoder = tiff::decoder::Decoder::new(BufReader::new(fs::File::open(file)?))?;
+    let mut pages = 1;
+    
+    while decoder.more_images() {
+        decoder.next_image()?;
+        pages += 1;
+    }
+    
+    Ok(pages)
+}
+
+fn decode_tiff_pages(file: &Path, max_alloc: Option<u64>) -> Result<Vec<DynamicImage>> {
+    let mut decoder = tiff::decoder::Decoder::new(BufReader::new(fs::File::open(file)?))?;
+    if let Some(max_alloc) = max_alloc {
+        let mut limits = tiff::decoder::Limits::default();
+        limits.decoding_buffer_size = usize::try_from(max_alloc).unwrap_or(usize::MAX);
+        decoder = decoder.with_limits(limits);
+    }
+    
+    let mut pages = Vec::new();
+    loop {
+        let page = pages.len() + 1;
+        let (width, height) = decoder.dimensions()?;
+        let color_type = decoder.colortype()?;
+        
+        let img = match (color_type, decoder.read_image()?) {
+            (TiffColorType::Gray(8), DecodingResult::U8(buf)) => ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma8),
+            (TiffColorType::Gray(16), DecodingResult::U16(buf)) => ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma16),
+            (TiffColorType::GrayA(8), DecodingResult::U8(buf)) => ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLumaA8),
+            (TiffColorType::GrayA(16), DecodingResult::U16(buf)) => ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLumaA16),
+            (TiffColorType::RGB(8), DecodingResult::U8(buf)) => ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb8),
+            (TiffColorType::RGB(16), DecodingResult::U16(buf)) => ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb16),
+            (TiffColorType::RGB(32), DecodingResult::F32(buf)) => ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb32F),
+            (TiffColorType::RGBA(8), DecodingResult::U8(buf)) => ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba8),
+            (TiffColorType::RGBA(16), DecodingResult::U16(buf)) => ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba16),
+            (TiffColorType::RGBA(32), DecodingResult::F32(buf)) => ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba32F),
+            (color_type, _) => return Err(anyhow!("Unsupported color type {:?} in TIFF page {}", color_type, page)),
+        };
+        pages.push(img.ok_or_else(|| anyhow!("Truncated image data in TIFF page {}", page))?);
+        
+        if !decoder.more_images() {
+            break;
+        }
+        decoder.next_image()?;
+    }
+    
+    Ok(pages)
+}
+
+// Sample depth channels of a layered format are extracted at: float for EXR, 8 bits otherwise
+fn layer_depth(format: &str) -> BitDepth {
+    if format == "exr-multichannel" { BitDepth::Float } else { BitDepth::Eight }
+}
+
+// A channel image as a layer, one gray channel where the depth has an image type for it
+fn gray_layer(img: DynamicImage) -> DynamicImage {
+    match img {
+        DynamicImage::ImageRgba8(_) => DynamicImage::ImageLuma8(img.into_luma8()),
+        img => img,
+    }
+}
+
+// Write layers with the writer of a layered output format. EXR keeps the float values of the
+// layers, the others are written with 8 bits
+fn write_layered(path: &Path, layers: &[(String, DynamicImage)], format: &str) -> Result<()> {
+    if format == "exr-multichannel" {
+        return write_multichannel_exr(path, layers);
+    }
+    let layers: Vec<(String, GrayImage)> = layers.iter().map(|(name, layer)| (name.clone(), layer.to_luma8())).collect();
+    match format {
+        "psd-layered" => psd::write_layers(path, &layers),
+        "ktx2-array" => write_ktx2_layers(path, &layers),
+        _ => write_layered_tiff(path, &layers),
+    }
+}
+
+// Write each channel as a named page of one TIFF, which GIMP and Krita open as layers
+fn write_layered_tiff(path: &Path, layers: &[(String, GrayImage)]) -> Result<()> {
+    let mut encoder = TiffEncoder::new(BufWriter::new(fs::File::create(path)?))?;
+    
+    for (name, layer) in layers {
+        let mut page = encoder.new_image_with_compression::<colortype::Gray8, _>(layer.width(), layer.height(), Lzw)?;
+        page.encoder().write_tag(Tag::Unknown(TIFF_TAG_PAGE_NAME), name.as_str())?;
+        page.write_data(layer.as_raw())?;
+    }
+    
+    Ok(())
+}
+
+const TIFF_TAG_PAGE_NAME: u16 = 285;
+
+// Write each channel as an R8 layer of a KTX2 array texture
+fn write_ktx2_layers(path: &Path, layers: &[(String, GrayImage)]) -> Result<()> {
+    let (width, height) = layers.first()
+        .map(|(_, layer)| layer.dimensions())
+        .ok_or_else(|| anyhow!("No channels to write"))?;
+    let layers: Vec<(String, &[u8])> = layers.iter().map(|(name, layer)| (name.clone(), layer.as_raw().as_slice())).collect();
+    ktx::write_array(path, width, height, 1, &layers)
+}
+
+// Write each channel as a 32-bit float luminance-only EXR channel named like Roughness.Y, from the
+// red of each layer
+fn write_multichannel_exr(path: &Path, layers: &[(String, DynamicImage)]) -> Result<()> {
+    use exr::prelude::{AnyChannel, AnyChannels, Encoding, FlatSamples, Layer, LayerAttributes, SmallVec, Text, WritableImage};
+    
+    let (width, height) = layers.first()
+        .map(|(_, layer)| (layer.width(), layer.height()))
+        .ok_or_else(|| anyhow!("No channels to write"))?;
+    
+    let channels = layers.iter()
+        .map(|(name, layer)| {
+            let samples = layer.to_rgba32f().pixels().map(|pixel| pixel[0]).collect();
+            AnyChannel::new(Text::from(format!("{}.Y", name).as_str()), FlatSamples::F32(samples))
+        })
+        .collect();
+    
+    let layer = Layer::new(
+        (width as usize, height as usize),
+        LayerAttributes::default(),
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(SmallVec::from_vec(channels)),
+    );
+    
+    exr::prelude::Image::from_layer(layer).write().to_file(path)?;
+    Ok(())
+}
+
+// Whether an error came from a decoder refusing to exceed its limits
+fn is_limit_error(err: &anyhow::Error) -> bool {
+    matches!(err.downcast_ref::<ImageError>(), Some(ImageError::Limits(_))) ||
+        matches!(err.downcast_ref::<tiff::TiffError>(), Some(tiff::TiffError::LimitsExceeded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn glob_matches(glob: &str, path: &str) -> bool {
+        glob_to_regex(glob).unwrap().is_match(path)
+    }
+    
+    #[test]
+    fn glob_star_stays_within_a_folder() {
+        assert!(glob_matches("*_ORM.png", "rock_ORM.png"));
+        assert!(glob_matches("*_ORM.png", "_ORM.png"));
+        assert!(!glob_matches("*_ORM.png", "rocks/rock_ORM.png"));
+        assert!(!glob_matches("*_ORM.png", "rock_ORM.png.bak"));
+        assert!(!glob_matches("*_ORM.png", "rock_ORMxpng"));
+    }
+    
+    #[test]
+    fn glob_double_star_crosses_folders() {
+        assert!(glob_matches("**/*_ORM.png", "rock_ORM.png"));
+        assert!(glob_matches("**/*_ORM.png", "a/b/rock_ORM.png"));
+        assert!(glob_matches("textures/**", "textures/a/b.png"));
+        assert!(!glob_matches("textures/**/*.png", "other/a.png"));
+        assert!(glob_matches("textures\\*.png", "textures/a.png"));
+    }
+    
+    #[test]
+    fn glob_question_mark_is_one_character() {
+        assert!(glob_matches("rock_?.png", "rock_1.png"));
+        assert!(!glob_matches("rock_?.png", "rock_12.png"));
+        assert!(!glob_matches("rock?orm.png", "rock/orm.png"));
+    }
+    
+    #[test]
+    fn glob_classes() {
+        assert!(glob_matches("rock_[abc].png", "rock_b.png"));
+        assert!(!glob_matches("rock_[abc].png", "rock_d.png"));
+        assert!(glob_matches("rock_[!abc].png", "rock_d.png"));
+        assert!(!glob_matches("rock_[^abc].png", "rock_a.png"));
+        assert!(glob_matches("rock_[0-9].png", "rock_7.png"));
+        assert!(!glob_matches("rock_[0-9].png", "rock_x.png"));
+        assert!(glob_matches("rock_[a-].png", "rock_-.png"));
+        assert!(glob_matches("rock_[]].png", "rock_].png"));
+    }
+    
+    #[test]
+    fn glob_class_contents_are_literal() {
+        assert!(glob_matches("rock_[&&x].png", "rock_&.png"));
+        assert!(!glob_matches("rock_[&&x].png", "rock_y.png"));
+        assert!(glob_matches("rock_[~~].png", "rock_~.png"));
+        assert!(glob_matches("rock_[a^].png", "rock_^.png"));
+        assert!(glob_matches("rock_[[].png", "rock_[.png"));
+        assert!(glob_to_regex("rock_[abc.png").is_err());
+        assert!(glob_to_regex("rock_[].png").is_err());
+    }
+    
+    #[test]
+    fn glob_braces() {
+        assert!(glob_matches("*_ORM.{png,tga}", "rock_ORM.tga"));
+        assert!(glob_matches("*_ORM.{png,tga}", "rock_ORM.png"));
+        assert!(!glob_matches("*_ORM.{png,tga}", "rock_ORM.exr"));
+        assert!(glob_matches("*_{AO,Occlusion}.*", "rock_Occlusion.png"));
+        assert!(glob_matches("a,b.png", "a,b.png"));
+        assert!(glob_matches("a}.png", "a}.png"));
+        assert!(glob_to_regex("*.{png,tga").is_err());
+        assert!(glob_to_regex("*.{png,{tga,exr}}").is_err());
+    }
+    
+    #[test]
+    fn glob_escapes_regex_characters() {
+        assert!(glob_matches("rock (1)+.png", "rock (1)+.png"));
+        assert!(!glob_matches("rock.png", "rockxpng"));
+        assert!(glob_matches("$rock^.png", "$rock^.png"));
+    }
+}
@@ -0,0 +1,181 @@
+// 1D lookup tables (.cube or CSV) applied to channel values as a piecewise linear curve
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Result, anyhow, Context};
+
+// Curve points as (input, output) pairs in normalized 0-1 units, sorted by input
+#[derive(Debug, Clone)]
+pub struct Lut {
+    points: Vec<(f32, f32)>,
+}
+
+impl Lut {
+    // .cube files are read as Resolve/Adobe 1D LUTs, anything else as CSV
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read LUT file: {}", path.display()))?;
+        Self::parse(&text, path)
+    }
+    
+    // The contents of a LUT file, path giving the format by its extension and naming it in errors
+    pub fn parse(text: &str, path: &Path) -> Result<Self> {
+        let is_cube = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("cube"));
+        let points = if is_cube { parse_cube(text) } else { parse_csv(text) }
+            .with_context(|| format!("Invalid LUT file: {}", path.display()))?;
+        
+        if points.len() < 2 {
+            return Err(anyhow!("LUT file needs at least two entries: {}", path.display()));
+        }
+        
+        Ok(Lut { points })
+    }
+    
+    // Interpolate between the surrounding points, inputs outside the curve take the end values
+    pub fn apply(&self, value: f32) -> f32 {
+        let upper = self.points.partition_point(|&(input, _)| input < value);
+        if upper == 0 {
+            return self.points[0].1;
+        }
+        if upper == self.points.len() {
+            return self.points[upper - 1].1;
+        }
+        
+        let (x0, y0) = self.points[upper - 1];
+        let (x1, y1) = self.points[upper];
+        if x1 == x0 {
+            return y1;
+        }
+        y0 + (value - x0) / (x1 - x0) * (y1 - y0)
+    }
+}
+
+// Rows of one to three values, only the first column is used since channels are grayscale.
+// The rows are spread evenly over DOMAIN_MIN..DOMAIN_MAX (0-1 unless set)
+fn parse_cube(text: &str) -> Result<Vec<(f32, f32)>> {
+    let mut domain = (0.0, 1.0);
+    let mut size = None;
+    let mut values = Vec::new();
+    
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        
+        let mut fields = line.split_whitespace();
+        let keyword = fields.next().unwrap_or("");
+        match keyword {
+            "TITLE" => {}
+            "LUT_3D_SIZE" => return Err(anyhow!("3D LUTs can't be applied to a single channel")),
+            "LUT_1D_SIZE" => {
+                size = Some(fields.next().and_then(|v| v.parse::<usize>().ok())
+                    .ok_or_else(|| anyhow!("Invalid LUT_1D_SIZE: {}", line))?);
+            }
+            "DOMAIN_MIN" | "DOMAIN_MAX" | "LUT_1D_INPUT_RANGE" => {
+                let numbers = parse_numbers(line.split_whitespace().skip(1))?;
+                let (Some(&first), Some(&last)) = (numbers.first(), numbers.last()) else {
+                    return Err(anyhow!("Missing value: {}", line));
+                };
+                match keyword {
+                    "DOMAIN_MIN" => domain.0 = first,
+                    "DOMAIN_MAX" => domain.1 = first,
+                    _ => domain = (first, last),
+                }
+            }
+            _ => {
+                let numbers = parse_numbers(line.split_whitespace())?;
+                values.push(numbers[0]);
+            }
+        }
+    }
+    
+    if let Some(size) = size && size != values.len() {
+        return Err(anyhow!("LUT_1D_SIZE is {} but the file has {} entries", size, values.len()));
+    }
+    
+    let last = values.len().saturating_sub(1).max(1) as f32;
+    Ok(values.iter()
+        .enumerate()
+        .map(|(index, &value)| (domain.0 + index as f32 / last * (domain.1 - domain.0), value))
+        .collect())
+}
+
+// One output value per line spread evenly over 0-1, or "input,output" pairs.
+// A non-numeric first line is treated as a header
+fn parse_csv(text: &str) -> Result<Vec<(f32, f32)>> {
+    let mut rows = Vec::new();
+    
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        
+        match parse_numbers(line.split([',', ';']).map(str::trim)) {
+            Ok(numbers) => rows.push(numbers),
+            Err(_) if index == 0 => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    
+    if rows.iter().all(|row| row.len() >= 2) {
+        let mut points: Vec<(f32, f32)> = rows.iter().map(|row| (row[0], row[1])).collect();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        return Ok(points);
+    }
+    
+    let last = rows.len().saturating_sub(1).max(1) as f32;
+    Ok(rows.iter()
+        .enumerate()
+        .map(|(index, row)| (index as f32 / last, row[0]))
+        .collect())
+}
+
+fn parse_numbers<'a>(fields: impl Iterator<Item = &'a str>) -> Result<Vec<f32>> {
+    let numbers = fields
+        .map(|field| field.parse::<f32>().map_err(|_| anyhow!("Not a number: '{}'", field)))
+        .collect::<Result<Vec<f32>>>()?;
+    
+    if numbers.is_empty() {
+        return Err(anyhow!("Empty row"));
+    }
+    Ok(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn cube_rows_spread_over_the_domain() {
+        let text = "TITLE \"curve\"\n# comment\nLUT_1D_SIZE 3\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 2 2 2\n0 0 0\n0.25 0.25 0.25\n1 1 1\n";
+        assert_eq!(parse_cube(text).unwrap(), vec![(0.0, 0.0), (1.0, 0.25), (2.0, 1.0)]);
+        assert_eq!(parse_cube("LUT_1D_INPUT_RANGE 0.5 1\n0\n1\n").unwrap(), vec![(0.5, 0.0), (1.0, 1.0)]);
+    }
+    
+    #[test]
+    fn cube_files_must_be_1d_and_match_their_size() {
+        assert!(parse_cube("LUT_3D_SIZE 2\n").is_err());
+        assert!(parse_cube("LUT_1D_SIZE 3\n0\n1\n").is_err());
+        assert!(parse_cube("LUT_1D_SIZE x\n").is_err());
+        assert!(parse_cube("0\nhalf\n").is_err());
+    }
+    
+    #[test]
+    fn csv_takes_pairs_or_evenly_spread_values() {
+        assert_eq!(parse_csv("input,output\n1,0\n0;1\n").unwrap(), vec![(0.0, 1.0), (1.0, 0.0)]);
+        assert_eq!(parse_csv("0\n0.5\n0.75\n").unwrap(), vec![(0.0, 0.0), (0.5, 0.5), (1.0, 0.75)]);
+        assert!(parse_csv("0\nhalf\n").is_err());
+    }
+    
+    #[test]
+    fn apply_interpolates_and_clamps() {
+        let lut = Lut::parse("0\n1\n0.5\n", Path::new("curve.csv")).unwrap();
+        assert_eq!(lut.apply(0.25), 0.5);
+        assert_eq!(lut.apply(0.75), 0.75);
+        assert_eq!(lut.apply(-1.0), 0.0);
+        assert_eq!(lut.apply(2.0), 0.5);
+        assert!(Lut::parse("1\n", Path::new("one.cube")).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+// Files describing the maps split from one source, for importers and lookdev tools that build
+// materials from them instead of guessing from file names
+use std::path::{Component, Path, PathBuf};
+
+use crate::html::escape;
+use crate::json;
+
+pub struct MaterialMap {
+    pub channel: String,
+    pub semantic: String,
+    pub path: PathBuf,
+    pub color_space: Option<&'static str>,
+    pub width: u32,
+    pub height: u32,
+    // Numbered from 1 for multi-page sources
+    pub page: Option<usize>,
+}
+
+// Map paths are written relative to the folder of the descriptor, with forward slashes
+pub fn to_json(name: &str, source: &Path, maps: &[MaterialMap], dir: &Path) -> String {
+    let maps: Vec<String> = maps.iter()
+        .map(|map| {
+            let page = map.page.map(|page| format!(",\"page\":{}", page)).unwrap_or_default();
+            format!(
+                "    {{\"channel\":{},\"semantic\":{},\"file\":{},\"color_space\":{},\"width\":{},\"height\":{}{}}}",
+                json::string(&map.channel), json::string(&map.semantic), json::string(&relative_to(&map.path, dir)),
+                map.color_space.map_or("null".to_string(), json::string), map.width, map.height, page,
+            )
+        })
+        .collect();
+    format!(
+        "{{\n  \"name\": {},\n  \"source\": {},\n  \"maps\": [\n{}\n  ]\n}}\n",
+        json::string(name), json::string(&relative_to(source, dir)), maps.join(",\n"),
+    )
+}
+
+// standard_surface input a semantic is wired to, with its type. Maps with other semantics, like
+// occlusion which standard_surface has no input for, are declared but left unconnected
+fn standard_surface_input(semantic: &str) -> Option<(&'static str, &'static str)> {
+    match semantic {
+        "base_color" | "basecolor" | "albedo" | "diffuse" => Some(("base_color", "color3")),
+        "roughness" | "specular_roughness" => Some(("specular_roughness", "float")),
+        "metallic" | "metalness" => Some(("metalness", "float")),
+        "normal" => Some(("normal", "vector3")),
+        "emission" | "emissive" => Some(("emission_color", "color3")),
+        "opacity" => Some(("opacity", "color3")),
+        _ => None,
+    }
+}
+
+// MaterialX 1.38 document with one image node per map and a material named after the source.
+// Only the first map of each semantic is wired, later pages are declared like unknown semantics
+pub fn to_materialx(name: &str, maps: &[MaterialMap], dir: &Path) -> String {
+    let name = node_name(name);
+    let mut nodes = String::new();
+    let mut inputs = String::new();
+    let mut wired = Vec::new();
+    for map in maps {
+        let node = node_name(&match map.page {
+            Some(page) => format!("{}_p{}", map.channel, page),
+            None => map.channel.clone(),
+        });
+        let input = standard_surface_input(&map.semantic).filter(|(input, _)| !wired.contains(input));
+        let image_type = match input {
+            Some((_, "vector3")) => "vector3",
+            Some((_, "color3")) => "color3",
+            _ => "float",
+        };
+        let colorspace = match map.color_space {
+            Some("srgb") => " colorspace=\"srgb_texture\"",
+            Some("linear") => " colorspace=\"lin_rec709\"",
+            _ => "",
+        };
+        nodes.push_str(&format!(
+            "  <image name=\"{}\" type=\"{}\">\n    <input name=\"file\" type=\"filename\" value=\"{}\"{} />\n  </image>\n",
+            node, image_type, escape(&relative_to(&map.path, dir)), colorspace,
+        ));
+        match input {
+            // Tangent-space maps are decoded from 0-1 by a normalmap node
+            Some(("normal", _)) => {
+                nodes.push_str(&format!(
+                    "  <normalmap name=\"{0}_normalmap\" type=\"vector3\">\n    <input name=\"in\" type=\"vector3\" nodename=\"{0}\" />\n  </normalmap>\n",
+                    node,
+                ));
+                inputs.push_str(&format!("    <input name=\"normal\" type=\"vector3\" nodename=\"{}_normalmap\" />\n", node));
+            }
+            Some((input, input_type)) => {
+                inputs.push_str(&format!("    <input name=\"{}\" type=\"{}\" nodename=\"{}\" />\n", input, input_type, node));
+            }
+            None => {}
+        }
+        if let Some((input, _)) = input {
+            wired.push(input);
+        }
+    }
+    format!(
+        "<?xml version=\"1.0\"?>\n<materialx version=\"1.38\">\n{nodes}  <standard_surface name=\"{name}_surface\" type=\"surfaceshader\">\n{inputs}  </standard_surface>\n  <surfacematerial name=\"{name}\" type=\"material\">\n    <input name=\"surfaceshader\" type=\"surfaceshader\" nodename=\"{name}_surface\" />\n  </surfacematerial>\n</materialx>\n",
+    )
+}
+
+// UsdPreviewSurface input a semantic is wired to, its type and the texture output read
+fn preview_surface_input(semantic: &str) -> Option<(&'static str, &'static str, &'static str)> {
+    match semantic {
+        "base_color" | "basecolor" | "albedo" | "diffuse" => Some(("diffuseColor", "color3f", "rgb")),
+        "roughness" | "specular_roughness" => Some(("roughness", "float", "r")),
+        "metallic" | "metalness" => Some(("metallic", "float", "r")),
+        "occlusion" | "ambient_occlusion" | "ao" => Some(("occlusion", "float", "r")),
+        "normal" => Some(("normal", "normal3f", "rgb")),
+        "emission" | "emissive" => Some(("emissiveColor", "color3f", "rgb")),
+        "opacity" => Some(("opacity", "float", "r")),
+        _ => None,
+    }
+}
+
+// USD layer with a Material named after the source, a UsdPreviewSurface and a UsdUVTexture per
+// map. Data maps are read raw with an identity scale and bias so roughness and metallic values
+// arrive as written; normal maps are remapped from 0-1 to -1-1
+pub fn to_usda(name: &str, maps: &[MaterialMap], dir: &Path) -> String {
+    let name = node_name(name);
+    let mut textures = String::new();
+    let mut inputs = String::new();
+    let mut wired = Vec::new();
+    for map in maps {
+        let node = node_name(&match map.page {
+            Some(page) => format!("{}_p{}", map.channel, page),
+            None => map.channel.clone(),
+        });
+        let input = preview_surface_input(&map.semantic).filter(|(input, ..)| !wired.contains(input));
+        let is_color = matches!(input, Some((_, "color3f", _)));
+        let color_space = match map.color_space {
+            Some("srgb") => "sRGB",
+            None if is_color => "auto",
+            _ => "raw",
+        };
+        let (scale, bias) = match input {
+            Some(("normal", ..)) => ("(2, 2, 2, 1)", "(-1, -1, -1, 0)"),
+            _ => ("(1, 1, 1, 1)", "(0, 0, 0, 0)"),
+        };
+        let output = match input {
+            Some((.., output)) => output,
+            None => "r",
+        };
+        let output_type = if output == "rgb" { "float3" } else { "float" };
+        textures.push_str(&format!(
+            "\n    def Shader \"{node}\"\n    {{\n        uniform token info:id = \"UsdUVTexture\"\n        asset inputs:file = @{file}@\n        token inputs:sourceColorSpace = \"{color_space}\"\n        float4 inputs:scale = {scale}\n        float4 inputs:bias = {bias}\n        float2 inputs:st.connect = </{name}/TexCoord.outputs:result>\n        {output_type} outputs:{output}\n    }}\n",
+            file = relative_to(&map.path, dir),
+        ));
+        if let Some((input, input_type, output)) = input {
+            inputs.push_str(&format!("        {} inputs:{}.connect = </{}/{}.outputs:{}>\n", input_type, input, name, node, output));
+            wired.push(input);
+        }
+    }
+    format!(
+        "#usda 1.0\n(\n    defaultPrim = \"{name}\"\n)\n\ndef Material \"{name}\"\n{{\n    token outputs:surface.connect = </{name}/PreviewSurface.outputs:surface>\n\n    def Shader \"PreviewSurface\"\n    {{\n        uniform token info:id = \"UsdPreviewSurface\"\n{inputs}        token outputs:surface\n    }}\n\n    def Shader \"TexCoord\"\n    {{\n        uniform token info:id = \"UsdPrimvarReader_float2\"\n        string inputs:varname = \"st\"\n        float2 outputs:result\n    }}\n{textures}}}\n",
+    )
+}
+
+// Principled BSDF inputs a semantic is linked to, the first one this Blender version has. AO has
+// no input, its node is added unlinked
+fn principled_inputs(semantic: &str) -> &'static [&'static str] {
+    match semantic {
+        "base_color" | "basecolor" | "albedo" | "diffuse" => &["Base Color"],
+        "roughness" | "specular_roughness" => &["Roughness"],
+        "metallic" | "metalness" => &["Metallic"],
+        "normal" => &["Normal"],
+        // Renamed in Blender 4.0
+        "emission" | "emissive" => &["Emission Color", "Emission"],
+        "opacity" => &["Alpha"],
+        _ => &[],
+    }
+}
+
+const BLENDER_PRELUDE: &str = r#"import bpy
+
+material = bpy.data.materials.new(NAME)
+material.use_nodes = True
+nodes = material.node_tree.nodes
+links = material.node_tree.links
+bsdf = next(node for node in nodes if node.type == "BSDF_PRINCIPLED")
+
+def texture(label, path, non_color, y):
+    node = nodes.new("ShaderNodeTexImage")
+    node.label = label
+    node.image = bpy.data.images.load(path, check_existing=True)
+    if non_color:
+        node.image.colorspace_settings.name = "Non-Color"
+    node.location = (-600, y)
+    return node
+
+def connect(output, *names):
+    for name in names:
+        if name in bsdf.inputs:
+            links.new(output, bsdf.inputs[name])
+            return
+
+def normal_map(node, y):
+    normal = nodes.new("ShaderNodeNormalMap")
+    normal.location = (-300, y)
+    links.new(node.outputs["Color"], normal.inputs["Color"])
+    return normal.outputs["Normal"]
+"#;
+
+// Python script that builds a material named after the source with a Principled BSDF linked to
+// the maps when run in Blender (Text Editor, or blender --python). Images are loaded from
+// absolute paths, since Blender resolves relative ones against the .blend file
+pub fn to_blender_script(name: &str, maps: &[MaterialMap]) -> String {
+    let mut script = format!(
+        "# Builds the material of {} from its split maps. Run it in Blender's Text Editor or with\n# blender --python, then assign the material to the asset\n",
+        // Quoted so a line break in the name can't end the comment
+        json::string(name),
+    );
+    script.push_str(&BLENDER_PRELUDE.replace("NAME", &json::string(name)));
+    script.push('\n');
+    let mut linked: Vec<&str> = Vec::new();
+    for (index, map) in maps.iter().enumerate() {
+        let path = std::path::absolute(&map.path).unwrap_or_else(|_| map.path.clone());
+        let label = match map.page {
+            Some(page) => format!("{} (page {})", map.channel, page),
+            None => map.channel.clone(),
+        };
+        let inputs = principled_inputs(&map.semantic);
+        let is_color = matches!(inputs.first(), Some(&"Base Color" | &"Emission Color"));
+        let non_color = map.color_space != Some("srgb") && !(is_color && map.color_space.is_none());
+        let y = -300 * index as i64;
+        script.push_str(&format!(
+            "node = texture({}, {}, {}, {})\n",
+            json::string(&label), json::string(&path.to_string_lossy()), if non_color { "True" } else { "False" }, y,
+        ));
+        let Some(first) = inputs.first().filter(|input| !linked.contains(input)) else {
+            continue;
+        };
+        let names: Vec<String> = inputs.iter().map(|input| json::string(input)).collect();
+        if *first == "Normal" {
+            script.push_str(&format!("connect(normal_map(node, {}), {})\n", y, names.join(", ")));
+        } else {
+            script.push_str(&format!("connect(node.outputs[\"Color\"], {})\n", names.join(", ")));
+        }
+        linked.push(first);
+    }
+    script
+}
+
+// MaterialX and USD names allow letters, digits and underscores, and can't start with a digit
+fn node_name(text: &str) -> String {
+    let name: String = text.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) || name.is_empty() { format!("_{}", name) } else { name }
+}
+
+// Path of path as seen from dir, going up with .. where they part. Paths on different drives
+// stay as they are
+pub fn relative_to(path: &Path, dir: &Path) -> String {
+    let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let (path, dir) = (absolute(path), absolute(dir));
+    let mut path_parts = path.components().peekable();
+    let mut dir_parts = dir.components().peekable();
+    if path_parts.peek() != dir_parts.peek() {
+        return path.to_string_lossy().replace('\\', "/");
+    }
+    while path_parts.peek().is_some() && path_parts.peek() == dir_parts.peek() {
+        path_parts.next();
+        dir_parts.next();
+    }
+    let mut parts: Vec<String> = dir_parts
+        .filter(|part| matches!(part, Component::Normal(_)))
+        .map(|_| "..".to_string())
+        .collect();
+    parts.extend(path_parts.map(|part| part.as_os_str().to_string_lossy().into_owned()));
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn blender_script_names_stay_in_comments_and_strings() {
+        let name = "rock\nimport os; os.system('id')\r#";
+        let script = to_blender_script(name, &[]);
+        assert!(script.lines().all(|line| !line.starts_with("import os")));
+        assert!(script.starts_with("# Builds the material of \"rock\\nimport os; os.system('id')\\r#\" from"));
+    }
+}
@@ -0,0 +1,343 @@
+// ICC profile, EXIF and XMP blocks of a source file, so they can be copied into outputs
+use std::fs;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::Path;
+
+use anyhow::{Result, Context};
+use flate2::read::ZlibDecoder;
+
+// Raw blocks as stored in the container: ICC bytes, EXIF as a TIFF structure, XMP as XML
+#[derive(Debug, Default, Clone)]
+pub struct Metadata {
+    pub icc: Option<Vec<u8>>,
+    pub exif: Option<Vec<u8>>,
+    pub xmp: Option<Vec<u8>>,
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+pub const PNG_XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+pub const JPEG_EXIF_HEADER: &[u8] = b"Exif\0\0";
+pub const JPEG_XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+pub const JPEG_ICC_HEADER: &[u8] = b"ICC_PROFILE\0";
+
+// Read metadata from PNG, JPEG and WebP sources, other formats have none we can copy
+pub fn read(path: &Path) -> Result<Metadata> {
+    let data = fs::read(path)
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    
+    let metadata = if data.starts_with(PNG_SIGNATURE) {
+        read_png(&data)
+    } else if data.starts_with(&[0xFF, 0xD8]) {
+        read_jpeg(&data)
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        read_webp(&data)
+    } else {
+        Metadata::default()
+    };
+    
+    Ok(metadata)
+}
+
+// EXIF orientation of a PNG, JPEG or WebP source. Only block headers and the EXIF block are read,
+// pixel data and other metadata are seeked over
+pub fn read_orientation(path: &Path) -> Result<Option<u16>> {
+    let mut file = BufReader::new(fs::File::open(path)
+        .with_context(|| format!("Failed to read file: {}", path.display()))?);
+    match find_exif(&mut file) {
+        Ok(exif) => Ok(exif.as_deref().and_then(orientation)),
+        // Truncated or malformed files have no orientation we can trust
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("Failed to read file: {}", path.display())),
+    }
+}
+
+fn find_exif(file: &mut (impl Read + Seek)) -> io::Result<Option<Vec<u8>>> {
+    let mut start = [0u8; 12];
+    file.read_exact(&mut start[..2])?;
+    if start[..2] == [0xFF, 0xD8] {
+        loop {
+            let header = read_bytes(file, 4)?;
+            if header[0] != 0xFF || header[1] == 0xDA || header[1] == 0xD9 {
+                return Ok(None);
+            }
+            let length = (u16::from_be_bytes([header[2], header[3]]) as usize).saturating_sub(2);
+            if header[1] == 0xE1 {
+                let segment = read_bytes(file, length)?;
+                if let Some(exif) = segment.strip_prefix(JPEG_EXIF_HEADER) {
+                    return Ok(Some(exif.to_vec()));
+                }
+            } else {
+                file.seek_relative(length as i64)?;
+            }
+        }
+    }
+    
+    file.read_exact(&mut start[2..])?;
+    if start[..8] == *PNG_SIGNATURE {
+        file.seek_relative(-4)?;
+        loop {
+            let header = read_bytes(file, 8)?;
+            let length = u32::from_be_bytes(header[..4].try_into().unwrap()) as u64;
+            match &header[4..] {
+                b"eXIf" => return bounded_block(file, length),
+                b"IDAT" | b"IEND" => return Ok(None),
+                _ => file.seek_relative(length as i64 + 4)?,
+            }
+        }
+    }
+    if &start[..4] == b"RIFF" && &start[8..12] == b"WEBP" {
+        loop {
+            let header = read_bytes(file, 8)?;
+            let size = u32::from_le_bytes(header[4..].try_into().unwrap()) as u64;
+            if &header[..4] == b"EXIF" {
+                return bounded_block(file, size);
+            }
+            file.seek_relative((size + (size & 1)) as i64)?;
+        }
+    }
+    Ok(None)
+}
+
+fn read_bytes(file: &mut impl Read, length: usize) -> io::Result<Vec<u8>> {
+    let mut data = vec![0; length];
+    file.read_exact(&mut data)?;
+    Ok(data)
+}
+
+// An EXIF block sized by the file, blocks larger than MAX_INFLATED_BYTES are treated as absent
+fn bounded_block(file: &mut impl Read, length: u64) -> io::Result<Option<Vec<u8>>> {
+    if length > MAX_INFLATED_BYTES {
+        return Ok(None);
+    }
+    read_bytes(file, length as usize).map(Some)
+}
+
+fn read_png(data: &[u8]) -> Metadata {
+    let mut metadata = Metadata::default();
+    let mut pos = PNG_SIGNATURE.len();
+    
+    // Length, type, data and CRC per chunk, malformed tails are ignored
+    while pos + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let kind = &data[pos + 4..pos + 8];
+        let Some(chunk) = data.get(pos + 8..pos + 8 + length) else {
+            break;
+        };
+        
+        match kind {
+            // Profile name, null, compression method, zlib stream
+            b"iCCP" => {
+                if let Some(name_end) = chunk.iter().position(|&b| b == 0) {
+                    metadata.icc = inflate(chunk.get(name_end + 2..).unwrap_or(&[]));
+                }
+            }
+            b"eXIf" => metadata.exif = Some(chunk.to_vec()),
+            // Keyword, null, compression flag and method, language tag, null, translated keyword, null, text
+            b"iTXt" if chunk.starts_with(PNG_XMP_KEYWORD) && chunk.get(PNG_XMP_KEYWORD.len()) == Some(&0) => {
+                let rest = &chunk[PNG_XMP_KEYWORD.len() + 1..];
+                if rest.len() >= 2 {
+                    let compressed = rest[0] == 1;
+                    let mut text = &rest[2..];
+                    for _ in 0..2 {
+                        let end = text.iter().position(|&b| b == 0).unwrap_or(text.len());
+                        text = text.get(end + 1..).unwrap_or(&[]);
+                    }
+                    metadata.xmp = if compressed { inflate(text) } else { Some(text.to_vec()) };
+                }
+            }
+            b"IDAT" | b"IEND" => break,
+            _ => {}
+        }
+        
+        pos += length + 12;
+    }
+    
+    metadata
+}
+
+fn read_jpeg(data: &[u8]) -> Metadata {
+    let mut metadata = Metadata::default();
+    let mut icc_parts: Vec<(u8, &[u8])> = Vec::new();
+    let mut pos = 2;
+    
+    // Walk marker segments up to the start of scan, where entropy-coded data begins
+    while pos + 4 <= data.len() && data[pos] == 0xFF {
+        let marker = data[pos + 1];
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let Some(segment) = data.get(pos + 4..pos + 2 + length) else {
+            break;
+        };
+        
+        match marker {
+            0xE1 if segment.starts_with(JPEG_EXIF_HEADER) => {
+                metadata.exif = Some(segment[JPEG_EXIF_HEADER.len()..].to_vec());
+            }
+            0xE1 if segment.starts_with(JPEG_XMP_HEADER) => {
+                metadata.xmp = Some(segment[JPEG_XMP_HEADER.len()..].to_vec());
+            }
+            // Profiles over 64 KiB are split across segments with a sequence number
+            0xE2 if segment.starts_with(JPEG_ICC_HEADER) && segment.len() > JPEG_ICC_HEADER.len() + 2 => {
+                icc_parts.push((segment[JPEG_ICC_HEADER.len()], &segment[JPEG_ICC_HEADER.len() + 2..]));
+            }
+            _ => {}
+        }
+        
+        pos += 2 + length;
+    }
+    
+    if !icc_parts.is_empty() {
+        icc_parts.sort_by_key(|(sequence, _)| *sequence);
+        metadata.icc = Some(icc_parts.iter().flat_map(|(_, part)| part.iter().copied()).collect());
+    }
+    
+    metadata
+}
+
+fn read_webp(data: &[u8]) -> Metadata {
+    let mut metadata = Metadata::default();
+    let mut pos = 12;
+    
+    // RIFF chunks: fourcc, little-endian size, data padded to an even length
+    while pos + 8 <= data.len() {
+        let kind = &data[pos..pos + 4];
+        let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let Some(chunk) = data.get(pos + 8..pos + 8 + size) else {
+            break;
+        };
+        
+        match kind {
+            b"ICCP" => metadata.icc = Some(chunk.to_vec()),
+            b"EXIF" => metadata.exif = Some(chunk.to_vec()),
+            b"XMP " => metadata.xmp = Some(chunk.to_vec()),
+            _ => {}
+        }
+        
+        pos += 8 + size + (size & 1);
+    }
+    
+    metadata
+}
+
+// EXIF orientation (1-8, 1 is upright) from IFD0 of the TIFF structure
+pub fn orientation(exif: &[u8]) -> Option<u16> {
+    let (offset, big_endian) = orientation_offset(exif)?;
+    let bytes = [exif[offset], exif[offset + 1]];
+    Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+}
+
+// Mark the image as upright, for copies of EXIF whose orientation was already applied
+pub fn reset_orientation(exif: &mut [u8]) {
+    if let Some((offset, big_endian)) = orientation_offset(exif) {
+        let bytes = if big_endian { 1u16.to_be_bytes() } else { 1u16.to_le_bytes() };
+        exif[offset..offset + 2].copy_from_slice(&bytes);
+    }
+}
+
+// Position of the orientation tag's SHORT value, and whether the data is big-endian
+fn orientation_offset(exif: &[u8]) -> Option<(usize, bool)> {
+    let big_endian = match exif.get(..4)? {
+        b"MM\0*" => true,
+        b"II*\0" => false,
+        _ => return None,
+    };
+    let u16_at = |pos: usize| -> Option<u16> {
+        let bytes = [*exif.get(pos)?, *exif.get(pos + 1)?];
+        Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_at = |pos: usize| -> Option<u32> {
+        let bytes: [u8; 4] = exif.get(pos..pos + 4)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    };
+    
+    // IFD entries: tag, type, count and a 4-byte value field
+    let ifd = u32_at(4)? as usize;
+    for entry in 0..u16_at(ifd)? as usize {
+        let pos = ifd + 2 + entry * 12;
+        if u16_at(pos)? == EXIF_ORIENTATION_TAG && u16_at(pos + 2)? == EXIF_TYPE_SHORT {
+            u16_at(pos + 8)?;
+            return Some((pos + 8, big_endian));
+        }
+    }
+    
+    None
+}
+
+const EXIF_ORIENTATION_TAG: u16 = 0x0112;
+const EXIF_TYPE_SHORT: u16 = 3;
+
+// Larger than any real ICC profile, EXIF block or XMP packet, a small compressed chunk can't
+// expand past it
+const MAX_INFLATED_BYTES: u64 = 4 * 1024 * 1024;
+
+// Chunks inflating past MAX_INFLATED_BYTES are treated as absent
+fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    let mut output = Vec::new();
+    ZlibDecoder::new(data).take(MAX_INFLATED_BYTES + 1).read_to_end(&mut output).ok()?;
+    (output.len() as u64 <= MAX_INFLATED_BYTES).then_some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+    use std::io::Write;
+    
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+    
+    // Big-endian TIFF structure with one IFD0 entry, orientation 6
+    const EXIF: &[u8] = b"MM\0*\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0\x06\0\0\0\0\0\0";
+    
+    fn png_chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&crc32fast::hash(&[kind, data].concat()).to_be_bytes());
+        chunk
+    }
+    
+    fn orientations(data: &[u8]) -> (Option<u16>, Option<u16>) {
+        let full = read_png(data).exif.or(read_jpeg(data).exif).or(read_webp(data).exif);
+        let seeked = find_exif(&mut io::Cursor::new(data)).unwrap();
+        (full.as_deref().and_then(orientation), seeked.as_deref().and_then(orientation))
+    }
+    
+    #[test]
+    fn orientation_is_found_without_reading_everything() {
+        let png = [PNG_SIGNATURE.to_vec(), png_chunk(b"IHDR", &[0; 13]), png_chunk(b"tEXt", b"a\0b"),
+            png_chunk(b"eXIf", EXIF), png_chunk(b"IDAT", &[0; 20]), png_chunk(b"IEND", &[])].concat();
+        assert_eq!(orientations(&png), (Some(6), Some(6)));
+        
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0, 4, 0, 0, 0xFF, 0xE1];
+        jpeg.extend_from_slice(&((JPEG_EXIF_HEADER.len() + EXIF.len() + 2) as u16).to_be_bytes());
+        jpeg.extend_from_slice(JPEG_EXIF_HEADER);
+        jpeg.extend_from_slice(EXIF);
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0, 2]);
+        assert_eq!(find_exif(&mut io::Cursor::new(&jpeg)).unwrap().as_deref(), Some(EXIF));
+        
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8L\x03\0\0\0abc\0EXIF".to_vec();
+        webp.extend_from_slice(&(EXIF.len() as u32).to_le_bytes());
+        webp.extend_from_slice(EXIF);
+        assert_eq!(orientations(&webp), (Some(6), Some(6)));
+        
+        let without = [PNG_SIGNATURE.to_vec(), png_chunk(b"IHDR", &[0; 13]), png_chunk(b"IDAT", &[0; 20])].concat();
+        assert_eq!(find_exif(&mut io::Cursor::new(&without)).unwrap(), None);
+        assert!(find_exif(&mut io::Cursor::new(&png[..40])).is_err());
+    }
+    
+    #[test]
+    fn inflate_stops_at_the_limit() {
+        assert_eq!(inflate(&deflate(b"<x:xmpmeta/>")).as_deref(), Some(&b"<x:xmpmeta/>"[..]));
+        let bomb = deflate(&vec![0; MAX_INFLATED_BYTES as usize + 1]);
+        assert!(bomb.len() < 64 * 1024);
+        assert_eq!(inflate(&bomb), None);
+        assert_eq!(inflate(b"not zlib"), None);
+    }
+}
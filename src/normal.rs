@@ -0,0 +1,54 @@
+// Tangent-space normal map helpers
+use anyhow::{Result, anyhow};
+use image::{DynamicImage, ImageBuffer, Pixel, Primitive, Rgba, Rgba32FImage};
+
+use crate::{HeightToNormal, WrapMode};
+
+// Invert green in place, converting between DirectX (Y down) and OpenGL (Y up) normal maps
+pub fn flip_green(img: &mut DynamicImage) -> Result<()> {
+    match img {
+        DynamicImage::ImageRgb8(buffer) => invert_green(buffer),
+        DynamicImage::ImageRgba8(buffer) => invert_green(buffer),
+        DynamicImage::ImageRgb16(buffer) => invert_green(buffer),
+        DynamicImage::ImageRgba16(buffer) => invert_green(buffer),
+        DynamicImage::ImageRgb32F(buffer) => invert_green(buffer),
+        DynamicImage::ImageRgba32F(buffer) => invert_green(buffer),
+        _ => return Err(anyhow!("Image has no green channel to flip")),
+    }
+    Ok(())
+}
+
+fn invert_green<P: Pixel>(buffer: &mut ImageBuffer<P, Vec<P::Subpixel>>) {
+    let max = P::Subpixel::DEFAULT_MAX_VALUE;
+    for pixel in buffer.pixels_mut() {
+        let channels = pixel.channels_mut();
+        channels[1] = max - channels[1];
+    }
+}
+
+// Normal map from the red channel of a height image (0-1), slopes come from a 3x3 Sobel filter.
+// The result is the unit normal remapped to 0-1 with opaque alpha
+pub fn height_to_normal(height: &Rgba32FImage, settings: &HeightToNormal) -> Rgba32FImage {
+    let (width, rows) = (height.width() as i64, height.height() as i64);
+    let sample = |x: i64, y: i64| -> f32 {
+        let (x, y) = match settings.wrap {
+            WrapMode::Clamp => (x.clamp(0, width - 1), y.clamp(0, rows - 1)),
+            WrapMode::Repeat => (x.rem_euclid(width), y.rem_euclid(rows)),
+        };
+        height.get_pixel(x as u32, y as u32)[0]
+    };
+    
+    Rgba32FImage::from_fn(height.width(), height.height(), |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let dx = (sample(x + 1, y - 1) + 2.0 * sample(x + 1, y) + sample(x + 1, y + 1))
+            - (sample(x - 1, y - 1) + 2.0 * sample(x - 1, y) + sample(x - 1, y + 1));
+        let dy = (sample(x - 1, y + 1) + 2.0 * sample(x, y + 1) + sample(x + 1, y + 1))
+            - (sample(x - 1, y - 1) + 2.0 * sample(x, y - 1) + sample(x + 1, y - 1));
+        
+        // Rows run downwards, so an OpenGL (Y up) normal takes the row slope as is and DirectX negates it
+        let nx = -dx * settings.strength;
+        let ny = if settings.directx { -dy } else { dy } * settings.strength;
+        let length = (nx * nx + ny * ny + 1.0).sqrt();
+        Rgba([nx / length * 0.5 + 0.5, ny / length * 0.5 + 0.5, 1.0 / length * 0.5 + 0.5, 1.0])
+    })
+}
@@ -0,0 +1,291 @@
+// Packing the separate maps of texture sets into one texture each, the reverse of a split. A set is
+// the files of one folder whose names match the pack table's patterns with the same text for *
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow, Context};
+use image::{DynamicImage, Rgba32FImage};
+use regex::{Regex, RegexBuilder};
+use walkdir::WalkDir;
+
+use crate::{
+    PackConfig, PackSource, Profile, SizeMismatch, atomic, encode_image, glob_to_regex, is_hidden, is_layered_format,
+    open_pages, output_extension, render_output_name,
+};
+
+const CHANNEL_NAMES: [&str; 4] = ["r", "g", "b", "a"];
+
+pub fn validate(pack: &PackConfig, profile: &Profile) -> Result<()> {
+    let sources = pack.sources();
+    if !sources.iter().flatten().any(|source| file_pattern(source).is_some()) {
+        return Err(anyhow!("The pack table of profile '{}' reads no file, set r, g, b or a to a file pattern", profile.name));
+    }
+    for (name, source) in CHANNEL_NAMES.iter().zip(sources) {
+        match source {
+            Some(PackSource::Fill(value)) | Some(PackSource::Map { fill: Some(value), .. }) if !(0.0..=255.0).contains(value) => {
+                return Err(anyhow!("pack {} fills with {}, values are 0-255", name, value));
+            }
+            Some(PackSource::Copy { copy }) => match sources[*copy as usize] {
+                None if *copy as usize == 3 => return Err(anyhow!("pack {} copies a, which isn't set", name)),
+                Some(PackSource::Copy { .. }) => return Err(anyhow!("pack {} copies {}, which is a copy itself", name, CHANNEL_NAMES[*copy as usize])),
+                _ => {}
+            },
+            _ => {}
+        }
+        if let Some(pattern) = source.and_then(file_pattern) {
+            pattern_regex(pattern)?;
+        }
+    }
+    if let Some([width, height]) = pack.size && (width == 0 || height == 0) {
+        return Err(anyhow!("pack size is {}x{}, maps can't be resized to nothing", width, height));
+    }
+    Ok(())
+}
+
+fn file_pattern(source: &PackSource) -> Option<&str> {
+    match source {
+        PackSource::File(pattern) | PackSource::Map { file: pattern, .. } => Some(pattern),
+        _ => None,
+    }
+}
+
+// A file name pattern as a case-insensitive regex capturing the set name its first * stands for
+fn pattern_regex(pattern: &str) -> Result<Regex> {
+    let (before, after) = pattern.split_once('*')
+        .ok_or_else(|| anyhow!("pack file pattern '{}' has no * standing for the set name", pattern))?;
+    let part = |glob: &str| -> Result<String> {
+        let regex = glob_to_regex(glob)?;
+        let anchored = regex.as_str();
+        Ok(anchored[1..anchored.len() - 1].to_string())
+    };
+    RegexBuilder::new(&format!("^{}(.+?){}$", part(before)?, part(after)?))
+        .case_insensitive(true)
+        .build()
+        .with_context(|| format!("Invalid pack file pattern: {}", pattern))
+}
+
+// The maps of one set by destination channel, None where the set has no file for its pattern
+struct Set {
+    name: String,
+    dir: PathBuf,
+    files: [Option<PathBuf>; 4],
+}
+
+// The pack table of glTF's metallicRoughness textures: roughness in G, metallic in B and, as many
+// exporters share one texture for both, occlusion in R. The rest of the table is kept
+pub fn gltf_table(table: Option<&PackConfig>) -> PackConfig {
+    let file = |pattern: &str| Some(PackSource::File(pattern.to_string()));
+    PackConfig {
+        output_name: "{stem}_metallicRoughness".to_string(),
+        r: Some(PackSource::Map { file: "*_{AO,Occlusion,AmbientOcclusion}.*".to_string(), channel: None, fill: Some(255.0) }),
+        g: file("*_{Roughness,Rough}.*"),
+        b: file("*_{Metallic,Metalness,Metal}.*"),
+        a: None,
+        ..table.cloned().unwrap_or_else(|| Profile::default().pack.expect("the default profile has a pack table"))
+    }
+}
+
+// gltf checks the maps of each set against the metallicRoughness convention
+pub fn pack(inputs: &[PathBuf], profile: &Profile, gltf: bool) -> Result<()> {
+    let pack = profile.pack.as_ref()
+        .ok_or_else(|| anyhow!("Profile '{}' has no pack table saying how to pack its maps", profile.name))?;
+    // Checked here rather than in validate, so profiles splitting into layered formats can keep the default table
+    if is_layered_format(&profile.output_format) {
+        return Err(anyhow!("pack writes one color image, it can't use the layered format '{}'", profile.output_format));
+    }
+    if gltf && !matches!(profile.output_format.to_ascii_lowercase().as_str(), "png" | "jpg" | "jpeg") {
+        println!("Warning: glTF viewers without extensions only load PNG and JPEG textures, not {}", profile.output_format);
+    }
+    let sources = pack.sources();
+    let patterns: Vec<(usize, Regex)> = sources.iter().enumerate()
+        .filter_map(|(index, source)| source.and_then(file_pattern).map(|pattern| (index, pattern)))
+        .map(|(index, pattern)| Ok((index, pattern_regex(pattern)?)))
+        .collect::<Result<_>>()?;
+    
+    // Keyed by folder and lowercase set name, so maps differing only in case group together
+    let mut sets: BTreeMap<(PathBuf, String), Set> = BTreeMap::new();
+    for file in candidate_files(inputs, profile) {
+        let name = file.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let dir = file.parent().unwrap_or(Path::new(".")).to_path_buf();
+        for (index, regex) in &patterns {
+            let Some(captures) = regex.captures(&name) else {
+                continue;
+            };
+            let set = sets.entry((dir.clone(), captures[1].to_lowercase()))
+                .or_insert_with(|| Set { name: captures[1].to_string(), dir: dir.clone(), files: Default::default() });
+            match &set.files[*index] {
+                Some(earlier) => println!("Warning: {} and {} both match the {} pattern of set {}, using the first",
+                    earlier.display(), file.display(), CHANNEL_NAMES[*index], set.name),
+                None => set.files[*index] = Some(file.clone()),
+            }
+        }
+    }
+    if sets.is_empty() {
+        return Err(anyhow!("No maps match the pack patterns of profile '{}'", profile.name));
+    }
+    
+    let (mut packed, mut skipped) = (0, 0);
+    for set in sets.values() {
+        match pack_set(set, pack, profile, gltf)? {
+            Some(output) => {
+                packed += 1;
+                println!("Packed set {} to: {}", set.name, output.display());
+            }
+            None => skipped += 1,
+        }
+    }
+    println!("Packed {} sets, skipped {}", packed, skipped);
+    Ok(())
+}
+
+// Files given directly, plus the images of directories with the profile's extensions
+fn candidate_files(inputs: &[PathBuf], profile: &Profile) -> Vec<PathBuf> {
+    let extensions = profile.allowed_extensions();
+    let mut files = Vec::new();
+    for input in inputs {
+        if !input.is_dir() {
+            files.push(input.clone());
+            continue;
+        }
+        let mut walker = WalkDir::new(input).follow_links(profile.follow_symlinks).sort_by_file_name();
+        if let Some(max_depth) = profile.max_depth {
+            walker = walker.max_depth(max_depth);
+        }
+        files.extend(walker
+            .into_iter()
+            .filter_entry(|entry| entry.depth() == 0 || profile.include_hidden || !is_hidden(entry))
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .filter(|path| path.extension().is_some_and(|ext| extensions.contains(&ext.to_string_lossy().to_ascii_lowercase()))));
+    }
+    files
+}
+
+// Write the packed texture of a set next to its maps, None when the set was skipped
+fn pack_set(set: &Set, pack: &PackConfig, profile: &Profile, gltf: bool) -> Result<Option<PathBuf>> {
+    let sources = pack.sources();
+    let missing: Vec<&str> = (0..4)
+        .filter(|&index| set.files[index].is_none() && matches!(sources[index], Some(PackSource::File(_) | PackSource::Map { fill: None, .. })))
+        .map(|index| CHANNEL_NAMES[index])
+        .collect();
+    if !missing.is_empty() {
+        println!("Skipping set {}: no map for {} and no fill value to use instead", set.name, missing.join(", "));
+        return Ok(None);
+    }
+    
+    let max_alloc = profile.max_alloc();
+    let mut maps: Vec<Option<DynamicImage>> = Vec::new();
+    for file in &set.files {
+        let img = match file {
+            Some(file) => Some(open_pages(file, max_alloc, profile.psd_layer.as_ref())
+                .with_context(|| format!("Failed to open image: {}", file.display()))?
+                .remove(0)),
+            None => None,
+        };
+        maps.push(img);
+    }
+    
+    // The explicit size applies to every set, the policy only to sets whose maps differ
+    let sizes: Vec<(&PathBuf, (u32, u32))> = set.files.iter().zip(&maps)
+        .filter_map(|(file, img)| Some((file.as_ref()?, img.as_ref().map(|img| (img.width(), img.height()))?)))
+        .collect();
+    let area = |(_, (width, height)): &&(&PathBuf, (u32, u32))| *width as u64 * *height as u64;
+    let (width, height) = match (pack.size, pack.mismatch) {
+        (Some([width, height]), _) => (width, height),
+        _ if sizes.iter().all(|(_, size)| *size == sizes[0].1) => sizes[0].1,
+        (None, SizeMismatch::Error) => {
+            let listed: Vec<String> = sizes.iter()
+                .map(|(file, (width, height))| format!("{} is {}x{}", file.display(), width, height))
+                .collect();
+            return Err(anyhow!("Maps of set {} differ in size: {}. Set the pack table's mismatch to resize-to-largest or resize-to-smallest, or its size, to resize them",
+                set.name, listed.join(", ")));
+        }
+        (None, SizeMismatch::ResizeToLargest) => sizes.iter().max_by_key(area).unwrap().1,
+        (None, SizeMismatch::ResizeToSmallest) => sizes.iter().min_by_key(area).unwrap().1,
+    };
+    for (file, img) in set.files.iter().zip(&mut maps) {
+        if let (Some(file), Some(img)) = (file, img) && (img.width(), img.height()) != (width, height) {
+            println!("Resizing {} from {}x{} to {}x{}", file.display(), img.width(), img.height(), width, height);
+            *img = img.resize_exact(width, height, pack.filter.filter_type());
+        }
+    }
+    
+    // Copies are filled in after the channels they copy
+    let values: Vec<Option<Rgba32FImage>> = maps.iter().map(|img| img.as_ref().map(DynamicImage::to_rgba32f)).collect();
+    if gltf {
+        warn_inverted(set, &values);
+    }
+    let mut packed = Rgba32FImage::from_pixel(width, height, image::Rgba([0.0, 0.0, 0.0, 1.0]));
+    for (index, source) in sources.iter().enumerate() {
+        let channel = match source {
+            Some(PackSource::File(_)) => 0,
+            Some(PackSource::Map { channel, .. }) => channel.map_or(0, |channel| channel as usize),
+            Some(PackSource::Fill(_)) | Some(PackSource::Copy { .. }) | None => continue,
+        };
+        match &values[index] {
+            Some(map) => packed.pixels_mut().zip(map.pixels()).for_each(|(pixel, value)| pixel[index] = value[channel]),
+            None => fill(&mut packed, index, sources[index]),
+        }
+    }
+    for (index, source) in sources.iter().enumerate() {
+        match source {
+            Some(PackSource::Fill(_)) => fill(&mut packed, index, *source),
+            Some(PackSource::Copy { copy }) => packed.pixels_mut().for_each(|pixel| pixel[index] = pixel[*copy as usize]),
+            _ => {}
+        }
+    }
+    
+    // The deepest map decides the sample depth where the format can store it, alpha is only kept
+    // when a is mapped
+    let eight_bit = matches!(output_extension(&profile.output_format), "jpg" | "jpeg" | "tga" | "webp");
+    let depth = maps.iter().flatten().map(|img| img.color().bytes_per_pixel() / img.color().channel_count()).max().unwrap_or(1);
+    let depth = if eight_bit { 1 } else { depth };
+    let packed = DynamicImage::ImageRgba32F(packed);
+    let img = match (depth, sources[3].is_some()) {
+        (1, true) => DynamicImage::ImageRgba8(packed.to_rgba8()),
+        (1, false) => DynamicImage::ImageRgb8(packed.to_rgb8()),
+        (2, true) => DynamicImage::ImageRgba16(packed.to_rgba16()),
+        (2, false) => DynamicImage::ImageRgb16(packed.to_rgb16()),
+        (_, true) => packed,
+        (_, false) => DynamicImage::ImageRgb32F(packed.to_rgb32f()),
+    };
+    
+    let vars = HashMap::from([("stem".to_string(), set.name.clone())]);
+    let stem = render_output_name(&pack.output_name, &vars)?;
+    let output = set.dir.join(format!("{}.{}", stem, output_extension(&profile.output_format)));
+    let data = encode_image(&img, &profile.output_format, profile, None, None)
+        .with_context(|| format!("Failed to encode packed texture of set {}", set.name))?;
+    atomic::write(&output, data)
+        .with_context(|| format!("Failed to write file: {}", output.display()))?;
+    Ok(Some(output))
+}
+
+// Set a destination channel to the fill value of its source, in 0-255 units
+fn fill(packed: &mut Rgba32FImage, index: usize, source: Option<&PackSource>) {
+    let value = match source {
+        Some(PackSource::Fill(value)) | Some(PackSource::Map { fill: Some(value), .. }) => *value / 255.0,
+        _ => return,
+    };
+    packed.pixels_mut().for_each(|pixel| pixel[index] = value);
+}
+
+// Maps that look like the opposite of what glTF expects there, like glossiness given as roughness.
+// They may be right, so they're only warned about
+fn warn_inverted(set: &Set, values: &[Option<Rgba32FImage>]) {
+    let stats = |index: usize| values[index].as_ref().map(|map| {
+        let count = (map.width() as u64 * map.height() as u64).max(1) as f32;
+        let mean = map.pixels().map(|pixel| pixel[0]).sum::<f32>() / count;
+        let metal = map.pixels().filter(|pixel| pixel[0] > 0.5).count() as f32 / count;
+        (mean, metal)
+    });
+    if let Some((mean, _)) = stats(0) && mean < 0.25 {
+        println!("Warning: occlusion map of set {} is mostly dark (mean {:.2}), occlusion is white where nothing occludes, check that it isn't inverted", set.name, mean);
+    }
+    if let Some((mean, _)) = stats(1) && mean < 0.15 {
+        println!("Warning: roughness map of set {} is almost black (mean {:.2}), glossiness or smoothness maps must be inverted to be used as roughness", set.name, mean);
+    }
+    if let Some((_, metal)) = stats(2) && metal > 0.9 {
+        println!("Warning: metallic map of set {} is metal in {:.0}% of the texture, check that it isn't inverted", set.name, metal * 100.0);
+    }
+}
@@ -0,0 +1,252 @@
+// In-memory splitting for embedders: images or encoded bytes in, channel images or encoded
+// files out, without going through the filesystem
+//
+//     let processor = Processor::builder().profile(profile).grayscale(true).build()?;
+//     for channel in processor.split(&image) { ... }
+//
+// Async services run jobs on their own threads instead of blocking executor threads:
+//
+//     let job = processor.spawn_split_bytes(data, name);
+//     let files = tokio::task::spawn_blocking(move || job.join()).await??;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+
+use anyhow::{Result, anyhow, Context};
+use image::{DynamicImage, ImageBuffer, Luma, LumaA};
+use image::io::Limits;
+
+use crate::encode::encode_image;
+use crate::{CancelToken, FileMatcher, Profile, ProgressSink, extract_page, load_channel_resources, named_captures, process_input, output_extension, render_output_name, validate_profile};
+
+pub struct ProcessorBuilder {
+    profile: Option<Profile>,
+    resource_dir: PathBuf,
+    grayscale: bool,
+    cancel: CancelToken,
+}
+
+impl ProcessorBuilder {
+    // The profile to split with, the built-in ORM profile if unset
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+    
+    // Folder relative LUT paths of the profile start at, the current directory by default
+    pub fn resource_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.resource_dir = dir.as_ref().to_path_buf();
+        self
+    }
+    
+    // Return single-channel images (Luma, or LumaA when the channel gets an alpha) from split
+    // instead of RGBA with equal R, G and B. Float and normal map outputs stay RGBA
+    pub fn grayscale(mut self, grayscale: bool) -> Self {
+        self.grayscale = grayscale;
+        self
+    }
+    
+    // Token that stops process early when cancelled from another thread
+    pub fn cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+    
+    // Load the profile's LUTs and expressions and check its settings, as the executable does
+    pub fn build(self) -> Result<Processor> {
+        let mut profile = self.profile.unwrap_or_default();
+        load_channel_resources(&mut profile, &self.resource_dir)?;
+        validate_profile(&profile)?;
+        let matcher = FileMatcher::new(&profile)?;
+        Ok(Processor { profile, matcher, grayscale: self.grayscale, cancel: self.cancel })
+    }
+}
+
+// A channel of a split image, format is the one the profile would encode it in
+pub struct ChannelImage {
+    pub channel: String,
+    pub format: String,
+    pub image: DynamicImage,
+}
+
+#[derive(Clone)]
+pub struct Processor {
+    profile: Profile,
+    matcher: FileMatcher,
+    grayscale: bool,
+    cancel: CancelToken,
+}
+
+impl Processor {
+    pub fn builder() -> ProcessorBuilder {
+        ProcessorBuilder { profile: None, resource_dir: PathBuf::from("."), grayscale: false, cancel: CancelToken::new() }
+    }
+    
+    // Extract every channel with its adjustments and alpha, in profile order. Channels of a
+    // layered output format come last, as 8-bit images or float for exr-multichannel
+    pub fn split(&self, img: &DynamicImage) -> Vec<ChannelImage> {
+        let (outputs, layers) = extract_page(img, &self.profile);
+        let mut channels: Vec<ChannelImage> = outputs.into_iter()
+            .map(|(channel, format, image)| {
+                let has_alpha = self.profile.alpha.is_some()
+                    || self.profile.channels.iter().any(|other| other.into_alpha_of.as_ref() == Some(&channel.name));
+                let image = if self.grayscale && channel.height_to_normal.is_none() { single_channel(image, has_alpha) } else { image };
+                ChannelImage { channel: channel.name.clone(), format: format.to_string(), image }
+            })
+            .collect();
+        for (channel, layer) in layers {
+            let image = match layer {
+                DynamicImage::ImageLuma8(_) if !self.grayscale => DynamicImage::ImageRgba8(layer.to_rgba8()),
+                layer => layer,
+            };
+            channels.push(ChannelImage { channel, format: self.profile.output_format.clone(), image });
+        }
+        channels
+    }
+    
+    // Split a file, or the matching files under a directory, next to themselves like the executable
+    // does. Run-wide outputs (report, stats CSV, the shared contact sheet) aren't written. Stops
+    // scheduling files after the first failure, which is returned, or once cancel_token is cancelled
+    pub fn process(&self, input: &Path, progress: &dyn ProgressSink) -> Result<()> {
+        process_input(input, &self.profile, &mut Vec::new(), progress, &self.cancel)
+    }
+    
+    // Split an encoded image and encode the channels, as (file name, data) pairs named by
+    // output_name. The extension of name picks the decoder. Layered formats are written through
+    // files, so they're refused here
+    pub fn split_bytes(&self, data: &[u8], name: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let profile = &self.profile;
+        let file = Path::new(name);
+        let extension = file.extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+            .ok_or_else(|| anyhow!("{} has no extension to pick a decoder from", name))?;
+        let format = image::ImageFormat::from_extension(&extension)
+            .ok_or_else(|| anyhow!("Can't decode .{} files in memory", extension))?;
+        let mut reader = image::io::Reader::with_format(Cursor::new(data), format);
+        if let Some(max_alloc) = profile.max_alloc() {
+            let mut limits = Limits::default();
+            limits.max_alloc = Some(max_alloc);
+            reader.limits(limits);
+        }
+        let img = reader.decode().with_context(|| format!("Failed to decode {}", name))?;
+        
+        let mut vars = named_captures(&self.matcher.regex, name);
+        vars.insert("stem".to_string(), self.matcher.stem(file));
+        
+        let (outputs, layers) = extract_page(&img, profile);
+        if !layers.is_empty() {
+            return Err(anyhow!("Layered output formats can't be written in memory, use a regular format"));
+        }
+        let mut encoded = Vec::new();
+        for (channel, format, channel_img) in outputs {
+            vars.insert("channel".to_string(), channel.name.clone());
+            let output_name = format!("{}.{}", render_output_name(&profile.output_name, &vars)?, output_extension(format));
+            let data = encode_image(&channel_img, format, profile, channel.color_space, None)
+                .with_context(|| format!("Failed to encode {}", output_name))?;
+            encoded.push((output_name, data));
+        }
+        Ok(encoded)
+    }
+    
+    // process on a thread of its own. The job has its own cancel token, so cancelling it leaves
+    // other jobs and calls of this processor running
+    pub fn spawn_process(&self, input: PathBuf, progress: impl ProgressSink + Send + 'static) -> Job<()> {
+        let cancel = CancelToken::new();
+        let processor = Processor { cancel: cancel.clone(), ..self.clone() };
+        Job::spawn(cancel, move || processor.process(&input, &progress))
+    }
+    
+    // split_bytes on a thread of its own
+    pub fn spawn_split_bytes(&self, data: Vec<u8>, name: String) -> Job<Vec<(String, Vec<u8>)>> {
+        let processor = self.clone();
+        Job::spawn(CancelToken::new(), move || processor.split_bytes(&data, &name))
+    }
+}
+
+// A split running on its own thread. join blocks, so async code calls it from spawn_blocking, or
+// polls with is_finished. A panic in the job becomes its error
+pub struct Job<T> {
+    handle: JoinHandle<Result<T>>,
+    cancel: CancelToken,
+}
+
+impl<T: Send + 'static> Job<T> {
+    fn spawn(cancel: CancelToken, work: impl FnOnce() -> Result<T> + Send + 'static) -> Self {
+        Job { handle: thread::spawn(work), cancel }
+    }
+    
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+    
+    // Stop the job like Ctrl-C stops a run, join still waits for the files in flight
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+    
+    pub fn join(self) -> Result<T> {
+        self.handle.join().map_err(|_| anyhow!("The job panicked"))?
+    }
+    
+    // The result if the job is finished, the job back otherwise
+    pub fn try_join(self) -> std::result::Result<Result<T>, Self> {
+        if self.is_finished() { Ok(self.join()) } else { Err(self) }
+    }
+}
+
+// Keep R (equal to G and B in channel outputs) and optionally alpha of an integer RGBA image
+fn single_channel(img: DynamicImage, has_alpha: bool) -> DynamicImage {
+    match (img, has_alpha) {
+        (DynamicImage::ImageRgba8(buffer), false) => DynamicImage::ImageLuma8(
+            ImageBuffer::from_fn(buffer.width(), buffer.height(), |x, y| Luma([buffer.get_pixel(x, y)[0]]))),
+        (DynamicImage::ImageRgba8(buffer), true) => DynamicImage::ImageLumaA8(
+            ImageBuffer::from_fn(buffer.width(), buffer.height(), |x, y| {
+                let pixel = buffer.get_pixel(x, y);
+                LumaA([pixel[0], pixel[3]])
+            })),
+        (DynamicImage::ImageRgba16(buffer), false) => DynamicImage::ImageLuma16(
+            ImageBuffer::from_fn(buffer.width(), buffer.height(), |x, y| Luma([buffer.get_pixel(x, y)[0]]))),
+        (DynamicImage::ImageRgba16(buffer), true) => DynamicImage::ImageLumaA16(
+            ImageBuffer::from_fn(buffer.width(), buffer.height(), |x, y| {
+                let pixel = buffer.get_pixel(x, y);
+                LumaA([pixel[0], pixel[3]])
+            })),
+        (img, _) => img,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, RgbaImage, Rgba};
+    
+    fn encoded_png() -> Vec<u8> {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 255])));
+        let mut data = Vec::new();
+        img.write_to(&mut Cursor::new(&mut data), ImageFormat::Png).unwrap();
+        data
+    }
+    
+    #[test]
+    fn jobs_match_blocking_calls() {
+        let processor = Processor::builder().build().unwrap();
+        let data = encoded_png();
+        let expected = processor.split_bytes(&data, "Rock_ORM.png").unwrap();
+        let mut job = processor.spawn_split_bytes(data, "Rock_ORM.png".to_string());
+        let files = loop {
+            match job.try_join() {
+                Ok(result) => break result.unwrap(),
+                Err(running) => job = running,
+            }
+        };
+        assert_eq!(files, expected);
+        assert_eq!(files.len(), 3);
+    }
+    
+    #[test]
+    fn job_errors_are_returned_by_join() {
+        let processor = Processor::builder().build().unwrap();
+        let job = processor.spawn_split_bytes(b"not an image".to_vec(), "Rock_ORM.png".to_string());
+        assert!(job.join().is_err());
+    }
+}
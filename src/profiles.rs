@@ -0,0 +1,557 @@
+// The profile command: adding profiles to config.toml without editing TOML by hand, and sharing
+// them as .ormprofile bundles
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow, Context};
+use clap::Subcommand;
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+
+use crate::json;
+use crate::lut::Lut;
+use crate::selfupdate::download;
+use crate::{
+    ChannelConfig, Config, FileMatcher, Profile, atomic, is_layered_format, load_channel_resources, matching_files,
+    output_extension, relative_path, validate_profile,
+};
+
+#[derive(Subcommand)]
+pub enum ProfileAction {
+    /// Add a profile to config.toml, a copy of the built-in ORM profile unless --interactive
+    New {
+        /// Name of the profile (asked for with --interactive)
+        name: Option<String>,
+        
+        /// Walk through the file pattern, channels, output format and options step by step
+        #[arg(short, long)]
+        interactive: bool,
+        
+        /// Folder of sample textures to try the file pattern on while choosing it
+        #[arg(long, value_name = "DIR")]
+        sample: Option<PathBuf>,
+    },
+    
+    /// Write profiles and the LUT files they use to a .ormprofile bundle to share
+    Export {
+        /// Profile to export
+        #[arg(required_unless_present = "all")]
+        name: Option<String>,
+        
+        /// Export every profile of config.toml
+        #[arg(long, conflicts_with = "name")]
+        all: bool,
+        
+        /// Bundle to write (default: <name>.ormprofile, or profiles.ormprofile with --all)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    
+    /// Add the profiles of a .ormprofile bundle, or of any file with [profiles.<name>] tables, to config.toml
+    Import {
+        /// Bundle file, or an https:// URL to download it from
+        source: String,
+        
+        /// What to do with profiles named like one already in config.toml
+        #[arg(long, value_enum, default_value = "ask")]
+        on_conflict: Conflict,
+        
+        /// Import from a URL without showing what will be added and asking first
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Conflict {
+    /// Ask for each profile, failing when there's no one to answer
+    Ask,
+    /// Replace the profile in config.toml
+    Overwrite,
+    /// Keep the profile in config.toml and leave the imported one out
+    Skip,
+    /// Import the profile under a free name like rma-2
+    Rename,
+}
+
+// A .ormprofile file. LUTs are keyed by the path their channels give, so a bundle doesn't depend on
+// files of the machine it was exported on. Other keys are ignored, so a config.toml imports too
+#[derive(Serialize, Deserialize, Default)]
+struct Bundle {
+    profiles: BTreeMap<String, Profile>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    luts: BTreeMap<String, String>,
+}
+
+const BUNDLE_HEADER: &str = "# ormseparatev3 profile bundle, add it to config.toml with: ormseparatev3 profile import <FILE>\n\n";
+
+pub fn profile(action: &ProfileAction, config: &Config, config_path: &Path, exe_dir: &Path) -> Result<()> {
+    match action {
+        ProfileAction::New { name, interactive: false, .. } => {
+            let name = name.as_deref().ok_or_else(|| anyhow!("Give the new profile a name, or use --interactive"))?;
+            check_new_name(name, config)?;
+            let profile = Profile { name: name.to_string(), ..Profile::default() };
+            write_profiles(config_path, &[], &[(name, &profile)])?;
+            println!("Added profile '{}' to {}, a copy of the built-in ORM profile", name, config_path.display());
+            Ok(())
+        }
+        ProfileAction::New { name, interactive: true, sample } => {
+            let profile = wizard(name.as_deref(), sample.as_deref(), config, exe_dir)?;
+            write_profiles(config_path, &[], &[(&profile.name, &profile)])?;
+            println!();
+            println!("Added profile '{}' to {}", profile.name, config_path.display());
+            println!("Use it with: ormseparatev3 --profile {} <INPUTS>...", profile.name);
+            Ok(())
+        }
+        ProfileAction::Export { name, all, output } => export(name.as_deref(), *all, output.as_deref(), config, exe_dir),
+        ProfileAction::Import { source, on_conflict, yes } => {
+            let is_url = source.starts_with("https://") || source.starts_with("http://");
+            let text = if is_url {
+                if !source.starts_with("https://") {
+                    return Err(anyhow!("Profiles are only downloaded over https: {}", source));
+                }
+                String::from_utf8(download(source)?).with_context(|| format!("{} isn't a text file", source))?
+            } else {
+                fs::read_to_string(source).with_context(|| format!("Failed to read profile bundle: {}", source))?
+            };
+            let bundle = toml::from_str(&text)
+                .with_context(|| format!("Invalid profile bundle: {}", source))?;
+            // Downloaded profiles can run shell commands, so they're shown before they're added
+            import(bundle, source, *on_conflict, is_url && !yes, config, config_path, exe_dir)
+        }
+    }
+}
+
+// Every profile with its file pattern, output settings and channels, sorted by name
+pub fn list_json(config: &Config) -> String {
+    let optional = |value: &Option<String>| value.as_deref().map_or("null".to_string(), json::string);
+    let mut names: Vec<&String> = config.profiles.keys().collect();
+    names.sort();
+    let profiles: Vec<String> = names.iter().map(|name| {
+        let profile = &config.profiles[*name];
+        let channels: Vec<String> = profile.channels.iter().map(|channel| format!(
+            "{{\"name\":{},\"channel\":{},\"expr\":{},\"reconstruct_z\":{},\"into_alpha_of\":{},\"semantic\":{},\"output_format\":{}}}",
+            json::string(&channel.name), channel.channel, optional(&channel.expr), channel.reconstruct_z,
+            optional(&channel.into_alpha_of), json::string(&channel.semantic()), json::string(channel.format(profile)),
+        )).collect();
+        format!(
+            "{{\"name\":{},\"file_regex\":{},\"file_glob\":{},\"output_format\":{},\"output_name\":{},\"channels\":[{}]}}",
+            json::string(name), optional(&profile.file_regex), optional(&profile.file_glob),
+            json::string(&profile.output_format), json::string(&profile.output_name), channels.join(","),
+        )
+    }).collect();
+    format!("{{\"default\":{},\"profiles\":[{}]}}", json::string(&config.default_profile), profiles.join(","))
+}
+
+// Profile names become folder names under luts/, so they can't climb out of it
+fn check_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() {
+        return Err(anyhow!("Profile names can't be empty"));
+    }
+    if name.contains(['/', '\\', ':']) || name.contains("..") || name == "." {
+        return Err(anyhow!("Profile name '{}' can't contain '/', '\\', ':' or '..'", name));
+    }
+    Ok(())
+}
+
+fn check_new_name(name: &str, config: &Config) -> Result<()> {
+    check_name(name)?;
+    if config.profiles.contains_key(name) {
+        return Err(anyhow!("Profile '{}' already exists in config.toml", name));
+    }
+    Ok(())
+}
+
+// Profiles are appended as their own [profiles.<name>] tables, and replaced ones cut out of the
+// text, so comments and formatting of the rest of config.toml are kept. The result is parsed
+// again before it's written
+fn write_profiles(config_path: &Path, replace: &[&str], add: &[(&str, &Profile)]) -> Result<()> {
+    let content = updated_config(config_path, replace, add)?;
+    fs::write(config_path, content)
+        .with_context(|| format!("Failed to write config file: {}", config_path.display()))
+}
+
+fn updated_config(config_path: &Path, replace: &[&str], add: &[(&str, &Profile)]) -> Result<String> {
+    let table = HashMap::from([("profiles", add.iter().copied().collect::<BTreeMap<_, _>>())]);
+    let addition = toml::to_string_pretty(&table).context("Failed to serialize profile")?;
+    let mut existing = fs::read_to_string(config_path)
+        .with_context(|| format!("Failed to read config file: {}", config_path.display()))?;
+    for name in replace {
+        existing = without_profile(&existing, name);
+    }
+    let separator = if existing.is_empty() || existing.ends_with("\n\n") { "" } else if existing.ends_with('\n') { "\n" } else { "\n\n" };
+    let content = format!("{}{}{}", existing, separator, addition);
+    toml::from_str::<Config>(&content)
+        .with_context(|| format!("Writing the profiles would leave {} unreadable, edit it by hand", config_path.display()))?;
+    Ok(content)
+}
+
+// config.toml without the [profiles.<name>] table of a profile and its subtables. Comments at the
+// end of a removed table are kept when the table after it stays, as they likely belong to that one.
+// Profiles written another way are left in, which write_profiles then catches as a duplicate
+fn without_profile(text: &str, name: &str) -> String {
+    let tables = [format!("profiles.{}", name), format!("profiles.\"{}\"", name), format!("profiles.'{}'", name)];
+    let mut removing = false;
+    let mut kept = String::new();
+    let mut comments = String::new();
+    for line in text.split_inclusive('\n') {
+        let line_start = line.trim_start();
+        if line_start.starts_with('[') {
+            let path = line_start.trim_start_matches('[').split(']').next().unwrap_or_default().trim();
+            removing = tables.iter().any(|table| path.strip_prefix(table.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with('.')));
+            if !removing {
+                kept.push_str(&comments);
+            }
+            comments.clear();
+        }
+        if !removing {
+            kept.push_str(line);
+        } else if line_start.is_empty() || line_start.starts_with('#') {
+            comments.push_str(line);
+        } else {
+            comments.clear();
+        }
+    }
+    kept + &comments
+}
+
+fn export(name: Option<&str>, all: bool, output: Option<&Path>, config: &Config, exe_dir: &Path) -> Result<()> {
+    let names: Vec<String> = match name {
+        Some(name) if !all => vec![name.to_string()],
+        _ => {
+            let mut names: Vec<String> = config.profiles.keys().cloned().collect();
+            names.sort();
+            names
+        }
+    };
+    let mut bundle = Bundle::default();
+    for name in &names {
+        let profile = config.get_profile(Some(name))?;
+        for lut in profile.channels.iter().filter_map(|channel| channel.lut.as_ref()) {
+            if let Entry::Vacant(entry) = bundle.luts.entry(lut.to_string_lossy().into_owned()) {
+                let path = exe_dir.join(lut);
+                let text = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read LUT file of profile '{}': {}", name, path.display()))?;
+                entry.insert(text);
+            }
+        }
+        bundle.profiles.insert(name.clone(), profile);
+    }
+    
+    let output = match (output, name) {
+        (Some(output), _) => output.to_path_buf(),
+        (None, Some(name)) if !all => PathBuf::from(format!("{}.ormprofile", name)),
+        (None, _) => PathBuf::from("profiles.ormprofile"),
+    };
+    let content = format!("{}{}", BUNDLE_HEADER, toml::to_string_pretty(&bundle).context("Failed to serialize profiles")?);
+    atomic::write(&output, content).with_context(|| format!("Failed to write profile bundle: {}", output.display()))?;
+    println!("Exported {} to {}", names.iter().map(|name| format!("'{}'", name)).collect::<Vec<_>>().join(", "), output.display());
+    for lut in bundle.luts.keys() {
+        println!("  Included LUT file: {}", lut);
+    }
+    Ok(())
+}
+
+// A profile as listed before a download is imported, with anything that runs commands called out
+fn describe(profile: &Profile, overwrite: bool) {
+    let pattern = profile.file_glob.as_ref().or(profile.file_regex.as_ref()).map_or(String::new(), |pattern| format!(", files '{}'", pattern));
+    let channels: Vec<&str> = profile.channels.iter().map(|channel| channel.name.as_str()).collect();
+    let replacing = if overwrite { ", replacing the existing profile" } else { "" };
+    println!("  Profile '{}': {} as {}{}{}", profile.name, channels.join(", "), profile.output_format, pattern, replacing);
+    for (when, command) in [("before", &profile.pre_command), ("after", &profile.post_command)] {
+        if let Some(command) = command {
+            println!("    Runs {} each output is written: {}", when, command);
+        }
+    }
+}
+
+// What happens to an imported profile
+enum Resolution {
+    Add(String),
+    Overwrite(String),
+    Skip,
+}
+
+// The first of name-2, name-3 and so on that no profile uses
+fn free_name(name: &str, taken: &HashSet<String>) -> String {
+    (2..).map(|n| format!("{}-{}", name, n)).find(|candidate| !taken.contains(candidate)).unwrap()
+}
+
+fn resolve(name: &str, on_conflict: Conflict, taken: &HashSet<String>) -> Result<Resolution> {
+    if !taken.contains(name) {
+        return Ok(Resolution::Add(name.to_string()));
+    }
+    match on_conflict {
+        Conflict::Overwrite => return Ok(Resolution::Overwrite(name.to_string())),
+        Conflict::Skip => return Ok(Resolution::Skip),
+        Conflict::Rename => return Ok(Resolution::Add(free_name(name, taken))),
+        Conflict::Ask => {}
+    }
+    loop {
+        let answer = ask(&format!("Profile '{}' already exists, overwrite, skip or rename it?", name), "skip")?;
+        match answer.to_lowercase().as_str() {
+            "o" | "overwrite" => return Ok(Resolution::Overwrite(name.to_string())),
+            "s" | "skip" => return Ok(Resolution::Skip),
+            "r" | "rename" => loop {
+                let new_name = ask("New name", &free_name(name, taken))?;
+                if taken.contains(&new_name) {
+                    println!("  Profile '{}' already exists too", new_name);
+                } else if let Err(err) = check_name(&new_name) {
+                    println!("  {}", err);
+                } else {
+                    return Ok(Resolution::Add(new_name));
+                }
+            },
+            _ => println!("  Answer overwrite, skip or rename"),
+        }
+    }
+}
+
+// Bundled LUTs are written to luts/<profile>/ next to config.toml, and the profile's channels
+// pointed there. Every profile and the new config.toml are validated before anything is written,
+// and LUT files written for a config.toml that then can't be saved are removed again
+fn import(bundle: Bundle, source: &str, on_conflict: Conflict, ask_first: bool, config: &Config, config_path: &Path, exe_dir: &Path) -> Result<()> {
+    if bundle.profiles.is_empty() {
+        return Err(anyhow!("No profiles in {}", source));
+    }
+    let mut taken: HashSet<String> = config.profiles.keys().cloned().collect();
+    let mut imported = Vec::new();
+    let mut luts: Vec<(PathBuf, &str)> = Vec::new();
+    for (name, mut profile) in bundle.profiles {
+        check_name(&name).with_context(|| format!("Invalid profile in {}, nothing was imported", source))?;
+        let (new_name, overwrite) = match resolve(&name, on_conflict, &taken)? {
+            Resolution::Add(new_name) => (new_name, false),
+            Resolution::Overwrite(new_name) => (new_name, true),
+            Resolution::Skip => {
+                println!("Skipped profile '{}', config.toml already has one", name);
+                continue;
+            }
+        };
+        profile.name = new_name.clone();
+        
+        let mut checked = profile.clone();
+        for (channel, checked_channel) in profile.channels.iter_mut().zip(&mut checked.channels) {
+            let Some(text) = channel.lut.as_ref().and_then(|lut| bundle.luts.get(lut.to_string_lossy().as_ref())) else {
+                continue;
+            };
+            let file_name = channel.lut.as_ref().and_then(|lut| lut.file_name()).map(PathBuf::from)
+                .ok_or_else(|| anyhow!("Channel {} of profile '{}' has a LUT without a file name", channel.name, name))?;
+            let lut = Path::new("luts").join(&new_name).join(file_name);
+            checked_channel.lut_curve = Some(Lut::parse(text, &lut)
+                .with_context(|| format!("Invalid LUT of profile '{}' in {}", name, source))?);
+            checked_channel.lut = None;
+            
+            let path = exe_dir.join(&lut);
+            let clashes = luts.iter().any(|(other, other_text)| *other == path && other_text != text) ||
+                (!overwrite && fs::read_to_string(&path).is_ok_and(|existing| existing != *text));
+            if clashes {
+                return Err(anyhow!("{} already exists with other contents, nothing was imported", path.display()));
+            }
+            luts.push((path, text));
+            channel.lut = Some(lut);
+        }
+        load_channel_resources(&mut checked, exe_dir)
+            .and_then(|_| validate_profile(&checked))
+            .and_then(|_| FileMatcher::new(&checked).map(|_| ()))
+            .with_context(|| format!("Profile '{}' in {} isn't valid, nothing was imported", name, source))?;
+        
+        taken.insert(new_name.clone());
+        imported.push((name, profile, overwrite));
+    }
+    if imported.is_empty() {
+        println!("Nothing to import");
+        return Ok(());
+    }
+    if ask_first {
+        println!("{} adds to {}:", source, config_path.display());
+        for (_, profile, overwrite) in &imported {
+            describe(profile, *overwrite);
+        }
+        for (path, _) in &luts {
+            println!("  LUT file {}", path.display());
+        }
+        if !confirm("Import these profiles?", false)? {
+            println!("Nothing was imported");
+            return Ok(());
+        }
+    }
+    
+    let replace: Vec<&str> = imported.iter().filter(|(_, _, overwrite)| *overwrite).map(|(_, profile, _)| profile.name.as_str()).collect();
+    let add: Vec<(&str, &Profile)> = imported.iter().map(|(_, profile, _)| (profile.name.as_str(), profile)).collect();
+    let content = updated_config(config_path, &replace, &add)?;
+    let mut created = Vec::new();
+    let written = luts.iter().try_for_each(|(path, text)| {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create LUT folder: {}", parent.display()))?;
+        }
+        if !path.exists() {
+            created.push(path);
+        }
+        fs::write(path, text).with_context(|| format!("Failed to write LUT file: {}", path.display()))
+    }).and_then(|_| fs::write(config_path, content)
+        .with_context(|| format!("Failed to write config file: {}", config_path.display())));
+    if written.is_err() {
+        for path in created {
+            let _ = fs::remove_file(path);
+        }
+    }
+    written?;
+    for (name, profile, overwrite) in &imported {
+        let channels: Vec<&str> = profile.channels.iter().map(|channel| channel.name.as_str()).collect();
+        let channels = channels.join(", ");
+        match (overwrite, *name == profile.name) {
+            (true, _) => println!("Imported profile '{}' ({}), replacing the one in config.toml", name, channels),
+            (false, true) => println!("Imported profile '{}' ({})", name, channels),
+            (false, false) => println!("Imported profile '{}' as '{}' ({})", name, profile.name, channels),
+        }
+    }
+    Ok(())
+}
+
+// A line typed by the user, or the default when nothing is
+fn ask(question: &str, default: &str) -> Result<String> {
+    if default.is_empty() {
+        print!("{}: ", question);
+    } else {
+        print!("{} [{}]: ", question, default);
+    }
+    io::stdout().flush().context("Failed to flush stdout")?;
+    let mut input = String::new();
+    if io::stdin().lock().read_line(&mut input).context("Failed to read user input")? == 0 {
+        return Err(anyhow!("Input ended before every question was answered, nothing was written"));
+    }
+    let input = input.trim();
+    Ok(if input.is_empty() { default.to_string() } else { input.to_string() })
+}
+
+fn confirm(question: &str, default: bool) -> Result<bool> {
+    loop {
+        let answer = ask(&format!("{} ({})", question, if default { "Y/n" } else { "y/N" }), "")?;
+        match answer.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("  Answer y or n"),
+        }
+    }
+}
+
+// Map names in R, G, B and A suggested by the wizard
+const DEFAULT_MAPS: [&str; 4] = ["Occlusion", "Roughness", "Metallic", ""];
+const CHANNEL_NAMES: [&str; 4] = ["red", "green", "blue", "alpha"];
+
+fn wizard(name: Option<&str>, sample: Option<&Path>, config: &Config, exe_dir: &Path) -> Result<Profile> {
+    println!("Creating a new profile, press Enter to keep the value in brackets");
+    println!();
+    
+    let name = match name {
+        Some(name) => {
+            check_new_name(name, config)?;
+            name.to_string()
+        }
+        None => loop {
+            let name = ask("Profile name", "")?;
+            match check_new_name(&name, config) {
+                Ok(()) => break name,
+                Err(err) => println!("  {}", err),
+            }
+        },
+    };
+    let mut profile = Profile { name: name.clone(), ..Profile::default() };
+    
+    // The pattern is tried on the sample folder until the user keeps it
+    let sample = match sample {
+        Some(sample) => Some(sample.to_path_buf()),
+        None => Some(ask("Folder of sample textures to try the file pattern on (empty to skip)", "")?)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from),
+    };
+    if let Some(dir) = &sample && !dir.is_dir() {
+        println!("  {} is not a folder, the pattern won't be tried", dir.display());
+    }
+    let mut default_pattern = format!("*_{}.*", name.to_uppercase());
+    loop {
+        let pattern = ask("File pattern, a glob like *_ORM.* or a regex like /orm/i", &default_pattern)?;
+        (profile.file_regex, profile.file_glob) = if pattern.starts_with('/') { (Some(pattern.clone()), None) } else { (None, Some(pattern.clone())) };
+        let matcher = match FileMatcher::new(&profile) {
+            Ok(matcher) => matcher,
+            Err(err) => {
+                println!("  {:#}", err);
+                continue;
+            }
+        };
+        let Some(dir) = sample.as_deref().filter(|dir| dir.is_dir()) else {
+            break;
+        };
+        let matched = matching_files(dir, &profile, &matcher);
+        println!("  Matches {} files in {}", matched.len(), dir.display());
+        for file in matched.iter().take(10) {
+            println!("    {}", relative_path(file, dir));
+        }
+        if matched.len() > 10 {
+            println!("    ... and {} more", matched.len() - 10);
+        }
+        if confirm("Use this pattern?", !matched.is_empty())? {
+            break;
+        }
+        default_pattern = pattern;
+    }
+    
+    println!();
+    println!("Name the map each channel holds, '-' leaves a channel out");
+    let mut channels = Vec::new();
+    for (index, (channel, default)) in CHANNEL_NAMES.iter().zip(DEFAULT_MAPS).enumerate() {
+        let map = ask(&format!("Map in the {} channel", channel), default)?;
+        if map.is_empty() || map == "-" {
+            continue;
+        }
+        // Alpha has no channel index of its own, it's read through an expression
+        let (channel, expr) = if index == 3 { (0, Some("a".to_string())) } else { (index, None) };
+        channels.push(ChannelConfig { name: map, channel, expr, ..Default::default() });
+    }
+    if channels.is_empty() {
+        return Err(anyhow!("The profile needs at least one channel, nothing was written"));
+    }
+    profile.channels = channels;
+    
+    println!();
+    loop {
+        let format = ask("Output format (png, tga, tif, exr, jpg, webp, or layered: tiff-layered, psd-layered, exr-multichannel, ktx2-array)", "png")?.to_lowercase();
+        let writable = is_layered_format(&format) ||
+            ImageFormat::from_extension(output_extension(&format)).is_some_and(|format| format.writing_enabled());
+        if writable {
+            profile.output_format = format;
+            break;
+        }
+        println!("  {} can't be written", format);
+    }
+    profile.output_name = ask("Output file name, {stem} is the source's and {channel} the map's name", &profile.output_name)?;
+    profile.skip_constant = confirm("Leave out maps with one value everywhere, like an all-black metallic?", false)?;
+    profile.strip_suffix = Some(ask("Packing suffix removed from {stem}, like _ORM (empty for none)", "")?)
+        .filter(|suffix| !suffix.is_empty())
+        .into_iter()
+        .collect();
+    
+    let mut checked = profile.clone();
+    load_channel_resources(&mut checked, exe_dir)?;
+    validate_profile(&checked).context("The profile isn't valid, nothing was written")?;
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn profile_names_stay_inside_luts() {
+        for name in ["orm", "rma-2", "my.profile"] {
+            assert!(check_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", " ", ".", "..", "../../x", "a/b", "a\\b", "c:x", "a..b"] {
+            assert!(check_name(name).is_err(), "{}", name);
+        }
+    }
+}
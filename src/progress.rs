@@ -0,0 +1,35 @@
+// Events of a run for embedders that draw their own progress, see Processor::process. Files are
+// processed in parallel, so file and channel events arrive from worker threads as they happen;
+// log lines arrive in input order, one at a time
+use std::path::Path;
+use std::time::Duration;
+
+pub trait ProgressSink: Sync {
+    // A line the executable would print
+    fn log(&self, _line: &str) {}
+    
+    // index counts from 0 in the files of the current input, total of which are processed
+    fn file_started(&self, _file: &Path, _index: usize, _total: usize) {}
+    
+    // A channel output (or the layered file) was written or linked, elapsed since its channel started
+    fn channel_done(&self, _file: &Path, _channel: &str, _output: &Path, _elapsed: Duration) {}
+    
+    // Also sent for files skipped by size or decoder limits
+    fn file_done(&self, _file: &Path, _index: usize, _total: usize, _elapsed: Duration) {}
+    
+    fn file_failed(&self, _file: &Path, _index: usize, _total: usize, _error: &anyhow::Error, _elapsed: Duration) {}
+}
+
+// For callers that only want the result
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {}
+
+// The executable's output
+pub struct Console;
+
+impl ProgressSink for Console {
+    fn log(&self, line: &str) {
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,80 @@
+// Where a channel output came from, written as {output}.json next to it or into PNG outputs as a
+// tEXt chunk, so a map found anywhere can be traced back to its source and settings
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Result, Context};
+
+use crate::hash::{hex, sha256};
+use crate::json;
+
+// Keyword of the tEXt chunk, the text is the same JSON as the sidecar
+pub const PNG_KEYWORD: &str = "ormseparatev3:provenance";
+
+// The parts shared by every output of one source
+pub struct Provenance {
+    source: String,
+    source_sha256: String,
+    profile: String,
+    created: String,
+}
+
+impl Provenance {
+    pub fn of(file: &Path, profile: &str) -> Result<Self> {
+        let data = fs::read(file).with_context(|| format!("Failed to read {} for its hash", file.display()))?;
+        let source = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+        Ok(Provenance {
+            source: source.to_string_lossy().into_owned(),
+            source_sha256: hex(&sha256(&data)),
+            profile: profile.to_string(),
+            created: timestamp(SystemTime::now()),
+        })
+    }
+    
+    // channel_index is the source channel (0 = R, 1 = G, 2 = B), None for computed channels
+    pub fn to_json(&self, channel: &str, channel_index: Option<usize>) -> String {
+        format!(
+            "{{\"source\":{},\"source_sha256\":\"{}\",\"profile\":{},\"channel\":{},\"channel_index\":{},\"tool\":\"ormseparatev3 {}\",\"created\":\"{}\"}}\n",
+            json::string(&self.source), self.source_sha256, json::string(&self.profile), json::string(channel),
+            channel_index.map_or("null".to_string(), |index| index.to_string()), env!("CARGO_PKG_VERSION"), self.created,
+        )
+    }
+    
+    // Keyword, null separator, Latin-1 text. Paths outside Latin-1 are kept as JSON escapes
+    pub fn png_chunk(&self, channel: &str, channel_index: Option<usize>) -> ([u8; 4], Vec<u8>) {
+        let mut data = PNG_KEYWORD.as_bytes().to_vec();
+        data.push(0);
+        for c in self.to_json(channel, channel_index).trim_end().chars() {
+            match c {
+                c if (c as u32) < 0x100 => data.push(c as u8),
+                c => {
+                    let mut units = [0; 2];
+                    for unit in c.encode_utf16(&mut units) {
+                        data.extend_from_slice(format!("\\u{:04x}", unit).as_bytes());
+                    }
+                }
+            }
+        }
+        (*b"tEXt", data)
+    }
+}
+
+// UTC in RFC 3339 form, e.g. 2024-05-01T12:30:00Z
+fn timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0);
+    let (days, rest) = (seconds / 86400, seconds % 86400);
+    
+    // Days since the epoch to a proleptic Gregorian date, with eras of 400 years from March 1st
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, rest / 3600, rest % 3600 / 60, rest % 60)
+}
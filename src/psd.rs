@@ -0,0 +1,647 @@
+// Minimal PSD/PSB reader: the merged composite or a single layer as an RGBA image
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Result, anyhow, Context};
+use flate2::read::ZlibDecoder;
+use image::{DynamicImage, GrayImage, ImageBuffer};
+use serde::{Deserialize, Serialize};
+
+// Which part of a PSD to treat as the packed source
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum LayerSelector {
+    // Counts layers with pixel content from the bottom, starting at 0
+    Index(usize),
+    Name(String),
+}
+
+impl LayerSelector {
+    // Numbers select by index, anything else by name
+    pub fn parse(value: &str) -> Self {
+        match value.parse() {
+            Ok(index) => LayerSelector::Index(index),
+            Err(_) => LayerSelector::Name(value.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for LayerSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayerSelector::Index(index) => write!(f, "#{}", index),
+            LayerSelector::Name(name) => write!(f, "'{}'", name),
+        }
+    }
+}
+
+// Color modes we can turn into RGB
+const COLOR_MODE_GRAYSCALE: u16 = 1;
+const COLOR_MODE_RGB: u16 = 3;
+
+struct Header {
+    // 1 = PSD, 2 = PSB (large document format)
+    version: u16,
+    channels: u16,
+    height: u32,
+    width: u32,
+    depth: u16,
+    color_mode: u16,
+}
+
+impl Header {
+    fn parse(cursor: &mut Cursor) -> Result<Self> {
+        if cursor.take(4)? != b"8BPS" {
+            return Err(anyhow!("Not a PSD file"));
+        }
+        
+        let version = cursor.u16()?;
+        if version != 1 && version != 2 {
+            return Err(anyhow!("Unsupported PSD version: {}", version));
+        }
+        cursor.skip(6)?;
+        
+        Ok(Header {
+            version,
+            channels: cursor.u16()?,
+            height: cursor.u32()?,
+            width: cursor.u32()?,
+            depth: cursor.u16()?,
+            color_mode: cursor.u16()?,
+        })
+    }
+    
+    fn is_psb(&self) -> bool {
+        self.version == 2
+    }
+    
+    // Section and channel lengths are 64-bit in PSB files
+    fn read_length(&self, cursor: &mut Cursor) -> Result<u64> {
+        if self.is_psb() {
+            cursor.u64()
+        } else {
+            cursor.u32().map(u64::from)
+        }
+    }
+}
+
+struct LayerRecord {
+    name: String,
+    top: i32,
+    left: i32,
+    bottom: i32,
+    right: i32,
+    // Channel id (0 = R, 1 = G, 2 = B, -1 = alpha, below that masks) and data length
+    channels: Vec<(i16, u64)>,
+    // Group start/end markers carry no pixels of their own
+    is_group_marker: bool,
+}
+
+impl LayerRecord {
+    fn width(&self) -> u32 {
+        (self.right - self.left).max(0) as u32
+    }
+    
+    fn height(&self) -> u32 {
+        (self.bottom - self.top).max(0) as u32
+    }
+}
+
+// Read only the canvas size from the header
+pub fn dimensions(path: &Path) -> Result<(u32, u32)> {
+    let mut buffer = [0; 26];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut buffer))
+        .with_context(|| format!("Failed to read PSD header: {}", path.display()))?;
+    
+    let header = Header::parse(&mut Cursor::new(&buffer))?;
+    Ok((header.width, header.height))
+}
+
+// Decode the merged composite, or the selected layer placed on a transparent canvas
+pub fn open(path: &Path, layer: Option<&LayerSelector>) -> Result<DynamicImage> {
+    let data = fs::read(path)
+        .with_context(|| format!("Failed to read PSD file: {}", path.display()))?;
+    let mut cursor = Cursor::new(&data);
+    
+    let header = Header::parse(&mut cursor)?;
+    if header.color_mode != COLOR_MODE_RGB && header.color_mode != COLOR_MODE_GRAYSCALE {
+        return Err(anyhow!("Unsupported PSD color mode {} (only RGB and grayscale are supported)", header.color_mode));
+    }
+    if !matches!(header.depth, 8 | 16 | 32) {
+        return Err(anyhow!("Unsupported PSD bit depth: {}", header.depth));
+    }
+    
+    // Color mode data and image resources aren't needed
+    let color_mode_len = cursor.u32()?;
+    cursor.skip(color_mode_len as usize)?;
+    let resources_len = cursor.u32()?;
+    cursor.skip(resources_len as usize)?;
+    
+    let layer_section_len = header.read_length(&mut cursor)? as usize;
+    let layer_section = cursor.take(layer_section_len)?;
+    
+    match layer {
+        Some(selector) => decode_layer(&header, layer_section, selector),
+        None => decode_composite(&header, &mut cursor),
+    }
+}
+
+fn decode_composite(header: &Header, cursor: &mut Cursor) -> Result<DynamicImage> {
+    let (width, height) = (header.width as usize, header.height as usize);
+    let row_bytes = width * bytes_per_sample(header.depth);
+    let compression = cursor.u16()?;
+    
+    // Color channels plus one alpha channel at most
+    let wanted = if header.color_mode == COLOR_MODE_RGB { 4 } else { 2 };
+    let channels = (header.channels as usize).min(wanted);
+    
+    let planes = match compression {
+        0 => (0..channels)
+            .map(|_| cursor.take(row_bytes * height).map(<[u8]>::to_vec))
+            .collect::<Result<Vec<_>>>()?,
+        1 => {
+            // Byte counts of every row of every channel come first
+            let counts = (0..header.channels as usize * height)
+                .map(|_| if header.is_psb() { cursor.u32() } else { cursor.u16().map(u32::from) })
+                .collect::<Result<Vec<_>>>()?;
+            
+            let mut planes = Vec::new();
+            for channel_counts in counts.chunks(height).take(channels) {
+                let mut plane = Vec::with_capacity(row_bytes * height);
+                for &count in channel_counts {
+                    unpack_bits(cursor.take(count as usize)?, row_bytes, &mut plane)?;
+                }
+                planes.push(plane);
+            }
+            planes
+        }
+        _ => return Err(anyhow!("Unsupported PSD composite compression: {}", compression)),
+    };
+    
+    let alpha = planes.get(wanted - 1).map(Vec::as_slice);
+    let color: Vec<&[u8]> = planes.iter().take(wanted - 1).map(Vec::as_slice).collect();
+    assemble(header, &color, alpha, (0, 0, header.width, header.height))
+}
+
+fn decode_layer(header: &Header, section: &[u8], selector: &LayerSelector) -> Result<DynamicImage> {
+    if section.is_empty() {
+        return Err(anyhow!("PSD file has no layers, cannot select layer {}", selector));
+    }
+    
+    let mut cursor = Cursor::new(section);
+    let info_len = header.read_length(&mut cursor)? as usize;
+    let mut info = Cursor::new(cursor.take(info_len)?);
+    
+    // A negative count means the first alpha channel holds the merged transparency
+    let count = info.i16()?.unsigned_abs() as usize;
+    let records = (0..count)
+        .map(|_| parse_layer_record(header, &mut info))
+        .collect::<Result<Vec<_>>>()?;
+    
+    // Channel image data follows all records, in the same order
+    let mut offsets = Vec::with_capacity(records.len());
+    for record in &records {
+        offsets.push(info.pos);
+        let layer_len: u64 = record.channels.iter().map(|(_, len)| len).sum();
+        info.skip(layer_len as usize)?;
+    }
+    
+    let candidates: Vec<usize> = (0..records.len())
+        .filter(|&i| !records[i].is_group_marker)
+        .collect();
+    let chosen = match selector {
+        LayerSelector::Index(index) => candidates.get(*index).copied(),
+        LayerSelector::Name(name) => candidates.iter().copied().find(|&i| &records[i].name == name),
+    };
+    let Some(chosen) = chosen else {
+        let names: Vec<String> = candidates.iter()
+            .enumerate()
+            .map(|(index, &i)| format!("{}: {}", index, records[i].name))
+            .collect();
+        return Err(anyhow!("Layer {} not found in PSD. Available layers: {}", selector, names.join(", ")));
+    };
+    
+    let record = &records[chosen];
+    if record.width() == 0 || record.height() == 0 {
+        return Err(anyhow!("Layer '{}' has no pixel data", record.name));
+    }
+    
+    let mut data = Cursor::new(info.data);
+    data.pos = offsets[chosen];
+    
+    let mut planes: [Option<Vec<u8>>; 4] = [None, None, None, None];
+    for &(id, len) in &record.channels {
+        let channel_data = data.take(len as usize)?;
+        let slot = match id {
+            0..=2 => id as usize,
+            -1 => 3,
+            // Masks are ignored
+            _ => continue,
+        };
+        planes[slot] = Some(decode_channel(header, channel_data, record.width(), record.height())?);
+    }
+    
+    let color_count = if header.color_mode == COLOR_MODE_RGB { 3 } else { 1 };
+    let color = planes[..color_count].iter()
+        .map(|plane| plane.as_deref().ok_or_else(|| anyhow!("Layer '{}' is missing a color channel", record.name)))
+        .collect::<Result<Vec<_>>>()?;
+    
+    let rect = (record.left, record.top, record.width(), record.height());
+    assemble(header, &color, planes[3].as_deref(), rect)
+}
+
+fn parse_layer_record(header: &Header, cursor: &mut Cursor) -> Result<LayerRecord> {
+    let top = cursor.i32()?;
+    let left = cursor.i32()?;
+    let bottom = cursor.i32()?;
+    let right = cursor.i32()?;
+    
+    let channel_count = cursor.u16()?;
+    let channels = (0..channel_count)
+        .map(|_| Ok((cursor.i16()?, header.read_length(cursor)?)))
+        .collect::<Result<Vec<_>>>()?;
+    
+    // Blend mode signature and key, opacity, clipping, flags, filler
+    cursor.skip(12)?;
+    
+    let extra_len = cursor.u32()? as usize;
+    let mut extra = Cursor::new(cursor.take(extra_len)?);
+    let mask_len = extra.u32()?;
+    extra.skip(mask_len as usize)?;
+    let ranges_len = extra.u32()?;
+    extra.skip(ranges_len as usize)?;
+    
+    // Pascal string padded to a multiple of 4 bytes
+    let name_len = extra.u8()? as usize;
+    let mut name = String::from_utf8_lossy(extra.take(name_len)?).into_owned();
+    extra.skip((4 - (name_len + 1) % 4) % 4)?;
+    
+    let mut is_group_marker = false;
+    while extra.remaining() >= 12 {
+        let signature = extra.take(4)?;
+        if signature != b"8BIM" && signature != b"8B64" {
+            break;
+        }
+        let key = extra.take(4)?;
+        let len = extra.u32()? as usize;
+        let mut block = Cursor::new(extra.take(len.min(extra.remaining()))?);
+        
+        match key {
+            // Unicode layer name
+            b"luni" => {
+                let chars = block.u32()? as usize;
+                let units = (0..chars)
+                    .map(|_| block.u16())
+                    .collect::<Result<Vec<_>>>()?;
+                name = String::from_utf16_lossy(&units);
+            }
+            // Section divider: 1/2 = open/closed group, 3 = end of group
+            b"lsct" | b"lsdk" => {
+                is_group_marker = matches!(block.u32()?, 1..=3);
+            }
+            _ => {}
+        }
+    }
+    
+    Ok(LayerRecord { name, top, left, bottom, right, channels, is_group_marker })
+}
+
+// Write grayscale images as named layers of an 8-bit grayscale PSD
+// The first image becomes the top layer and the merged preview
+pub fn write_layers(path: &Path, layers: &[(String, GrayImage)]) -> Result<()> {
+    let (width, height) = layers.first()
+        .map(|(_, layer)| layer.dimensions())
+        .ok_or_else(|| anyhow!("No layers to write"))?;
+    let plane_len = width as usize * height as usize;
+    
+    let mut out = Vec::new();
+    out.extend_from_slice(b"8BPS");
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out.extend_from_slice(&[0; 6]);
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out.extend_from_slice(&height.to_be_bytes());
+    out.extend_from_slice(&width.to_be_bytes());
+    out.extend_from_slice(&8u16.to_be_bytes());
+    out.extend_from_slice(&COLOR_MODE_GRAYSCALE.to_be_bytes());
+    
+    // No color mode data or image resources
+    out.extend_from_slice(&0u32.to_be_bytes());
+    out.extend_from_slice(&0u32.to_be_bytes());
+    
+    // Layers are stored bottom first
+    let mut records = Vec::new();
+    let mut channel_data = Vec::new();
+    let channel_len = (2 + plane_len) as u32;
+    for (name, layer) in layers.iter().rev() {
+        records.extend_from_slice(&0i32.to_be_bytes());
+        records.extend_from_slice(&0i32.to_be_bytes());
+        records.extend_from_slice(&(height as i32).to_be_bytes());
+        records.extend_from_slice(&(width as i32).to_be_bytes());
+        
+        // Transparency and gray channels, both uncompressed
+        records.extend_from_slice(&2u16.to_be_bytes());
+        records.extend_from_slice(&(-1i16).to_be_bytes());
+        records.extend_from_slice(&channel_len.to_be_bytes());
+        records.extend_from_slice(&0i16.to_be_bytes());
+        records.extend_from_slice(&channel_len.to_be_bytes());
+        
+        // Normal blending, full opacity, visible
+        records.extend_from_slice(b"8BIMnorm");
+        records.extend_from_slice(&[255, 0, 0, 0]);
+        
+        let mut extra = Vec::new();
+        extra.extend_from_slice(&0u32.to_be_bytes());
+        extra.extend_from_slice(&0u32.to_be_bytes());
+        let ascii: Vec<u8> = name.bytes().filter(u8::is_ascii).take(255).collect();
+        extra.push(ascii.len() as u8);
+        extra.extend_from_slice(&ascii);
+        while extra.len() % 4 != 0 {
+            extra.push(0);
+        }
+        
+        // The Pascal name above is ASCII only, this keeps the full name
+        let units: Vec<u16> = name.encode_utf16().collect();
+        let mut unicode = Vec::new();
+        unicode.extend_from_slice(&(units.len() as u32).to_be_bytes());
+        for unit in &units {
+            unicode.extend_from_slice(&unit.to_be_bytes());
+        }
+        if unicode.len() % 4 != 0 {
+            unicode.resize(unicode.len() + 4 - unicode.len() % 4, 0);
+        }
+        extra.extend_from_slice(b"8BIMluni");
+        extra.extend_from_slice(&(unicode.len() as u32).to_be_bytes());
+        extra.extend_from_slice(&unicode);
+        
+        records.extend_from_slice(&(extra.len() as u32).to_be_bytes());
+        records.extend_from_slice(&extra);
+        
+        channel_data.extend_from_slice(&0u16.to_be_bytes());
+        channel_data.extend(std::iter::repeat_n(u8::MAX, plane_len));
+        channel_data.extend_from_slice(&0u16.to_be_bytes());
+        channel_data.extend_from_slice(layer.as_raw());
+    }
+    
+    let mut layer_info = Vec::new();
+    layer_info.extend_from_slice(&(layers.len() as i16).to_be_bytes());
+    layer_info.extend_from_slice(&records);
+    layer_info.extend_from_slice(&channel_data);
+    if layer_info.len() % 2 != 0 {
+        layer_info.push(0);
+    }
+    
+    // Layer info plus an empty global layer mask
+    out.extend_from_slice(&(layer_info.len() as u32 + 8).to_be_bytes());
+    out.extend_from_slice(&(layer_info.len() as u32).to_be_bytes());
+    out.extend_from_slice(&layer_info);
+    out.extend_from_slice(&0u32.to_be_bytes());
+    
+    // Merged image data for readers that don't understand layers
+    out.extend_from_slice(&0u16.to_be_bytes());
+    out.extend_from_slice(layers[0].1.as_raw());
+    
+    fs::write(path, out)?;
+    Ok(())
+}
+
+// Decode one layer channel (compression marker included) into big-endian samples
+fn decode_channel(header: &Header, data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    let mut cursor = Cursor::new(data);
+    let compression = cursor.u16()?;
+    let sample_bytes = bytes_per_sample(header.depth);
+    let row_bytes = width as usize * sample_bytes;
+    let size = row_bytes * height as usize;
+    
+    match compression {
+        0 => Ok(cursor.take(size)?.to_vec()),
+        1 => {
+            let counts = (0..height)
+                .map(|_| if header.is_psb() { cursor.u32() } else { cursor.u16().map(u32::from) })
+                .collect::<Result<Vec<_>>>()?;
+            
+            let mut plane = Vec::with_capacity(size);
+            for count in counts {
+                unpack_bits(cursor.take(count as usize)?, row_bytes, &mut plane)?;
+            }
+            Ok(plane)
+        }
+        2 | 3 => {
+            let mut plane = Vec::with_capacity(size);
+            ZlibDecoder::new(cursor.take(cursor.remaining())?)
+                .read_to_end(&mut plane)
+                .context("Failed to inflate PSD channel data")?;
+            if plane.len() < size {
+                return Err(anyhow!("Truncated PSD channel data"));
+            }
+            plane.truncate(size);
+            
+            if compression == 3 {
+                undo_prediction(&mut plane, row_bytes, header.depth)?;
+            }
+            Ok(plane)
+        }
+        _ => Err(anyhow!("Unsupported PSD channel compression: {}", compression)),
+    }
+}
+
+// PackBits run-length decoding of a single row
+fn unpack_bits(mut input: &[u8], row_bytes: usize, output: &mut Vec<u8>) -> Result<()> {
+    let end = output.len() + row_bytes;
+    
+    while output.len() < end && !input.is_empty() {
+        let header = input[0] as i8;
+        input = &input[1..];
+        
+        if header >= 0 {
+            let count = header as usize + 1;
+            let literal = input.get(..count).ok_or_else(|| anyhow!("Truncated PSD RLE data"))?;
+            output.extend_from_slice(literal);
+            input = &input[count..];
+        } else if header != -128 {
+            let count = 1 - header as isize;
+            let value = *input.first().ok_or_else(|| anyhow!("Truncated PSD RLE data"))?;
+            output.extend(std::iter::repeat_n(value, count as usize));
+            input = &input[1..];
+        }
+    }
+    
+    if output.len() != end {
+        return Err(anyhow!("Corrupt PSD RLE data"));
+    }
+    Ok(())
+}
+
+// ZIP-with-prediction stores each row as deltas of the previous sample
+fn undo_prediction(plane: &mut [u8], row_bytes: usize, depth: u16) -> Result<()> {
+    for row in plane.chunks_mut(row_bytes) {
+        match depth {
+            8 => {
+                for i in 1..row.len() {
+                    row[i] = row[i].wrapping_add(row[i - 1]);
+                }
+            }
+            16 => {
+                for i in (2..row.len()).step_by(2) {
+                    let previous = u16::from_be_bytes([row[i - 2], row[i - 1]]);
+                    let value = u16::from_be_bytes([row[i], row[i + 1]]).wrapping_add(previous);
+                    row[i..i + 2].copy_from_slice(&value.to_be_bytes());
+                }
+            }
+            _ => return Err(anyhow!("ZIP prediction is not supported for {}-bit PSD files", depth)),
+        }
+    }
+    Ok(())
+}
+
+fn bytes_per_sample(depth: u16) -> usize {
+    depth as usize / 8
+}
+
+// Place planar channel data covering rect (left, top, width, height) onto the canvas
+fn assemble(header: &Header, color: &[&[u8]], alpha: Option<&[u8]>, rect: (i32, i32, u32, u32)) -> Result<DynamicImage> {
+    let (width, height) = (header.width, header.height);
+    
+    match header.depth {
+        8 => {
+            let pixels = compose(width, height, color, alpha, rect, 1, |b| b[0], u8::MAX);
+            ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8)
+        }
+        16 => {
+            let pixels = compose(width, height, color, alpha, rect, 2, |b| u16::from_be_bytes([b[0], b[1]]), u16::MAX);
+            ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgba16)
+        }
+        _ => {
+            let pixels = compose(width, height, color, alpha, rect, 4, |b| f32::from_be_bytes([b[0], b[1], b[2], b[3]]), 1.0);
+            ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgba32F)
+        }
+    }.ok_or_else(|| anyhow!("Truncated PSD image data"))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn compose<T: Copy + Default>(
+    width: u32,
+    height: u32,
+    color: &[&[u8]],
+    alpha: Option<&[u8]>,
+    (left, top, rect_width, rect_height): (i32, i32, u32, u32),
+    sample_bytes: usize,
+    read: impl Fn(&[u8]) -> T,
+    opaque: T,
+) -> Vec<T> {
+    let mut pixels = vec![T::default(); width as usize * height as usize * 4];
+    
+    for y in 0..rect_height as i64 {
+        let canvas_y = top as i64 + y;
+        if canvas_y < 0 || canvas_y >= height as i64 {
+            continue;
+        }
+        for x in 0..rect_width as i64 {
+            let canvas_x = left as i64 + x;
+            if canvas_x < 0 || canvas_x >= width as i64 {
+                continue;
+            }
+            
+            let src = (y as usize * rect_width as usize + x as usize) * sample_bytes;
+            let dst = (canvas_y as usize * width as usize + canvas_x as usize) * 4;
+            for c in 0..3 {
+                // Grayscale documents have a single plane for all three
+                let plane = color[c.min(color.len() - 1)];
+                pixels[dst + c] = read(&plane[src..src + sample_bytes]);
+            }
+            pixels[dst + 3] = alpha.map_or(opaque, |plane| read(&plane[src..src + sample_bytes]));
+        }
+    }
+    
+    pixels
+}
+
+// Big-endian reader over a byte slice
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Cursor { data, pos: 0 }
+    }
+    
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+    
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.remaining() {
+            return Err(anyhow!("Unexpected end of PSD data"));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+    
+    fn skip(&mut self, len: usize) -> Result<()> {
+        self.take(len).map(|_| ())
+    }
+    
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+    
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+    
+    fn i16(&mut self) -> Result<i16> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+    
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+    
+    fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+    
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn layer(seed: u8) -> GrayImage {
+        GrayImage::from_fn(5, 3, |x, y| image::Luma([seed.wrapping_mul(31).wrapping_add((x * 7 + y * 13) as u8)]))
+    }
+    
+    #[test]
+    fn written_layers_read_back() {
+        let path = std::env::temp_dir().join(format!("ormseparatev3-psd-test-{}.psd", std::process::id()));
+        let layers: Vec<(String, GrayImage)> = ["Occlusion", "Roughness", "Metallic"].iter().enumerate()
+            .map(|(i, name)| (name.to_string(), layer(i as u8)))
+            .collect();
+        write_layers(&path, &layers).unwrap();
+        
+        let read = |selector: Option<&LayerSelector>| open(&path, selector).unwrap().to_luma8();
+        assert_eq!(dimensions(&path).unwrap(), (5, 3));
+        assert_eq!(read(None), layers[0].1);
+        for (name, expected) in &layers {
+            assert_eq!(&read(Some(&LayerSelector::Name(name.clone()))), expected);
+        }
+        // Indices count from the bottom, the first image is the top layer
+        assert_eq!(read(Some(&LayerSelector::Index(0))), layers[2].1);
+        assert!(open(&path, Some(&LayerSelector::Name("Height".to_string()))).is_err());
+        let _ = fs::remove_file(&path);
+    }
+    
+    #[test]
+    fn layer_selectors_parse() {
+        assert_eq!(LayerSelector::parse("2"), LayerSelector::Index(2));
+        assert_eq!(LayerSelector::parse("Roughness"), LayerSelector::Name("Roughness".to_string()));
+    }
+}
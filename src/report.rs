@@ -0,0 +1,218 @@
+// What happened to each processed file, collected for run-wide outputs like contact sheets
+use std::fs;
+use std::ops::AddAssign;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Result, Context};
+use image::{DynamicImage, RgbaImage};
+
+// Time spent in each stage of splitting. Write includes pre_command and post_command
+#[derive(Default, Clone, Copy)]
+pub struct StageTimes {
+    pub decode: Duration,
+    pub extract: Duration,
+    pub encode: Duration,
+    pub write: Duration,
+}
+
+impl AddAssign for StageTimes {
+    fn add_assign(&mut self, other: Self) {
+        self.decode += other.decode;
+        self.extract += other.extract;
+        self.encode += other.encode;
+        self.write += other.write;
+    }
+}
+
+pub struct FileSummary {
+    pub file: PathBuf,
+    pub dimensions: Option<(u32, u32)>,
+    pub pages: usize,
+    pub duration: Duration,
+    // Why the file was left alone, or why processing it failed
+    pub skipped: Option<String>,
+    pub error: Option<String>,
+    // Things a reviewer should look at, like constant channels or sizes that aren't a power of two
+    pub warnings: Vec<String>,
+    // Labeled previews of the source and each written channel, only made for contact sheets and reports
+    pub thumbnails: Vec<(String, RgbaImage)>,
+    // Value statistics of each channel output, only gathered when a stats CSV is wanted
+    pub outputs: Vec<OutputSummary>,
+    pub stages: StageTimes,
+    // Extract time of each channel (and page), part of stages.extract
+    pub channel_times: Vec<(String, Duration)>,
+}
+
+impl FileSummary {
+    pub fn new(file: PathBuf) -> Self {
+        FileSummary {
+            file,
+            dimensions: None,
+            pages: 0,
+            duration: Duration::ZERO,
+            skipped: None,
+            error: None,
+            warnings: Vec::new(),
+            thumbnails: Vec::new(),
+            outputs: Vec::new(),
+            stages: StageTimes::default(),
+            channel_times: Vec::new(),
+        }
+    }
+    
+    pub fn add_extract(&mut self, channel: String, elapsed: Duration) {
+        self.stages.extract += elapsed;
+        self.channel_times.push((channel, elapsed));
+    }
+    
+    fn status(&self) -> &'static str {
+        if self.error.is_some() {
+            "failed"
+        } else if self.skipped.is_some() {
+            "skipped"
+        } else {
+            "ok"
+        }
+    }
+}
+
+// One channel (or the layered file) of one page. Channels with no stats are layered files,
+// channels with no size weren't written (constant, duplicates or a layer of a layered file)
+pub struct OutputSummary {
+    pub name: String,
+    pub mean: Option<f32>,
+    pub stddev: Option<f32>,
+    pub bytes: Option<u64>,
+    // The written output an identical channel was skipped or linked for
+    pub duplicate_of: Option<PathBuf>,
+}
+
+impl OutputSummary {
+    // Mean and standard deviation of the first sample of a channel image, in 0-255 units
+    pub fn of_channel(name: String, img: &DynamicImage) -> Self {
+        let rgba = img.to_rgba32f();
+        let pixels = (rgba.width() as f64 * rgba.height() as f64).max(1.0);
+        let (mut sum, mut sum_sq) = (0.0f64, 0.0f64);
+        for pixel in rgba.pixels() {
+            sum += pixel[0] as f64;
+            sum_sq += pixel[0] as f64 * pixel[0] as f64;
+        }
+        let mean = sum / pixels;
+        let variance = (sum_sq / pixels - mean * mean).max(0.0);
+        OutputSummary {
+            name,
+            mean: Some((mean * 255.0) as f32),
+            stddev: Some((variance.sqrt() * 255.0) as f32),
+            bytes: None,
+            duplicate_of: None,
+        }
+    }
+}
+
+// One row per file. Output columns are named after the channel (and page) and appear in the
+// order first seen, so files with more pages or skipped channels leave cells empty. Duplicate
+// columns are only added when the run found duplicates
+pub fn write_stats_csv(path: &Path, summaries: &[FileSummary]) -> Result<()> {
+    let mut names: Vec<&str> = Vec::new();
+    for output in summaries.iter().flat_map(|summary| &summary.outputs) {
+        if !names.contains(&output.name.as_str()) {
+            names.push(&output.name);
+        }
+    }
+    let duplicates = summaries.iter().flat_map(|summary| &summary.outputs).any(|output| output.duplicate_of.is_some());
+    
+    let mut header: Vec<String> = ["file", "status", "width", "height", "pages", "seconds", "output_bytes", "message"]
+        .iter().map(|field| field.to_string()).collect();
+    for name in &names {
+        header.extend([format!("{}_mean", name), format!("{}_stddev", name), format!("{}_bytes", name)]);
+        if duplicates {
+            header.push(format!("{}_duplicate_of", name));
+        }
+    }
+    let mut csv = csv_row(&header);
+    
+    for summary in summaries {
+        let (width, height) = summary.dimensions.map_or((String::new(), String::new()), |(w, h)| (w.to_string(), h.to_string()));
+        let total: u64 = summary.outputs.iter().filter_map(|output| output.bytes).sum();
+        let mut row = vec![
+            summary.file.display().to_string(),
+            summary.status().to_string(),
+            width,
+            height,
+            summary.pages.to_string(),
+            format!("{:.3}", summary.duration.as_secs_f64()),
+            total.to_string(),
+            summary.error.clone().or_else(|| summary.skipped.clone()).unwrap_or_default(),
+        ];
+        for name in &names {
+            let output = summary.outputs.iter().find(|output| output.name == *name);
+            row.push(output.and_then(|output| output.mean).map(|mean| format!("{:.2}", mean)).unwrap_or_default());
+            row.push(output.and_then(|output| output.stddev).map(|stddev| format!("{:.2}", stddev)).unwrap_or_default());
+            row.push(output.and_then(|output| output.bytes).map(|bytes| bytes.to_string()).unwrap_or_default());
+            if duplicates {
+                row.push(output.and_then(|output| output.duplicate_of.as_ref()).map(|path| path.display().to_string()).unwrap_or_default());
+            }
+        }
+        csv.push_str(&csv_row(&row));
+    }
+    
+    if let Some(dir) = path.parent() && !dir.as_os_str().is_empty() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create output directory: {}", dir.display()))?;
+    }
+    fs::write(path, csv)
+        .with_context(|| format!("Failed to save stats: {}", path.display()))
+}
+
+// Fields with commas, quotes or line breaks are quoted, quotes inside doubled
+fn csv_row(fields: &[String]) -> String {
+    let fields: Vec<String> = fields.iter().map(|field| {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.clone()
+        }
+    }).collect();
+    format!("{}\n", fields.join(","))
+}
+
+// Stage times of every split file and percentiles over the run, to tell whether a slow batch is
+// bound by decoding, channel extraction, encoding or the disk
+pub fn print_timings(summaries: &[FileSummary]) {
+    let split: Vec<&FileSummary> = summaries.iter().filter(|summary| summary.pages > 0).collect();
+    if split.is_empty() {
+        return;
+    }
+    let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    
+    println!();
+    println!("Timings (ms)");
+    for summary in &split {
+        let channels: Vec<String> = summary.channel_times.iter()
+            .map(|(channel, elapsed)| format!("{} {:.1}", channel, ms(*elapsed)))
+            .collect();
+        println!("  {}: decode {:.1}, extract {:.1} ({}), encode {:.1}, write {:.1}",
+            summary.file.display(), ms(summary.stages.decode), ms(summary.stages.extract), channels.join(", "),
+            ms(summary.stages.encode), ms(summary.stages.write));
+    }
+    
+    let mut total = StageTimes::default();
+    split.iter().for_each(|summary| total += summary.stages);
+    let sum = (ms(total.decode) + ms(total.extract) + ms(total.encode) + ms(total.write)).max(f64::EPSILON);
+    println!();
+    println!("  {:<7} {:>9} {:>9} {:>9} {:>7}", "Stage", "p50", "p90", "max", "share");
+    let stages = |times: &StageTimes| [times.decode, times.extract, times.encode, times.write];
+    for (index, name) in ["decode", "extract", "encode", "write"].iter().enumerate() {
+        let mut values: Vec<f64> = split.iter().map(|summary| ms(stages(&summary.stages)[index])).collect();
+        values.sort_by(f64::total_cmp);
+        println!("  {:<7} {:>9.1} {:>9.1} {:>9.1} {:>6.0}%",
+            name, percentile(&values, 50.0), percentile(&values, 90.0), values[values.len() - 1], ms(stages(&total)[index]) / sum * 100.0);
+    }
+}
+
+// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
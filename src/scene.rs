@@ -0,0 +1,301 @@
+// Textures referenced by 3D scene files, so a model can be given as input instead of the folders
+// its textures are in. Each texture is paired with the material using it
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow, Context};
+use regex::Regex;
+
+use crate::json::{self, Value};
+use crate::serve::percent_decode;
+
+pub struct SceneTexture {
+    // Empty for textures no material uses
+    pub material: String,
+    // The material slot or statement it's used in, like map_Kd or metallicRoughnessTexture
+    pub slot: String,
+    pub path: PathBuf,
+}
+
+pub struct Scene {
+    // In file order, as referenced whether they exist or not, with relative paths resolved
+    // against the scene's folder
+    pub textures: Vec<SceneTexture>,
+    // Images stored inside the scene file, which can't be split in place
+    pub embedded: usize,
+}
+
+pub fn is_scene(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| matches!(ext.to_ascii_lowercase().to_str(), Some("mtl" | "obj" | "gltf" | "glb" | "fbx")))
+}
+
+pub fn read(scene: &Path) -> Result<Scene> {
+    let data = fs::read(scene)
+        .with_context(|| format!("Failed to read scene: {}", scene.display()))?;
+    let dir = scene.parent().unwrap_or(Path::new("."));
+    let extension = scene.extension().unwrap_or_default().to_ascii_lowercase();
+    let text = || String::from_utf8_lossy(&data).into_owned();
+    let textures = match extension.to_str() {
+        Some("obj") => obj_textures(&text(), scene, dir)?,
+        Some("gltf") => return gltf(&json::parse(&text()).with_context(|| format!("Invalid glTF: {}", scene.display()))?, dir),
+        Some("glb") => {
+            let chunk = glb_json(&data).with_context(|| format!("Invalid GLB: {}", scene.display()))?;
+            return gltf(&json::parse(&chunk).with_context(|| format!("Invalid GLB: {}", scene.display()))?, dir);
+        }
+        Some("fbx") if data.starts_with(b"Kaydara FBX Binary") => {
+            return Err(anyhow!("{} is a binary FBX, export it as ASCII FBX to read its textures", scene.display()));
+        }
+        Some("fbx") => fbx_textures(&text(), dir),
+        _ => mtl_textures(&text(), dir),
+    };
+    Ok(Scene { textures, embedded: 0 })
+}
+
+// mtllib lists one or more material libraries, names can't contain spaces
+fn obj_textures(text: &str, scene: &Path, dir: &Path) -> Result<Vec<SceneTexture>> {
+    let mut textures = Vec::new();
+    for line in text.lines() {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() == Some("mtllib") {
+            for library in tokens {
+                let library = resolve(dir, library);
+                let text = fs::read_to_string(&library)
+                    .with_context(|| format!("Failed to read material library {} of {}", library.display(), scene.display()))?;
+                textures.extend(mtl_textures(&text, library.parent().unwrap_or(dir)));
+            }
+        }
+    }
+    Ok(textures)
+}
+
+fn mtl_textures(text: &str, dir: &Path) -> Vec<SceneTexture> {
+    let mut textures = Vec::new();
+    let mut material = String::new();
+    for line in text.lines() {
+        let line = line.trim();
+        let Some((statement, rest)) = line.split_once(char::is_whitespace) else {
+            continue;
+        };
+        let lowercase = statement.to_ascii_lowercase();
+        if lowercase == "newmtl" {
+            material = rest.trim().to_string();
+        } else if (lowercase.starts_with("map_") || matches!(lowercase.as_str(), "bump" | "disp" | "decal" | "refl" | "norm"))
+            && let Some(file) = map_file(rest)
+        {
+            textures.push(SceneTexture { material: material.clone(), slot: statement.to_string(), path: resolve(dir, file) });
+        }
+    }
+    textures
+}
+
+// The file name of a map statement, after its options like "-s 1 1 1" or "-clamp on". Names may
+// contain spaces
+fn map_file(arguments: &str) -> Option<&str> {
+    let mut rest = arguments.trim();
+    while rest.starts_with('-') {
+        let (option, after) = rest.split_once(char::is_whitespace)?;
+        // Options with up to three numbers, the others take exactly one value
+        let values = match option {
+            "-o" | "-s" | "-t" => 3,
+            "-mm" => 2,
+            _ => 1,
+        };
+        rest = after.trim_start();
+        for index in 0..values {
+            let (value, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if index > 0 && value.parse::<f32>().is_err() {
+                break;
+            }
+            rest = after.trim_start();
+        }
+    }
+    (!rest.is_empty()).then_some(rest)
+}
+
+// The JSON chunk of a binary glTF: a 12-byte header, then chunks of length, type and data
+fn glb_json(data: &[u8]) -> Result<String> {
+    if !data.starts_with(b"glTF") {
+        return Err(anyhow!("Not a binary glTF file"));
+    }
+    let length = data.get(12..16)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+        .ok_or_else(|| anyhow!("Missing JSON chunk"))?;
+    if data.get(16..20) != Some(b"JSON") {
+        return Err(anyhow!("The first chunk isn't JSON"));
+    }
+    let chunk = data.get(20..20 + length).ok_or_else(|| anyhow!("Truncated JSON chunk"))?;
+    Ok(String::from_utf8_lossy(chunk).into_owned())
+}
+
+// Texture slots of glTF 2.0 materials, as (object holding it, slot)
+const GLTF_SLOTS: &[(Option<&str>, &str)] = &[
+    (Some("pbrMetallicRoughness"), "baseColorTexture"),
+    (Some("pbrMetallicRoughness"), "metallicRoughnessTexture"),
+    (None, "normalTexture"),
+    (None, "occlusionTexture"),
+    (None, "emissiveTexture"),
+];
+
+fn gltf(root: &Value, dir: &Path) -> Result<Scene> {
+    let images = root.get("images").map(Value::as_array).unwrap_or_default();
+    let textures = root.get("textures").map(Value::as_array).unwrap_or_default();
+    // Image URIs are relative references with percent escapes, data: URIs and buffer views embed
+    // the image
+    let image_paths: Vec<Option<PathBuf>> = images.iter()
+        .map(|image| image.get("uri").and_then(Value::as_str)
+            .filter(|uri| !uri.starts_with("data:"))
+            .map(|uri| resolve(dir, &percent_decode(uri, false))))
+        .collect();
+    
+    let mut found = Vec::new();
+    let mut used = vec![false; images.len()];
+    for (index, material) in root.get("materials").map(Value::as_array).unwrap_or_default().iter().enumerate() {
+        let name = material.get("name").and_then(Value::as_str).map_or_else(|| format!("material_{}", index), str::to_string);
+        for (holder, slot) in GLTF_SLOTS {
+            let holder = match holder {
+                Some(holder) => material.get(holder),
+                None => Some(material),
+            };
+            let image = holder
+                .and_then(|holder| holder.get(slot))
+                .and_then(|info| info.get("index"))
+                .and_then(Value::as_index)
+                .and_then(|texture| textures.get(texture))
+                .and_then(|texture| texture.get("source"))
+                .and_then(Value::as_index)
+                .filter(|image| *image < images.len());
+            if let Some(image) = image {
+                used[image] = true;
+                if let Some(path) = &image_paths[image] {
+                    found.push(SceneTexture { material: name.clone(), slot: slot.to_string(), path: path.clone() });
+                }
+            }
+        }
+    }
+    for (image, path) in image_paths.iter().enumerate() {
+        if !used[image] && let Some(path) = path {
+            found.push(SceneTexture { material: String::new(), slot: String::new(), path: path.clone() });
+        }
+    }
+    Ok(Scene { textures: found, embedded: image_paths.iter().filter(|path| path.is_none()).count() })
+}
+
+// ASCII FBX, 6.1 and 7.x. Objects are declared as `Texture: <id>, "Texture::<name>", ...` with
+// the file in RelativeFilename or FileName, and linked by `C: "OP", <texture>, <material>, "<slot>"`
+// (7.x) or `Connect: "OP", "Texture::<name>", "Material::<name>", "<slot>"` (6.1)
+fn fbx_textures(text: &str, dir: &Path) -> Vec<SceneTexture> {
+    let object = Regex::new(r#"^\s*(Texture|Material):\s*(?:(-?\d+),\s*)?"(?:Texture|Material)::([^"]*)""#).unwrap();
+    let file = Regex::new(r#"^\s*(RelativeFilename|FileName):\s*"([^"]*)""#).unwrap();
+    let connection = Regex::new(r#"^\s*(?:C|Connect):\s*"OP",\s*("[^"]*"|-?\d+),\s*("[^"]*"|-?\d+),\s*"([^"]*)""#).unwrap();
+    
+    // Textures by id (the quoted name in 6.1) with their relative and absolute file names
+    let mut textures: Vec<(String, Option<String>, Option<String>)> = Vec::new();
+    let mut materials: HashMap<String, String> = HashMap::new();
+    let mut current_texture = None;
+    let mut links = Vec::new();
+    for line in text.lines() {
+        if let Some(captures) = object.captures(line) {
+            let name = captures[3].to_string();
+            let id = captures.get(2).map_or_else(|| format!("\"{}::{}\"", &captures[1], name), |id| id.as_str().to_string());
+            if &captures[1] == "Texture" {
+                textures.push((id, None, None));
+                current_texture = Some(textures.len() - 1);
+            } else {
+                materials.insert(id, name);
+                current_texture = None;
+            }
+        } else if let Some(captures) = file.captures(line) && let Some(index) = current_texture {
+            let value = Some(captures[2].to_string()).filter(|value| !value.is_empty());
+            if &captures[1] == "RelativeFilename" {
+                textures[index].1 = value;
+            } else {
+                textures[index].2 = value;
+            }
+        } else if let Some(captures) = connection.captures(line) {
+            links.push((captures[1].to_string(), captures[2].to_string(), captures[3].to_string()));
+        }
+    }
+    
+    let mut found = Vec::new();
+    for (id, relative, absolute) in textures {
+        // Relative names survive moving the scene with its textures, absolute ones are a fallback
+        let path = match (relative.map(|name| resolve(dir, &name)), absolute.map(|name| resolve(dir, &name))) {
+            (Some(relative), Some(absolute)) if !relative.exists() && absolute.exists() => absolute,
+            (Some(relative), _) => relative,
+            (None, Some(absolute)) => absolute,
+            (None, None) => continue,
+        };
+        let uses: Vec<(&String, &String)> = links.iter()
+            .filter(|(from, ..)| *from == id)
+            .filter_map(|(_, to, slot)| materials.get(to).map(|material| (material, slot)))
+            .collect();
+        if uses.is_empty() {
+            found.push(SceneTexture { material: String::new(), slot: String::new(), path });
+            continue;
+        }
+        for (material, slot) in uses {
+            found.push(SceneTexture { material: material.clone(), slot: slot.clone(), path: path.clone() });
+        }
+    }
+    found
+}
+
+// Exporters on Windows write backslashes, which are plain characters elsewhere
+fn resolve(dir: &Path, reference: &str) -> PathBuf {
+    let reference = if cfg!(windows) { reference.to_string() } else { reference.replace('\\', "/") };
+    dir.join(reference)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn map_file_skips_options() {
+        assert_eq!(map_file("rock_ORM.png"), Some("rock_ORM.png"));
+        assert_eq!(map_file("-s 1 1 1 -o 0 0 0 rock ORM.png"), Some("rock ORM.png"));
+        assert_eq!(map_file("-s 2 rock.png"), Some("rock.png"));
+        assert_eq!(map_file("-clamp on  -bm 0.5 rock.png"), Some("rock.png"));
+        assert_eq!(map_file("-clamp on"), None);
+        assert_eq!(map_file("-clamp"), None);
+        assert_eq!(map_file("   "), None);
+    }
+    
+    #[test]
+    fn fbx_textures_follow_their_connections() {
+        let dir = Path::new("/scene");
+        let modern = r#"
+            Texture: 100, "Texture::rock", "" {
+                RelativeFilename: "textures\rock_ORM.png"
+                FileName: "C:/art/rock_ORM.png"
+            }
+            Material: 200, "Material::Rock", "" {
+            }
+            Texture: 101, "Texture::unused", "" {
+                FileName: "/art/unused.png"
+            }
+            Texture: 102, "Texture::empty", "" {
+            }
+            C: "OP", 100, 200, "Maps|ORM"
+        "#;
+        let textures = fbx_textures(modern, dir);
+        assert_eq!(textures.len(), 2);
+        assert_eq!((textures[0].material.as_str(), textures[0].slot.as_str()), ("Rock", "Maps|ORM"));
+        assert_eq!(textures[0].path, dir.join(if cfg!(windows) { "textures\\rock_ORM.png" } else { "textures/rock_ORM.png" }));
+        assert_eq!((textures[1].material.as_str(), textures[1].path.as_path()), ("", Path::new("/art/unused.png")));
+        
+        let legacy = r#"
+            Texture: "Texture::rock", "TextureVideoClip" {
+                FileName: "rock.png"
+            }
+            Material: "Material::Rock", "" {
+            }
+            Connect: "OP", "Texture::rock", "Material::Rock", "DiffuseColor"
+        "#;
+        let textures = fbx_textures(legacy, dir);
+        assert_eq!(textures.len(), 1);
+        assert_eq!((textures[0].material.as_str(), textures[0].slot.as_str()), ("Rock", "DiffuseColor"));
+        assert_eq!(textures[0].path, dir.join("rock.png"));
+    }
+}
@@ -0,0 +1,131 @@
+// Update the executable from the project's GitHub releases. Downloads go through curl, which ships
+// with Windows 10+, macOS and most Linux installs, since there's no HTTP client in our dependencies
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Result, anyhow, Context};
+
+use crate::hash::{hex, sha256};
+
+const REPOSITORY: &str = env!("CARGO_PKG_REPOSITORY");
+
+// Asset names published by the release workflow, e.g. ormseparatev3-windows-x86_64.exe,
+// each with a {asset}.sha256 next to it
+fn asset_name() -> String {
+    format!("ormseparatev3-{}-{}{}", std::env::consts::OS, std::env::consts::ARCH, std::env::consts::EXE_SUFFIX)
+}
+
+pub fn self_update(check_only: bool, force: bool) -> Result<()> {
+    let exe = std::env::current_exe().context("Failed to get executable path")?;
+    // Left behind by an update on Windows, where the running executable can only be renamed
+    let _ = fs::remove_file(old_path(&exe));
+    
+    let tag = latest_tag()?;
+    let current = env!("CARGO_PKG_VERSION");
+    println!("Current version: {}", current);
+    println!("Latest release: {}", tag);
+    
+    let newer = match (parse_version(&tag), parse_version(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => return Err(anyhow!("Can't compare versions {} and {}", tag, current)),
+    };
+    if !newer && !force {
+        println!("Already up to date");
+        return Ok(());
+    }
+    if check_only {
+        println!("An update is available, run self-update to install it");
+        return Ok(());
+    }
+    
+    let asset = asset_name();
+    let url = format!("{}/releases/download/{}/{}", REPOSITORY, tag, asset);
+    println!("Downloading {}", url);
+    let binary = download(&url)?;
+    // The checksum comes from the same release, so it catches corrupted or truncated downloads,
+    // not a tampered release
+    let checksum = String::from_utf8(download(&format!("{}.sha256", url))?)
+        .context("Checksum file isn't text")?;
+    let expected = checksum.split_whitespace().next()
+        .ok_or_else(|| anyhow!("Checksum file is empty"))?
+        .to_ascii_lowercase();
+    let actual = hex(&sha256(&binary));
+    if actual != expected {
+        return Err(anyhow!("Checksum mismatch, expected {} but the download has {}", expected, actual));
+    }
+    println!("Integrity check passed, SHA-256 {} matches the release's checksum file", actual);
+    
+    replace_executable(&exe, &binary)?;
+    println!("Updated {} to {}", exe.display(), tag);
+    Ok(())
+}
+
+// GitHub redirects /releases/latest to /releases/tag/{tag}
+fn latest_tag() -> Result<String> {
+    let output = curl(&["-o", if cfg!(windows) { "NUL" } else { "/dev/null" }, "-w", "%{url_effective}", &format!("{}/releases/latest", REPOSITORY)])?;
+    let url = String::from_utf8_lossy(&output);
+    url.rsplit_once("/tag/")
+        .map(|(_, tag)| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .ok_or_else(|| anyhow!("No releases found at {}", REPOSITORY))
+}
+
+pub fn download(url: &str) -> Result<Vec<u8>> {
+    curl(&[url]).with_context(|| format!("Failed to download {}", url))
+}
+
+fn curl(args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["-fsSL", "--proto", "=https"])
+        .args(args)
+        .output()
+        .context("Failed to run curl, is it installed?")?;
+    if !output.status.success() {
+        return Err(anyhow!("curl failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(output.stdout)
+}
+
+// "v1.2.3" or "1.2" as comparable numbers, pre-release suffixes are ignored
+fn parse_version(text: &str) -> Option<Vec<u64>> {
+    let version = text.trim().trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+    version.split('.').map(|part| part.parse().ok()).collect()
+}
+
+fn old_path(exe: &Path) -> PathBuf {
+    let mut name = exe.file_name().unwrap_or_default().to_os_string();
+    name.push(".old");
+    exe.with_file_name(name)
+}
+
+// Write the new binary next to the old one and move it into place, so a failed write
+// never leaves a broken executable behind. On Windows the old executable is moved back if the
+// new one can't take its place
+fn replace_executable(exe: &Path, binary: &[u8]) -> Result<()> {
+    let mut name = exe.file_name().unwrap_or_default().to_os_string();
+    name.push(".new");
+    let new_path = exe.with_file_name(name);
+    fs::write(&new_path, binary)
+        .with_context(|| format!("Failed to write {}", new_path.display()))?;
+    
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&new_path, fs::Permissions::from_mode(0o755))?;
+    }
+    // A running executable can't be replaced on Windows, but it can be renamed out of the way
+    #[cfg(windows)]
+    fs::rename(exe, old_path(exe))
+        .with_context(|| format!("Failed to move {} aside", exe.display()))?;
+    
+    let replaced = fs::rename(&new_path, exe);
+    if replaced.is_err() {
+        #[cfg(windows)]
+        let _ = fs::rename(old_path(exe), exe);
+        let _ = fs::remove_file(&new_path);
+    }
+    replaced.with_context(|| format!("Failed to replace {}", exe.display()))
+}
+
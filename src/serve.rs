@@ -0,0 +1,732 @@
+// HTTP API for asset managers: submit an image, poll the job, download the split channels.
+// A plain HTTP/1.1 server on std::net, one thread per connection (up to MAX_CONNECTIONS) and a
+// fixed pool of workers
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Result, anyhow, Context};
+use walkdir::WalkDir;
+
+use crate::json;
+use crate::{AfterSource, CancelToken, Config, FileMatcher, NoProgress, Profile, relative_path, summarized_process_file, usable_profiles};
+
+// Request lines and headers together, anything longer is rejected
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+
+// Connections handled at the same time, each can buffer a body of up to --max-upload-mb. Further
+// connections are answered 503 right away
+const MAX_CONNECTIONS: usize = 32;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Queued,
+    Running,
+    Done,
+    Skipped,
+    Failed,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Queued => "queued",
+            Status::Running => "running",
+            Status::Done => "done",
+            Status::Skipped => "skipped",
+            Status::Failed => "failed",
+        }
+    }
+}
+
+struct Job {
+    status: Status,
+    profile: String,
+    // Directory holding the submitted image and everything written from it
+    dir: PathBuf,
+    file: PathBuf,
+    message: Option<String>,
+    warnings: Vec<String>,
+    // Paths relative to dir
+    outputs: Vec<String>,
+}
+
+struct Server {
+    profiles: HashMap<String, Profile>,
+    default_profile: String,
+    root: PathBuf,
+    // Canonical folder ?path= may read images from, ?path= is refused without one
+    path_root: Option<PathBuf>,
+    max_upload: u64,
+    next_id: AtomicU64,
+    connections: AtomicUsize,
+    jobs: Mutex<HashMap<u64, Job>>,
+    queue: Mutex<Sender<u64>>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    content_type: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn new(status: u16, content_type: &str, body: Vec<u8>) -> Self {
+        Response { status, content_type: content_type.to_string(), headers: Vec::new(), body }
+    }
+    
+    fn json(status: u16, body: String) -> Self {
+        Response::new(status, "application/json", body.into_bytes())
+    }
+    
+    fn error(status: u16, message: &str) -> Self {
+        Response::json(status, format!("{{\"error\":{}}}", json::string(message)))
+    }
+}
+
+pub fn serve(bind: &str, port: u16, workers: Option<usize>, max_upload_mb: u64, path_root: Option<&Path>, config: &Config, exe_dir: &Path) -> Result<()> {
+    let profiles = usable_profiles(config, exe_dir).into_iter()
+        .map(|(name, profile)| {
+            let profile = job_profile(&name, profile);
+            (name, profile)
+        })
+        .collect();
+    let path_root = path_root
+        .map(|dir| dir.canonicalize().with_context(|| format!("Failed to open --path-root folder: {}", dir.display())))
+        .transpose()?;
+    
+    let root = std::env::temp_dir().join(format!("ormseparatev3-serve-{}", std::process::id()));
+    fs::create_dir_all(&root)
+        .with_context(|| format!("Failed to create job directory: {}", root.display()))?;
+    
+    let (sender, receiver) = mpsc::channel();
+    let server = Arc::new(Server {
+        profiles,
+        default_profile: config.default_profile.clone(),
+        root,
+        path_root,
+        max_upload: max_upload_mb.saturating_mul(1024 * 1024),
+        next_id: AtomicU64::new(1),
+        connections: AtomicUsize::new(0),
+        jobs: Mutex::new(HashMap::new()),
+        queue: Mutex::new(sender),
+    });
+    
+    let workers = workers.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |count| count.get())).max(1);
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..workers {
+        let (server, receiver) = (server.clone(), receiver.clone());
+        std::thread::spawn(move || work(&server, &receiver));
+    }
+    
+    let listener = TcpListener::bind((bind, port))
+        .with_context(|| format!("Failed to listen on {}:{}", bind, port))?;
+    println!("Listening on http://{}:{} with {} workers", bind, port, workers);
+    println!("Jobs are stored in {}", server.root.display());
+    
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { continue };
+        if server.connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            server.connections.fetch_sub(1, Ordering::SeqCst);
+            write_response(&mut stream, &Response::error(503, "Too many connections, try again later"));
+            continue;
+        }
+        let server = server.clone();
+        std::thread::spawn(move || {
+            handle_connection(stream, &server);
+            server.connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+    Ok(())
+}
+
+// Jobs write into their own directory and run no commands of the server, so settings that
+// send outputs or sources elsewhere, or run hooks, are dropped for uploads
+fn job_profile(name: &str, mut profile: Profile) -> Profile {
+    let mut dropped = Vec::new();
+    if profile.output_dir.take().is_some() {
+        dropped.push("output_dir");
+    }
+    if profile.backup_dir.take().is_some() {
+        dropped.push("backup_dir");
+    }
+    if profile.after_source != AfterSource::Keep {
+        profile.after_source = AfterSource::Keep;
+        dropped.push("after_source");
+    }
+    if profile.pre_command.take().is_some() {
+        dropped.push("pre_command");
+    }
+    if profile.post_command.take().is_some() {
+        dropped.push("post_command");
+    }
+    if !dropped.is_empty() {
+        println!("Profile '{}': {} ignored for jobs", name, dropped.join(", "));
+    }
+    profile
+}
+
+fn work(server: &Server, receiver: &Mutex<Receiver<u64>>) {
+    loop {
+        let id = match receiver.lock().unwrap().recv() {
+            Ok(id) => id,
+            Err(_) => return,
+        };
+        let (dir, file, profile) = {
+            let mut jobs = server.jobs.lock().unwrap();
+            // Deleted while it waited in the queue
+            let Some(job) = jobs.get_mut(&id) else { continue };
+            job.status = Status::Running;
+            (job.dir.clone(), job.file.clone(), job.profile.clone())
+        };
+        
+        let profile = &server.profiles[&profile];
+        let mut log = Vec::new();
+        let (status, message, warnings) = match FileMatcher::new(profile) {
+            Ok(matcher) => {
+                let (summary, result) = summarized_process_file(&file, &dir, profile, &matcher, &mut log, &NoProgress, (0, 1), &CancelToken::new());
+                match (result, summary.skipped) {
+                    (Err(err), _) => (Status::Failed, Some(failure(id, &err)), summary.warnings),
+                    (Ok(()), Some(reason)) => (Status::Skipped, Some(reason), summary.warnings),
+                    (Ok(()), None) => (Status::Done, None, summary.warnings),
+                }
+            }
+            Err(err) => (Status::Failed, Some(failure(id, &err)), Vec::new()),
+        };
+        
+        let outputs: Vec<String> = WalkDir::new(&dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file() && entry.path() != file)
+            .map(|entry| relative_path(entry.path(), &dir))
+            .collect();
+        println!("Job {}: {} {} ({} outputs)", id, status.name(), file.file_name().unwrap_or_default().to_string_lossy(), outputs.len());
+        
+        if let Some(job) = server.jobs.lock().unwrap().get_mut(&id) {
+            job.status = status;
+            job.message = message;
+            job.warnings = warnings;
+            job.outputs = outputs;
+        }
+    }
+}
+
+// Errors name temp and job paths of the server, so clients only learn that the job failed
+fn failure(id: u64, err: &anyhow::Error) -> String {
+    println!("Job {}: {:#}", id, err);
+    "Splitting failed, the server log has the details".to_string()
+}
+
+// Logged on the server, the client gets the message without the OS error
+fn internal_error(message: &str, err: impl std::fmt::Display) -> Response {
+    println!("{}: {}", message, err);
+    Response::error(500, message)
+}
+
+fn handle_connection(mut stream: TcpStream, server: &Server) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(60)));
+    let response = match read_request(&mut stream, server.max_upload) {
+        Ok(request) => route(request, server),
+        Err(response) => response,
+    };
+    write_response(&mut stream, &response);
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response) {
+    let reason = match response.status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let mut head = format!("HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status, reason, response.content_type, response.body.len());
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    let _ = stream.write_all(head.as_bytes()).and_then(|_| stream.write_all(&response.body));
+}
+
+fn read_request(stream: &mut TcpStream, max_upload: u64) -> std::result::Result<Request, Response> {
+    let mut reader = BufReader::new(stream);
+    let mut lines = Vec::new();
+    let mut header_bytes = 0;
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => return Err(Response::error(400, "Incomplete request")),
+            Ok(read) => header_bytes += read,
+        }
+        if header_bytes > MAX_HEADER_BYTES {
+            return Err(Response::error(400, "Request headers are too long"));
+        }
+        let line = line.trim_end().to_string();
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+    
+    let request_line = lines.first().ok_or_else(|| Response::error(400, "Empty request"))?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(Response::error(400, "Malformed request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let headers: HashMap<String, String> = lines[1..].iter()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    
+    if headers.get("transfer-encoding").is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked")) {
+        return Err(Response::error(411, "Chunked uploads aren't supported, send a Content-Length"));
+    }
+    let length: u64 = match headers.get("content-length") {
+        Some(length) => length.parse().map_err(|_| Response::error(400, "Invalid Content-Length"))?,
+        None => 0,
+    };
+    if length > max_upload {
+        return Err(Response::error(413, &format!("Uploads are limited to {} MiB", max_upload / 1024 / 1024)));
+    }
+    // Grown as the body arrives, so a large Content-Length alone doesn't allocate anything
+    let mut body = Vec::new();
+    let read = reader.take(length).read_to_end(&mut body);
+    if read.is_err() || (body.len() as u64) < length {
+        return Err(Response::error(400, "Request body is shorter than its Content-Length"));
+    }
+    
+    Ok(Request {
+        method: method.to_ascii_uppercase(),
+        path: percent_decode(path, false),
+        query: parse_query(query),
+        headers,
+        body,
+    })
+}
+
+// Takes the request so submit can keep the body without copying it
+fn route(request: Request, server: &Server) -> Response {
+    let (method, path) = (request.method.clone(), request.path.clone());
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    let job_id = |segment: &str| segment.parse::<u64>().ok();
+    match (method.as_str(), segments.as_slice()) {
+        ("GET", ["profiles"]) => profiles(server),
+        ("POST", ["jobs"]) => submit(request, server).unwrap_or_else(|response| response),
+        ("GET", ["jobs", id]) => with_job(server, job_id(id), |id, job| Response::json(200, job_json(id, job))),
+        ("GET", ["jobs", id, "result"]) => with_job(server, job_id(id), |_, job| result(job, request.query.get("format").map(String::as_str))),
+        ("GET", ["jobs", id, "outputs", name @ ..]) => with_job(server, job_id(id), |_, job| output(job, &name.join("/"))),
+        ("DELETE", ["jobs", id]) => delete(server, job_id(id)),
+        (_, ["profiles"] | ["jobs", ..]) => Response::error(405, "Method not allowed"),
+        _ => Response::error(404, "Not found"),
+    }
+}
+
+// A ?path= image, relative to --path-root or absolute. Symlinks and .. are resolved before the
+// path is checked against the root, and errors don't say whether a file outside it exists
+fn read_server_file(path: &str, server: &Server) -> std::result::Result<Vec<u8>, Response> {
+    let root = server.path_root.as_ref()
+        .ok_or_else(|| Response::error(403, "?path= is disabled, start the server with --path-root to allow it"))?;
+    let not_found = || Response::error(404, &format!("No readable file {} under --path-root", path));
+    let file = root.join(path).canonicalize().map_err(|_| not_found())?;
+    if !file.starts_with(root) || !file.is_file() {
+        return Err(not_found());
+    }
+    fs::read(&file).map_err(|_| not_found())
+}
+
+fn profiles(server: &Server) -> Response {
+    let mut names: Vec<&String> = server.profiles.keys().collect();
+    names.sort();
+    let names: Vec<String> = names.into_iter().map(|name| json::string(name)).collect();
+    Response::json(200, format!("{{\"default\":{},\"profiles\":[{}]}}", json::string(&server.default_profile), names.join(",")))
+}
+
+// Either ?path= naming an image under --path-root, a multipart/form-data upload with a file field,
+// or the raw image as the body with ?filename=. The image is copied into the job's directory,
+// so the outputs never land in asset folders on the server
+fn submit(request: Request, server: &Server) -> std::result::Result<Response, Response> {
+    let content_type = request.headers.get("content-type").map(String::as_str).unwrap_or("");
+    let mut fields = request.query.clone();
+    let (name, data) = if let Some(path) = request.query.get("path") {
+        (path.clone(), read_server_file(path, server)?)
+    } else if content_type.starts_with("multipart/form-data") {
+        let boundary = content_type.split(';')
+            .filter_map(|param| param.trim().strip_prefix("boundary="))
+            .next()
+            .map(|boundary| boundary.trim_matches('"'))
+            .ok_or_else(|| Response::error(400, "multipart/form-data without a boundary"))?;
+        let mut file = None;
+        for part in multipart_parts(&request.body, boundary) {
+            match part.filename {
+                Some(filename) if part.name == "file" => file = Some((filename, part.data)),
+                None => {
+                    fields.insert(part.name, String::from_utf8_lossy(&part.data).into_owned());
+                }
+                _ => {}
+            }
+        }
+        file.ok_or_else(|| Response::error(400, "The form has no file field"))?
+    } else if !request.body.is_empty() {
+        let filename = request.query.get("filename")
+            .ok_or_else(|| Response::error(400, "Raw uploads need ?filename= for the image format"))?;
+        (filename.clone(), request.body)
+    } else {
+        return Err(Response::error(400, "Send an image as multipart/form-data, as the body with ?filename=, or ?path="));
+    };
+    
+    // Keep only the file name, clients may send full paths
+    let file_name = name.rsplit(['/', '\\']).next().unwrap_or("").to_string();
+    if file_name.is_empty() || file_name == "." || file_name == ".." {
+        return Err(Response::error(400, &format!("Invalid file name: {}", name)));
+    }
+    let profile = fields.get("profile").cloned().unwrap_or_else(|| server.default_profile.clone());
+    if !server.profiles.contains_key(&profile) {
+        return Err(Response::error(400, &format!("Profile '{}' not found or invalid", profile)));
+    }
+    
+    let id = server.next_id.fetch_add(1, Ordering::SeqCst);
+    let dir = server.root.join(id.to_string());
+    let file = dir.join(&file_name);
+    fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&file, &data))
+        .map_err(|err| internal_error("Failed to store the upload", err))?;
+    
+    let job = Job { status: Status::Queued, profile, dir, file, message: None, warnings: Vec::new(), outputs: Vec::new() };
+    let body = job_json(id, &job);
+    server.jobs.lock().unwrap().insert(id, job);
+    server.queue.lock().unwrap().send(id)
+        .map_err(|_| Response::error(500, "The worker pool has stopped"))?;
+    
+    let mut response = Response::json(202, body);
+    response.headers.push(("Location".to_string(), format!("/jobs/{}", id)));
+    Ok(response)
+}
+
+fn with_job(server: &Server, id: Option<u64>, respond: impl FnOnce(u64, &Job) -> Response) -> Response {
+    let jobs = server.jobs.lock().unwrap();
+    match id.and_then(|id| jobs.get(&id).map(|job| (id, job))) {
+        Some((id, job)) => respond(id, job),
+        None => Response::error(404, "No such job"),
+    }
+}
+
+fn job_json(id: u64, job: &Job) -> String {
+    let list = |items: &[String]| items.iter().map(|item| json::string(item)).collect::<Vec<_>>().join(",");
+    format!("{{\"id\":{},\"status\":\"{}\",\"profile\":{},\"file\":{},\"message\":{},\"warnings\":[{}],\"outputs\":[{}],\"result\":\"/jobs/{}/result\"}}",
+        id, job.status.name(), json::string(&job.profile),
+        json::string(&job.file.file_name().unwrap_or_default().to_string_lossy()),
+        job.message.as_deref().map_or("null".to_string(), json::string),
+        list(&job.warnings), list(&job.outputs), id)
+}
+
+// Every output of a finished job as a zip (default) or a multipart/mixed body
+fn result(job: &Job, format: Option<&str>) -> Response {
+    if matches!(job.status, Status::Queued | Status::Running) {
+        return Response::error(409, &format!("Job is {}", job.status.name()));
+    }
+    let mut files = Vec::new();
+    for name in &job.outputs {
+        match fs::read(job.dir.join(name)) {
+            Ok(data) => files.push((name.clone(), data)),
+            Err(err) => return internal_error(&format!("Failed to read output {}", name), err),
+        }
+    }
+    let stem = header_filename(&job.file.file_stem().unwrap_or_default().to_string_lossy());
+    
+    match format.unwrap_or("zip") {
+        "zip" => match zip(&files) {
+            Ok(data) => {
+                let mut response = Response::new(200, "application/zip", data);
+                response.headers.push(("Content-Disposition".to_string(), format!("attachment; filename=\"{}.zip\"", stem)));
+                response
+            }
+            Err(err) => internal_error("Failed to zip the outputs", format!("{:#}", err)),
+        },
+        "multipart" => {
+            let boundary = format!("ormseparatev3-{}", std::process::id());
+            let mut body = Vec::new();
+            for (name, data) in &files {
+                body.extend_from_slice(format!("--{}\r\nContent-Type: {}\r\nContent-Disposition: attachment; filename=\"{}\"\r\n\r\n",
+                    boundary, content_type(name), header_filename(name)).as_bytes());
+                body.extend_from_slice(data);
+                body.extend_from_slice(b"\r\n");
+            }
+            body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+            Response::new(200, &format!("multipart/mixed; boundary={}", boundary), body)
+        }
+        other => Response::error(400, &format!("Unknown result format '{}', use zip or multipart", other)),
+    }
+}
+
+// A file name for a quoted Content-Disposition parameter. Client names can hold quotes and line
+// breaks, which would end the parameter or the header
+fn header_filename(name: &str) -> String {
+    name.chars().filter(|c| !c.is_control() && !matches!(c, '"' | '\\')).collect()
+}
+
+fn output(job: &Job, name: &str) -> Response {
+    // Only names from the job's own list, so the path can't leave its directory
+    if !job.outputs.iter().any(|output| output == name) {
+        return Response::error(404, "No such output");
+    }
+    match fs::read(job.dir.join(name)) {
+        Ok(data) => Response::new(200, content_type(name), data),
+        Err(err) => internal_error(&format!("Failed to read output {}", name), err),
+    }
+}
+
+fn delete(server: &Server, id: Option<u64>) -> Response {
+    let mut jobs = server.jobs.lock().unwrap();
+    let Some((id, job)) = id.and_then(|id| jobs.get(&id).map(|job| (id, job))) else {
+        return Response::error(404, "No such job");
+    };
+    if job.status == Status::Running {
+        return Response::error(409, "Job is running");
+    }
+    if let Some(job) = jobs.remove(&id) {
+        let _ = fs::remove_dir_all(&job.dir);
+    }
+    Response::json(200, format!("{{\"id\":{},\"deleted\":true}}", id))
+}
+
+fn content_type(name: &str) -> &'static str {
+    match Path::new(name).extension().map(|ext| ext.to_string_lossy().to_ascii_lowercase()).as_deref() {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("tga") => "image/x-tga",
+        Some("tif" | "tiff") => "image/tiff",
+        Some("bmp") => "image/bmp",
+        Some("webp") => "image/webp",
+        Some("exr") => "image/x-exr",
+        Some("psd") => "image/vnd.adobe.photoshop",
+        Some("csv") => "text/csv",
+        Some("html") => "text/html",
+        _ => "application/octet-stream",
+    }
+}
+
+struct Part {
+    name: String,
+    filename: Option<String>,
+    data: Vec<u8>,
+}
+
+fn multipart_parts(body: &[u8], boundary: &str) -> Vec<Part> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = Vec::new();
+    let mut rest = match find(body, &delimiter) {
+        Some(start) => &body[start + delimiter.len()..],
+        None => return parts,
+    };
+    // Each part runs to the next delimiter, the last one is followed by --
+    while !rest.starts_with(b"--") {
+        let Some(end) = find(rest, &delimiter) else { break };
+        let part = rest[..end].strip_prefix(b"\r\n").unwrap_or(&rest[..end]);
+        let part = part.strip_suffix(b"\r\n").unwrap_or(part);
+        rest = &rest[end + delimiter.len()..];
+        
+        let Some(split) = find(part, b"\r\n\r\n") else { continue };
+        let headers = String::from_utf8_lossy(&part[..split]);
+        let disposition = headers.lines()
+            .find(|line| line.to_ascii_lowercase().starts_with("content-disposition:"))
+            .unwrap_or("");
+        let param = |key: &str| disposition.split(';')
+            .filter_map(|param| param.trim().strip_prefix(key))
+            .next()
+            .map(|value| value.trim_matches('"').to_string());
+        if let Some(name) = param("name=") {
+            parts.push(Part { name, filename: param("filename="), data: part[split + 4..].to_vec() });
+        }
+    }
+    parts
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key, true), percent_decode(value, true))
+        })
+        .collect()
+}
+
+// %XX escapes, and + as a space in query strings
+pub fn percent_decode(text: &str, plus_is_space: bool) -> String {
+    let hex = |byte: u8| (byte as char).to_digit(16).map(|digit| digit as u8);
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() && hex(bytes[i + 1]).is_some() && hex(bytes[i + 2]).is_some() => {
+                decoded.push(hex(bytes[i + 1]).unwrap() * 16 + hex(bytes[i + 2]).unwrap());
+                i += 2;
+            }
+            b'+' if plus_is_space => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Stored (uncompressed) zip, the outputs are already compressed images
+fn zip(files: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut directory = Vec::new();
+    for (name, contents) in files {
+        let offset = u32::try_from(data.len()).map_err(|_| anyhow!("Outputs are too large for a zip"))?;
+        let size = u32::try_from(contents.len()).map_err(|_| anyhow!("{} is too large for a zip", name))?;
+        let crc = crc32fast::hash(contents);
+        // Version 2.0, UTF-8 names, stored, 1980-01-01 00:00
+        let fields = |data: &mut Vec<u8>| {
+            for value in [0x0800u16, 0, 0, 0x0021] {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            for value in [crc, size, size] {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            data.extend_from_slice(&0u16.to_le_bytes());
+        };
+        
+        data.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        data.extend_from_slice(&20u16.to_le_bytes());
+        fields(&mut data);
+        data.extend_from_slice(name.as_bytes());
+        data.extend_from_slice(contents);
+        
+        directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        directory.extend_from_slice(&20u16.to_le_bytes());
+        directory.extend_from_slice(&20u16.to_le_bytes());
+        fields(&mut directory);
+        // Comment length, disk, internal and external attributes, then the header offset
+        for value in [0u16, 0, 0] {
+            directory.extend_from_slice(&value.to_le_bytes());
+        }
+        directory.extend_from_slice(&0u32.to_le_bytes());
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+    
+    let directory_offset = u32::try_from(data.len()).map_err(|_| anyhow!("Outputs are too large for a zip"))?;
+    let count = files.len() as u16;
+    data.extend_from_slice(&directory);
+    data.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    for value in [0u16, 0, count, count] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    data.extend_from_slice(&directory_offset.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn multipart_parts_are_split() {
+        let body = b"preamble\r\n--XyZ\r\n\
+            Content-Disposition: form-data; name=\"profile\"\r\n\r\n\
+            orm\r\n--XyZ\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"Rock_ORM.png\"\r\n\
+            Content-Type: image/png\r\n\r\n\
+            \x89PNG\r\n\r\n--Xy not a delimiter\r\n--XyZ\r\n\
+            Content-Type: text/plain\r\n\r\n\
+            no name\r\n--XyZ--\r\n";
+        let parts = multipart_parts(body, "XyZ");
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "profile");
+        assert_eq!(parts[0].filename, None);
+        assert_eq!(parts[0].data, b"orm");
+        assert_eq!(parts[1].name, "file");
+        assert_eq!(parts[1].filename.as_deref(), Some("Rock_ORM.png"));
+        assert_eq!(parts[1].data, b"\x89PNG\r\n\r\n--Xy not a delimiter");
+    }
+    
+    #[test]
+    fn multipart_without_delimiter_is_empty() {
+        assert!(multipart_parts(b"just text", "XyZ").is_empty());
+        assert!(multipart_parts(b"--XyZ--\r\n", "XyZ").is_empty());
+    }
+    
+    #[test]
+    fn header_filenames_stay_in_their_header() {
+        assert_eq!(header_filename("rock\"\r\nSet-Cookie: x=1\\"), "rockSet-Cookie: x=1");
+        assert_eq!(header_filename("Rock_ORM Ümlaut"), "Rock_ORM Ümlaut");
+    }
+    
+    #[test]
+    fn queries_are_decoded() {
+        let query = parse_query("path=a%2Fb+c.png&profile=orm&flag");
+        assert_eq!(query["path"], "a/b c.png");
+        assert_eq!(query["profile"], "orm");
+        assert_eq!(query["flag"], "");
+        assert_eq!(percent_decode("100%+%zz%4", false), "100%+%zz%4");
+    }
+    
+    // Files listed by the central directory, read back through their local headers
+    fn unzip(data: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let u16_at = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]) as usize;
+        let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap()) as usize;
+        let end = data.len() - 22;
+        assert_eq!(u32_at(end), 0x06054b50);
+        let (count, mut entry) = (u16_at(end + 10), u32_at(end + 16));
+        let mut files = Vec::new();
+        for _ in 0..count {
+            assert_eq!(u32_at(entry), 0x02014b50);
+            let name_len = u16_at(entry + 28);
+            let name = String::from_utf8(data[entry + 46..entry + 46 + name_len].to_vec()).unwrap();
+            let (crc, size, header) = (u32_at(entry + 16), u32_at(entry + 20), u32_at(entry + 42));
+            assert_eq!(u32_at(header), 0x04034b50);
+            let start = header + 30 + u16_at(header + 26) + u16_at(header + 28);
+            let contents = data[start..start + size].to_vec();
+            assert_eq!(crc32fast::hash(&contents) as usize, crc);
+            files.push((name, contents));
+            entry += 46 + name_len;
+        }
+        files
+    }
+    
+    #[test]
+    fn zip_round_trips() {
+        let files = vec![
+            ("Rock_ORM_Occlusion.png".to_string(), vec![1, 2, 3]),
+            ("empty.txt".to_string(), Vec::new()),
+            ("folder/Rock_Ümlaut.png".to_string(), (0..=255).collect()),
+        ];
+        assert_eq!(unzip(&zip(&files).unwrap()), files);
+        assert!(unzip(&zip(&[]).unwrap()).is_empty());
+    }
+}
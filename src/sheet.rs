@@ -0,0 +1,123 @@
+// Contact sheets: one row of labeled thumbnails per file, for reviewing many results at once
+use image::{DynamicImage, Rgba, RgbaImage};
+use image::imageops::{self, FilterType};
+
+use crate::report::FileSummary;
+
+const THUMBNAIL_SIZE: u32 = 128;
+const PADDING: u32 = 6;
+const LABEL_HEIGHT: u32 = GLYPH_HEIGHT + 4;
+const BACKGROUND: Rgba<u8> = Rgba([32, 32, 32, 255]);
+const TEXT: Rgba<u8> = Rgba([230, 230, 230, 255]);
+
+// Fit an image into the thumbnail square, composited over black so alpha doesn't hide the data
+pub fn thumbnail(img: &DynamicImage) -> RgbaImage {
+    let mut thumb = img.resize(THUMBNAIL_SIZE, THUMBNAIL_SIZE, FilterType::Triangle).to_rgba8();
+    for pixel in thumb.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        for value in &mut pixel.0[..3] {
+            *value = (*value as u32 * alpha / 255) as u8;
+        }
+        pixel[3] = 255;
+    }
+    thumb
+}
+
+// Rows are titled with the file name, files without thumbnails (skipped or failed) are left out
+pub fn render(summaries: &[FileSummary]) -> Option<RgbaImage> {
+    let rows: Vec<&FileSummary> = summaries.iter().filter(|summary| !summary.thumbnails.is_empty()).collect();
+    let columns = rows.iter().map(|summary| summary.thumbnails.len()).max()? as u32;
+    
+    let cell_width = THUMBNAIL_SIZE + PADDING;
+    let row_height = LABEL_HEIGHT + THUMBNAIL_SIZE + LABEL_HEIGHT + PADDING;
+    let mut sheet = RgbaImage::from_pixel(PADDING + columns * cell_width, PADDING + rows.len() as u32 * row_height, BACKGROUND);
+    
+    for (row, summary) in rows.iter().enumerate() {
+        let top = PADDING + row as u32 * row_height;
+        let title = summary.file.file_name().unwrap_or_default().to_string_lossy();
+        let title_width = sheet.width() - 2 * PADDING;
+        draw_text(&mut sheet, PADDING, top, &title, title_width);
+        
+        for (column, (label, thumb)) in summary.thumbnails.iter().enumerate() {
+            let left = PADDING + column as u32 * cell_width;
+            // Centered in its square, thumbnails of non-square images are smaller on one side
+            let x = left + (THUMBNAIL_SIZE - thumb.width()) / 2;
+            let y = top + LABEL_HEIGHT + (THUMBNAIL_SIZE - thumb.height()) / 2;
+            imageops::overlay(&mut sheet, thumb, x as i64, y as i64);
+            draw_text(&mut sheet, left, top + LABEL_HEIGHT + THUMBNAIL_SIZE + 2, label, THUMBNAIL_SIZE);
+        }
+    }
+    
+    Some(sheet)
+}
+
+// Draw text with the built-in font, cut off at max_width pixels
+fn draw_text(img: &mut RgbaImage, left: u32, top: u32, text: &str, max_width: u32) {
+    let max_chars = (max_width / GLYPH_ADVANCE) as usize;
+    for (index, c) in text.chars().take(max_chars).enumerate() {
+        let x0 = left + index as u32 * GLYPH_ADVANCE;
+        for (y, bits) in glyph(c).iter().enumerate() {
+            for x in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - x)) != 0 && x0 + x < img.width() && top + (y as u32) < img.height() {
+                    img.put_pixel(x0 + x, top + y as u32, TEXT);
+                }
+            }
+        }
+    }
+}
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const GLYPH_ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+// 5x7 uppercase bitmap font, one byte per row with the leftmost pixel in bit 4.
+// Lowercase letters are drawn as uppercase, unknown characters as '?'
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x0A, 0x04, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        ' ' => [0x00; 7],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '/' => [0x01, 0x01, 0x02, 0x04, 0x08, 0x10, 0x10],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
@@ -0,0 +1,147 @@
+// Frame maps of sprite sheets as TexturePacker writes them, so each sprite can be split on its own:
+// JSON (hash or array, also Phaser's multi-atlas), Starling/Sparrow XML and TexturePacker's generic XML
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Result, anyhow, Context};
+use regex::Regex;
+
+use crate::json::{self, Value};
+
+pub struct Frame {
+    // Frame name without its image extension, path separators replaced so it fits in a file name
+    pub name: String,
+    // Region in the sheet, as stored
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    // Packed turned 90° clockwise, turned back when cut out
+    pub rotated: bool,
+}
+
+pub fn read(path: &Path) -> Result<Vec<Frame>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read frame map: {}", path.display()))?;
+    let frames = if text.trim_start().starts_with('<') {
+        xml_frames(&text)
+    } else {
+        json_frames(&json::parse(&text)?)
+    }.with_context(|| format!("Invalid frame map: {}", path.display()))?;
+    
+    if frames.is_empty() {
+        return Err(anyhow!("No frames in frame map: {}", path.display()));
+    }
+    if let Some(frame) = frames.iter().find(|frame| frame.width == 0 || frame.height == 0) {
+        return Err(anyhow!("Frame '{}' in {} is empty", frame.name, path.display()));
+    }
+    if let Some(frame) = frames.iter().find(|frame| frames.iter().filter(|other| other.name == frame.name).count() > 1) {
+        return Err(anyhow!("Frame '{}' is listed more than once in {}", frame.name, path.display()));
+    }
+    Ok(frames)
+}
+
+fn json_frames(root: &Value) -> Result<Vec<Frame>> {
+    // Multi-atlas files list the frames of each sheet under textures, one sheet per input
+    let sheet = root.get("textures").and_then(|textures| textures.as_array().first()).unwrap_or(root);
+    let frames = sheet.get("frames").ok_or_else(|| anyhow!("Missing frames"))?;
+    let entries: Vec<(&str, &Value)> = match frames {
+        Value::Object(members) => members.iter().map(|(name, frame)| (name.as_str(), frame)).collect(),
+        Value::Array(items) => items.iter()
+            .map(|frame| frame.get("filename").and_then(Value::as_str).map(|name| (name, frame)).ok_or_else(|| anyhow!("Frame without a filename")))
+            .collect::<Result<_>>()?,
+        _ => return Err(anyhow!("frames is neither an object nor an array")),
+    };
+    
+    entries.into_iter().map(|(name, frame)| {
+        let rect = frame.get("frame").ok_or_else(|| anyhow!("Frame '{}' has no frame rectangle", name))?;
+        let field = |key: &str| rect.get(key).and_then(Value::as_index).and_then(|value| u32::try_from(value).ok())
+            .ok_or_else(|| anyhow!("Frame '{}' has no valid {}", name, key));
+        let rotated = frame.get("rotated").and_then(Value::as_bool).unwrap_or(false);
+        let (width, height) = (field("w")?, field("h")?);
+        // JSON rectangles are the upright size, the stored region of a rotated frame is turned
+        let (width, height) = if rotated { (height, width) } else { (width, height) };
+        Ok(Frame { name: frame_name(name), x: field("x")?, y: field("y")?, width, height, rotated })
+    }).collect()
+}
+
+// <SubTexture name x y width height rotated/> in Starling/Sparrow atlases, <sprite n x y w h r/>
+// in TexturePacker's generic XML. Both give the region as stored
+fn xml_frames(text: &str) -> Result<Vec<Frame>> {
+    let element = Regex::new(r"<(SubTexture|sprite)\b([^>]*)>").unwrap();
+    let attribute = Regex::new(r#"([\w:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    
+    element.captures_iter(text).map(|captures| {
+        let attributes: Vec<(&str, String)> = attribute.captures_iter(&captures[2])
+            .map(|attribute| {
+                let value = attribute.get(2).or(attribute.get(3)).map_or("", |value| value.as_str());
+                (attribute.get(1).unwrap().as_str(), xml_unescape(value))
+            })
+            .collect();
+        let get = |keys: &[&str]| attributes.iter().find(|(key, _)| keys.contains(key)).map(|(_, value)| value.as_str());
+        let name = get(&["name", "n"]).ok_or_else(|| anyhow!("<{}> without a name", &captures[1]))?;
+        let number = |keys: &[&str]| get(keys).and_then(|value| value.parse::<u32>().ok())
+            .ok_or_else(|| anyhow!("Frame '{}' has no valid {}", name, keys[0]));
+        let rotated = get(&["rotated", "r"]).is_some_and(|value| matches!(value, "true" | "y" | "1"));
+        Ok(Frame {
+            name: frame_name(name),
+            x: number(&["x"])?,
+            y: number(&["y"])?,
+            width: number(&["width", "w"])?,
+            height: number(&["height", "h"])?,
+            rotated,
+        })
+    }).collect()
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+// "heroes/run_01.png" becomes "heroes_run_01"
+fn frame_name(name: &str) -> String {
+    let stem = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.contains(['/', '\\']) => stem,
+        _ => name,
+    };
+    stem.replace(['/', '\\'], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn frame_names_drop_the_extension_and_separators() {
+        assert_eq!(frame_name("heroes/run_01.png"), "heroes_run_01");
+        assert_eq!(frame_name("a\\b.c/d"), "a_b.c_d");
+        assert_eq!(frame_name(".hidden"), ".hidden");
+        assert_eq!(frame_name("plain"), "plain");
+    }
+    
+    #[test]
+    fn reads_starling_and_generic_xml() {
+        let frames = xml_frames(r#"<TextureAtlas>
+            <SubTexture name="coin &amp; gem.png" x="2" y="4" width="16" height="8" rotated="true"/>
+            <sprite n='tree' x='20' y='0' w='32' h='64'/>
+        </TextureAtlas>"#).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].name, "coin & gem");
+        assert_eq!((frames[0].x, frames[0].y, frames[0].width, frames[0].height, frames[0].rotated), (2, 4, 16, 8, true));
+        assert_eq!(frames[1].name, "tree");
+        assert_eq!((frames[1].x, frames[1].y, frames[1].width, frames[1].height, frames[1].rotated), (20, 0, 32, 64, false));
+    }
+    
+    #[test]
+    fn xml_frames_need_a_name_and_numbers() {
+        assert!(xml_frames(r#"<SubTexture x="0" y="0" width="1" height="1"/>"#).is_err());
+        assert!(xml_frames(r#"<SubTexture name="a" x="-1" y="0" width="1" height="1"/>"#).is_err());
+    }
+    
+    #[test]
+    fn rotated_json_frames_swap_their_size() {
+        let root = json::parse(r#"{"frames": [{"filename": "a.png", "frame": {"x": 1, "y": 2, "w": 10, "h": 4}, "rotated": true}]}"#).unwrap();
+        let frames = json_frames(&root).unwrap();
+        assert_eq!((frames[0].width, frames[0].height), (4, 10));
+    }
+}
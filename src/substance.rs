@@ -0,0 +1,77 @@
+// Substance 3D Painter export preset (.spexp) packing the maps of a profile into one texture the
+// way its channels read them, so what artists export is what the profile splits
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Result, anyhow, Context};
+
+use crate::json;
+use crate::{BitDepth, Profile};
+
+// Painter map a semantic is exported from, as (srcMapType, srcMapName)
+fn painter_map(semantic: &str) -> Option<(&'static str, &'static str)> {
+    match semantic {
+        // Painted AO combined with the baked one, as Painter's own ORM presets use
+        "occlusion" | "ambient_occlusion" | "ao" => Some(("virtualMap", "Mixed_AO")),
+        "roughness" | "specular_roughness" => Some(("documentMap", "roughness")),
+        "metallic" | "metalness" => Some(("documentMap", "metallic")),
+        "height" | "displacement" => Some(("documentMap", "height")),
+        "opacity" => Some(("documentMap", "opacity")),
+        "glossiness" | "smoothness" => Some(("virtualMap", "Glossiness")),
+        "specular" => Some(("documentMap", "specularlevel")),
+        _ => None,
+    }
+}
+
+pub fn substance_preset(profile: &Profile, output: &Path, file_name: &str, file_format: &str) -> Result<()> {
+    const SLOTS: [&str; 4] = ["R", "G", "B", "A"];
+    
+    let mut channels = Vec::new();
+    let mut used = Vec::new();
+    for channel in &profile.channels {
+        let semantic = channel.semantic();
+        let reason = if channel.expr.is_some() || channel.reconstruct_z {
+            Some("computed by expr or reconstruct_z".to_string())
+        } else if channel.channel >= SLOTS.len() {
+            Some(format!("reads source channel {}", channel.channel))
+        } else if used.contains(&channel.channel) {
+            Some(format!("source channel {} is already exported", SLOTS[channel.channel]))
+        } else if painter_map(&semantic).is_none() {
+            Some(format!("semantic '{}' has no Painter map, set semantic on the channel", semantic))
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            println!("  Skipping channel {}: {}", channel.name, reason);
+            continue;
+        }
+        let (map_type, map_name) = painter_map(&semantic).unwrap();
+        used.push(channel.channel);
+        println!("  {} <- {} ({})", SLOTS[channel.channel], map_name, channel.name);
+        channels.push(format!(
+            "            {{ \"destChannel\": \"{}\", \"srcChannel\": \"L\", \"srcMapType\": \"{}\", \"srcMapName\": \"{}\" }}",
+            SLOTS[channel.channel], map_type, map_name,
+        ));
+    }
+    if channels.is_empty() {
+        return Err(anyhow!("No channel of profile '{}' maps to a Painter channel", profile.name));
+    }
+    
+    // The packed texture is the input of the split, so it gets the deepest output's precision
+    let depths: Vec<BitDepth> = profile.channels.iter().filter_map(|channel| channel.bit_depth).collect();
+    let bit_depth = if depths.contains(&BitDepth::Float) {
+        "32f"
+    } else if depths.contains(&BitDepth::Sixteen) {
+        "16"
+    } else {
+        "8"
+    };
+    let preset = format!(
+        "{{\n  \"exportPresets\": [\n    {{\n      \"name\": {},\n      \"maps\": [\n        {{\n          \"fileName\": {},\n          \"channels\": [\n{}\n          ],\n          \"parameters\": {{ \"fileFormat\": {}, \"bitDepth\": \"{}\", \"dithering\": false, \"paddingAlgorithm\": \"infinite\" }}\n        }}\n      ]\n    }}\n  ]\n}}\n",
+        json::string(&profile.name), json::string(file_name), channels.join(",\n"), json::string(file_format), bit_depth,
+    );
+    fs::write(output, preset)
+        .with_context(|| format!("Failed to write preset: {}", output.display()))?;
+    println!("Saved Substance Painter export preset to: {}", output.display());
+    Ok(())
+}
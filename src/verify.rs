@@ -0,0 +1,219 @@
+// Round-trip check of a profile: split a file, decode the outputs, re-pack them and compare with the source
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Result, anyhow, Context};
+use image::{DynamicImage, GrayImage, ImageFormat, Rgba32FImage};
+
+use crate::compare::{compare, format_psnr};
+use crate::ktx;
+use crate::psd::{self, LayerSelector};
+use crate::{
+    ChannelConfig, PageSources, Profile, channel_image, decode_tiff_pages, encode_image,
+    gray_layer, is_layered_format, layer_depth, open_pages, output_extension, page_alpha, routed_alpha, set_alpha,
+    write_ktx2_layers, write_layered_tiff, write_multichannel_exr,
+};
+
+// Outputs that only copy a source channel, anything computed or adjusted can't be compared to the source
+fn unchecked_reason(channel: &ChannelConfig, profile: &Profile) -> Option<&'static str> {
+    let converted = matches!((profile.input_color_space, channel.color_space), (Some(from), Some(to)) if from != to);
+    if channel.expression.is_some() {
+        Some("computed by expr or reconstruct_z")
+    } else if channel.height_to_normal.is_some() {
+        Some("written as a normal map")
+    } else if channel.levels.is_some() || channel.gamma.is_some() || channel.lut_curve.is_some() || channel.threshold.is_some() || converted {
+        Some("adjusted by levels, gamma, a LUT, threshold or color space conversion")
+    } else if channel.flip_green && channel.channel == 1 {
+        Some("green is flipped")
+    } else {
+        None
+    }
+}
+
+// Channels drifting more than tolerance (0-255 units) from the source fail the check.
+// skip_constant and duplicates only decide which files get written, so they're ignored here
+pub fn verify(file: &Path, profile: &Profile, tolerance: f32) -> Result<()> {
+    let max_alloc = profile.max_alloc();
+    let pages = open_pages(file, max_alloc, profile.psd_layer.as_ref())
+        .with_context(|| format!("Failed to open image: {}", file.display()))?;
+    
+    println!("Verifying {} with profile '{}'", file.display(), profile.name);
+    
+    let mut drifted = 0;
+    for (page_idx, img) in pages.iter().enumerate() {
+        if pages.len() > 1 {
+            println!("  Page {} of {}", page_idx + 1, pages.len());
+        }
+        
+        let original = img.to_rgba32f();
+        let mut repacked = original.clone();
+        let mut sources = PageSources::new(img);
+        
+        // Outputs are built as process_file does, including alpha, then decoded again.
+        // Layered formats go through a file since their writers need one
+        let alpha = page_alpha(&mut sources, profile);
+        let routed_alpha = routed_alpha(&mut sources, profile);
+        let mut decoded: Vec<(&str, &str, Rgba32FImage)> = Vec::new();
+        let (mut layers, mut layer_names) = (Vec::new(), Vec::new());
+        for channel in profile.channels.iter().filter(|channel| channel.into_alpha_of.is_none()) {
+            let format = channel.format(profile);
+            if is_layered_format(format) {
+                let layer = channel_image(&mut sources, channel, profile, layer_depth(format));
+                layers.push((channel.name.clone(), gray_layer(layer)));
+                layer_names.push(channel.name.as_str());
+                continue;
+            }
+            
+            let depth = channel.bit_depth.unwrap_or_default();
+            let mut channel_img = channel_image(&mut sources, channel, profile, depth);
+            if let Some(alpha) = routed_alpha.get(channel.name.as_str()).or(alpha.as_ref()) {
+                set_alpha(&mut channel_img, alpha);
+            }
+            
+            let extension = output_extension(format);
+            let encoded = encode_image(&channel_img, format, profile, channel.color_space, None)
+                .with_context(|| format!("Failed to encode channel {} as {}", channel.name, extension))?;
+            let image_format = ImageFormat::from_extension(extension)
+                .ok_or_else(|| anyhow!("Can't decode {} outputs to verify them", extension))?;
+            let output = image::load_from_memory_with_format(&encoded, image_format)
+                .with_context(|| format!("Failed to decode channel {} from {}", channel.name, extension))?;
+            decoded.push((&channel.name, format, output.to_rgba32f()));
+        }
+        if !layers.is_empty() {
+            let images = round_trip_layers(&layers, &profile.output_format)?;
+            decoded.extend(layer_names.into_iter().zip(images).map(|(name, image)| (name, profile.output_format.as_str(), image)));
+        }
+        
+        // Re-pack: routed channels come back from the alpha of their target, the rest from the output's value
+        let mut checked = Vec::new();
+        for channel in &profile.channels {
+            if let Some(reason) = unchecked_reason(channel, profile) {
+                println!("  {} not checked, {}", channel.name, reason);
+                continue;
+            }
+            let (output_name, sample) = match &channel.into_alpha_of {
+                Some(target) => (target.as_str(), 3),
+                None => (channel.name.as_str(), 0),
+            };
+            let Some((_, format, output)) = decoded.iter().find(|(name, ..)| *name == output_name) else {
+                continue;
+            };
+            for (pixel, output_pixel) in repacked.pixels_mut().zip(output.pixels()) {
+                pixel[channel.channel] = output_pixel[sample];
+            }
+            checked.push((channel.channel, &channel.name, *format));
+        }
+        
+        for (source_channel, name, format) in checked {
+            let metrics = compare(&original, &repacked, &[source_channel])?.remove(0);
+            let ok = metrics.max_diff <= tolerance;
+            if !ok {
+                drifted += 1;
+            }
+            println!("  {} from {} ({}): max diff {:.1}, PSNR {} dB, SSIM {:.4}{}",
+                ["R", "G", "B", "A"][source_channel], name, output_extension(format), metrics.max_diff,
+                format_psnr(metrics.psnr), metrics.ssim, if ok { "" } else { "  DRIFT" });
+        }
+    }
+    
+    if drifted > 0 {
+        return Err(anyhow!("{} channels drifted more than the tolerance of {}", drifted, tolerance));
+    }
+    println!("All checked channels are within the tolerance of {}", tolerance);
+    Ok(())
+}
+
+// Write layers with the profile's layered writer to a temporary file and read each one back
+fn round_trip_layers(layers: &[(String, DynamicImage)], format: &str) -> Result<Vec<Rgba32FImage>> {
+    let path = std::env::temp_dir().join(format!("ormseparatev3-verify-{}.{}", std::process::id(), output_extension(format)));
+    let result = write_and_read_layers(&path, layers, format);
+    let _ = fs::remove_file(&path);
+    result.with_context(|| format!("Failed to round trip {} layers", format))
+}
+
+fn write_and_read_layers(path: &Path, layers: &[(String, DynamicImage)], format: &str) -> Result<Vec<Rgba32FImage>> {
+    if format == "exr-multichannel" {
+        write_multichannel_exr(path, layers)?;
+        return read_exr_channels(path, layers);
+    }
+    let layers: Vec<(String, GrayImage)> = layers.iter().map(|(name, layer)| (name.clone(), layer.to_luma8())).collect();
+    let layers = layers.as_slice();
+    match format {
+        "psd-layered" => {
+            psd::write_layers(path, layers)?;
+            layers.iter()
+                .map(|(name, _)| Ok(psd::open(path, Some(&LayerSelector::Name(name.clone())))?.to_rgba32f()))
+                .collect()
+        }
+        "ktx2-array" => {
+            write_ktx2_layers(path, layers)?;
+            let (width, height, _, data) = ktx::read_array(path)?;
+            Ok(data.into_iter().map(|layer| {
+                let gray = GrayImage::from_raw(width, height, layer).expect("KTX2 layers have the size of the header");
+                DynamicImage::ImageLuma8(gray).to_rgba32f()
+            }).collect())
+        }
+        _ => {
+            write_layered_tiff(path, layers)?;
+            Ok(decode_tiff_pages(path, None)?.iter().map(DynamicImage::to_rgba32f).collect())
+        }
+    }
+}
+
+// Luminance channels named like Roughness.Y, read back into the R of a gray image
+fn read_exr_channels(path: &Path, layers: &[(String, DynamicImage)]) -> Result<Vec<Rgba32FImage>> {
+    use exr::prelude::{ReadChannels, ReadLayers, read};
+    
+    let image = read().no_deep_data().largest_resolution_level().all_channels().first_valid_layer().all_attributes().from_file(path)?;
+    let (width, height) = layers.first().map(|(_, layer)| (layer.width(), layer.height())).unwrap_or_default();
+    
+    layers.iter().map(|(name, _)| {
+        let channel_name = format!("{}.Y", name);
+        let channel = image.layer_data.channel_data.list.iter()
+            .find(|channel| channel.name.to_string() == channel_name)
+            .ok_or_else(|| anyhow!("Channel {} is missing from the EXR file", channel_name))?;
+        let values: Vec<f32> = channel.sample_data.values_as_f32().collect();
+        Ok(Rgba32FImage::from_fn(width, height, |x, y| {
+            let value = values[(y * width + x) as usize];
+            image::Rgba([value, value, value, 1.0])
+        }))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Luma};
+    
+    fn layers() -> Vec<(String, DynamicImage)> {
+        ["Occlusion", "Roughness", "Metallic"].iter().enumerate()
+            .map(|(i, name)| {
+                let layer = ImageBuffer::from_fn(6, 4, |x, y| Luma([((i as u32 * 5000 + x * 3001 + y * 977) % 65536) as u16]));
+                (name.to_string(), DynamicImage::ImageLuma16(layer))
+            })
+            .collect()
+    }
+    
+    #[test]
+    fn exr_keeps_float_values() {
+        let layers = layers();
+        let read = round_trip_layers(&layers, "exr-multichannel").unwrap();
+        for ((_, layer), read) in layers.iter().zip(&read) {
+            let expected = layer.to_rgba32f();
+            assert!(expected.pixels().zip(read.pixels()).all(|(a, b)| a[0] == b[0]));
+        }
+    }
+    
+    #[test]
+    fn eight_bit_layered_formats_round_trip() {
+        let layers = layers();
+        for format in ["psd-layered", "ktx2-array", "tiff-layered"] {
+            let read = round_trip_layers(&layers, format).unwrap();
+            assert_eq!(read.len(), layers.len(), "{}", format);
+            for ((_, layer), read) in layers.iter().zip(&read) {
+                let expected = DynamicImage::ImageLuma8(layer.to_luma8()).to_rgba32f();
+                assert_eq!(&expected, read, "{}", format);
+            }
+        }
+    }
+}
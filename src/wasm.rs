@@ -0,0 +1,102 @@
+// Exports for a browser build, bytes in and bytes out without a filesystem. There's no
+// wasm-bindgen, so JavaScript copies the input into memory from ormsep_alloc, calls ormsep_split
+// and reads the outputs back through the accessors below (see web/index.html)
+use std::cell::RefCell;
+
+use anyhow::{Result, Context};
+
+use crate::{Processor, Profile};
+
+thread_local! {
+    static OUTPUTS: RefCell<Vec<(String, Vec<u8>)>> = const { RefCell::new(Vec::new()) };
+    static ERROR: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ormsep_alloc(len: usize) -> *mut u8 {
+    let mut buffer = Vec::<u8>::with_capacity(len);
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    ptr
+}
+
+/// # Safety
+/// `ptr` and `len` must come from one `ormsep_alloc` call, and the buffer must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ormsep_dealloc(ptr: *mut u8, len: usize) {
+    drop(unsafe { Vec::from_raw_parts(ptr, 0, len) });
+}
+
+/// Split the image in `data`, `name` is its file name (for the format and output names) and
+/// `profile` a profile in config.toml syntax, empty for the default ORM profile. Returns the
+/// number of outputs, or -1 with the message in `ormsep_error`.
+///
+/// # Safety
+/// Each pointer and length pair must describe readable memory, `name` and `profile` UTF-8.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ormsep_split(data: *const u8, data_len: usize, name: *const u8, name_len: usize,
+    profile: *const u8, profile_len: usize) -> i32
+{
+    let split = (|| {
+        let data = unsafe { std::slice::from_raw_parts(data, data_len) };
+        let name = std::str::from_utf8(unsafe { std::slice::from_raw_parts(name, name_len) }).context("name is not UTF-8")?;
+        let profile = std::str::from_utf8(unsafe { std::slice::from_raw_parts(profile, profile_len) }).context("profile is not UTF-8")?;
+        let processor = Processor::builder().profile(parse_profile(profile)?).build()?;
+        processor.split_bytes(data, name)
+    })();
+    
+    match split {
+        Ok(outputs) => {
+            let count = outputs.len() as i32;
+            OUTPUTS.with(|stored| *stored.borrow_mut() = outputs);
+            count
+        }
+        Err(err) => {
+            ERROR.with(|error| *error.borrow_mut() = format!("{:#}", err));
+            OUTPUTS.with(|stored| stored.borrow_mut().clear());
+            -1
+        }
+    }
+}
+
+fn parse_profile(text: &str) -> Result<Profile> {
+    if text.trim().is_empty() {
+        return Ok(Profile::default());
+    }
+    Profile::from_toml(text)
+}
+
+// Pointers into the outputs of the last split, valid until the next call to ormsep_split
+fn output<T>(index: usize, get: impl FnOnce(&(String, Vec<u8>)) -> T, missing: T) -> T {
+    OUTPUTS.with(|stored| stored.borrow().get(index).map_or(missing, get))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ormsep_output_name(index: usize) -> *const u8 {
+    output(index, |(name, _)| name.as_ptr(), std::ptr::null())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ormsep_output_name_len(index: usize) -> usize {
+    output(index, |(name, _)| name.len(), 0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ormsep_output_data(index: usize) -> *const u8 {
+    output(index, |(_, data)| data.as_ptr(), std::ptr::null())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ormsep_output_data_len(index: usize) -> usize {
+    output(index, |(_, data)| data.len(), 0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ormsep_error() -> *const u8 {
+    ERROR.with(|error| error.borrow().as_ptr())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ormsep_error_len() -> usize {
+    ERROR.with(|error| error.borrow().len())
+}